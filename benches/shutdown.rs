@@ -0,0 +1,148 @@
+//! Registration cost and trigger latency for different ways to store hooks.
+//!
+//! Run with `cargo bench --bench shutdown`. The storage variants are:
+//! - `registry`: [`Registry`], boxed hooks behind a mutex with timeouts, observers etc.
+//! - `preallocated`: [`PreallocatedRegistry`], boxed at registration, no allocation at trigger
+//! - `box`: plain `Vec<Box<dyn FnOnce()>>`, the storage of [`OnShutdownCallback`]
+//! - `generic`: a guard that is generic over its closure, no allocation at all
+//! - `fn-pointer`: plain `Vec<fn()>`, like `StaticShutdownHook` and the signal-safe tier
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use simple_on_shutdown::{
+    HookConfig, OnShutdownCallback, PreallocatedRegistry, Registry, ShutdownReason,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Hook counts for the trigger benchmarks.
+const HOOK_COUNTS: [usize; 3] = [1, 16, 256];
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn hook() {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Hook with a capture. Boxing a closure without captures doesn't allocate.
+fn capturing_hook() -> impl FnOnce() + Send + 'static {
+    let calls = &CALLS;
+    move || {
+        calls.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Guard that stores its closure without boxing it.
+struct GenericGuard<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for GenericGuard<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            f()
+        }
+    }
+}
+
+fn registration(c: &mut Criterion) {
+    let mut group = c.benchmark_group("register");
+    group.bench_function("registry", |b| {
+        b.iter_batched_ref(
+            Registry::new,
+            |registry| registry.register(HookConfig::new("hook"), capturing_hook()),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("preallocated", |b| {
+        b.iter_batched_ref(
+            || PreallocatedRegistry::with_capacity(1),
+            |registry| registry.register("hook", capturing_hook()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    // the guards are returned, so running them isn't measured
+    group.bench_function("box", |b| {
+        b.iter_batched(
+            || (),
+            |()| OnShutdownCallback::new(Box::new(capturing_hook())),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("generic", |b| {
+        b.iter_batched(
+            || (),
+            |()| GenericGuard(Some(capturing_hook())),
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("fn-pointer", |b| {
+        b.iter_batched_ref(
+            || Vec::<fn()>::with_capacity(1),
+            |hooks| hooks.push(black_box(hook)),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn trigger(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trigger");
+    for n in HOOK_COUNTS {
+        group.bench_with_input(BenchmarkId::new("registry", n), &n, |b, &n| {
+            b.iter_batched(
+                || {
+                    let registry = Registry::new();
+                    for _ in 0..n {
+                        registry.register(HookConfig::new("hook"), capturing_hook());
+                    }
+                    registry
+                },
+                |registry| registry.run(ShutdownReason::Exit, None),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("preallocated", n), &n, |b, &n| {
+            b.iter_batched(
+                || {
+                    let registry = PreallocatedRegistry::with_capacity(n);
+                    for _ in 0..n {
+                        registry.register("hook", capturing_hook()).unwrap();
+                    }
+                    registry
+                },
+                |registry| registry.run(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("box", n), &n, |b, &n| {
+            b.iter_batched(
+                || {
+                    (0..n)
+                        .map(|_| Box::new(capturing_hook()) as Box<dyn FnOnce()>)
+                        .collect::<OnShutdownCallback>()
+                },
+                drop,
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("generic", n), &n, |b, &n| {
+            b.iter_batched(
+                || {
+                    (0..n)
+                        .map(|_| GenericGuard(Some(capturing_hook())))
+                        .collect::<Vec<_>>()
+                },
+                drop,
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("fn-pointer", n), &n, |b, &n| {
+            b.iter_batched(
+                || vec![hook as fn(); n],
+                |hooks| hooks.iter().rev().for_each(|f| f()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, registration, trigger);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+//! Detects compiler features that are newer than the minimum supported Rust
+//! version, so the crate can use them where they are available.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(has_diagnostic_namespace)");
+    // `#[diagnostic::on_unimplemented]`, Rust 1.78
+    if rustc_minor_version().is_some_and(|minor| minor >= 78) {
+        println!("cargo:rustc-cfg=has_diagnostic_namespace");
+    }
+}
+
+/// Minor version of the compiler, e.g. `74` for `rustc 1.74.1 (...)`.
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    version.split('.').nth(1)?.parse().ok()
+}
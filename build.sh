@@ -1,11 +1,21 @@
 #!/usr/bin/env bash
 
 cargo build --all --all-targets --examples
-cargo test
+cargo test --workspace
+# signal-driven integration tests, they spawn the examples
+cargo test --features signals,testing
 cargo run --example minimal
+# minimum supported Rust version, see `rust-version` in Cargo.toml
+cargo +1.74.0 build --workspace
+cargo +1.74.0 build --features signals,async,testing,child-processes,unsafe-crash-handlers,cli
+# the `minimal` profile must reject every other feature
+cargo check --no-default-features --features minimal
+! cargo check --no-default-features --features minimal,std
 # the other examples need CTRL+C to stop
 
 # test no-std build with some no-std target
 #  but don't build tests here, because std is required for them
 rustup target add thumbv6m-none-eabi
-cargo build --target thumbv6m-none-eabi
+cargo build --target thumbv6m-none-eabi --no-default-features
+cargo build --target thumbv6m-none-eabi --no-default-features --features minimal
+cargo build --target thumbv6m-none-eabi -p simple-on-shutdown-core
@@ -0,0 +1,186 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Anchor that decouples the lifetime of callbacks from the lexical scope they
+//! were declared in.
+
+use crate::OnShutdownCallback;
+#[cfg(not(test))]
+use alloc::{
+    boxed::Box,
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::cell::RefCell;
+#[cfg(test)]
+use std::rc::{Rc, Weak};
+
+/// Callbacks that were attached to an anchor, in attach order.
+type Callbacks = RefCell<Vec<Box<dyn FnOnce()>>>;
+
+/// Created at the top of `main()`. Callbacks and guards attach to it and run
+/// when the anchor drops, in reverse order of attaching. It doesn't matter where
+/// the guards were declared or whereto they were moved in the meantime.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::{OnShutdownCallback, ShutdownAnchor};
+///
+/// fn setup(anchor: &ShutdownAnchor) {
+///     // would run at the end of setup() without the anchor
+///     let guard = OnShutdownCallback::from(|| println!("closing connections"));
+///     anchor.adopt(guard);
+/// }
+///
+/// fn main() {
+///     let anchor = ShutdownAnchor::new();
+///     setup(&anchor);
+///     anchor.attach(|| println!("flushing logs"));
+///     println!("running");
+/// } // prints "flushing logs", then "closing connections"
+/// ```
+pub struct ShutdownAnchor(Rc<Callbacks>);
+
+/// Handle to a [`ShutdownAnchor`] that can be cloned and stored anywhere on the
+/// same thread. Callbacks attached after the anchor dropped run immediately.
+#[derive(Clone)]
+pub struct AnchorHandle(Weak<Callbacks>);
+
+impl ShutdownAnchor {
+    /// Constructor.
+    pub fn new() -> Self {
+        Self(Rc::new(RefCell::new(Vec::new())))
+    }
+
+    /// Attaches a callback that runs when the anchor drops.
+    pub fn attach(&self, cb: impl FnOnce() + 'static) {
+        self.0.borrow_mut().push(Box::new(cb));
+    }
+
+    /// Takes over the callback of `guard`: it runs when the anchor drops instead
+    /// of when the guard drops.
+    pub fn adopt(&self, mut guard: OnShutdownCallback) {
+        if let Some(cb) = guard.0.take() {
+            self.0.borrow_mut().push(cb);
+        }
+    }
+
+    /// Returns a handle that attaches callbacks to this anchor.
+    pub fn handle(&self) -> AnchorHandle {
+        AnchorHandle(Rc::downgrade(&self.0))
+    }
+}
+
+impl AnchorHandle {
+    /// See [`ShutdownAnchor::attach`].
+    pub fn attach(&self, cb: impl FnOnce() + 'static) {
+        match self.0.upgrade() {
+            Some(callbacks) => callbacks.borrow_mut().push(Box::new(cb)),
+            None => cb(),
+        }
+    }
+
+    /// See [`ShutdownAnchor::adopt`].
+    pub fn adopt(&self, mut guard: OnShutdownCallback) {
+        if let Some(cb) = guard.0.take() {
+            self.attach(cb);
+        }
+    }
+}
+
+impl Default for ShutdownAnchor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntoIterator for ShutdownAnchor {
+    type Item = Box<dyn FnOnce()>;
+    type IntoIter = core::iter::Rev<<Vec<Self::Item> as IntoIterator>::IntoIter>;
+
+    /// Detaches all callbacks without running them, in the order the anchor
+    /// would run them. Callbacks attached later through an [`AnchorHandle`]
+    /// run immediately.
+    fn into_iter(self) -> Self::IntoIter {
+        let callbacks = self.0.take();
+        callbacks.into_iter().rev()
+    }
+}
+
+impl Drop for ShutdownAnchor {
+    /// Executes the callbacks in reverse order of attaching.
+    fn drop(&mut self) {
+        // callbacks may attach new callbacks, don't hold the borrow
+        loop {
+            let cb = self.0.borrow_mut().pop();
+            match cb {
+                Some(cb) => cb(),
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_anchor() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let push = |name: &'static str| {
+            let order = order.clone();
+            move || order.borrow_mut().push(name)
+        };
+
+        let anchor = ShutdownAnchor::new();
+        let handle = anchor.handle();
+        {
+            // would run at the end of this block without the anchor
+            handle.adopt(OnShutdownCallback::new(Box::new(push("guard"))));
+        }
+        anchor.attach(push("direct"));
+        assert!(order.borrow().is_empty());
+        drop(anchor);
+        assert_eq!(*order.borrow(), ["direct", "guard"]);
+
+        // the anchor is gone
+        handle.attach(push("late"));
+        assert_eq!(*order.borrow(), ["direct", "guard", "late"]);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let anchor = ShutdownAnchor::new();
+        for name in ["a", "b"] {
+            let order = order.clone();
+            anchor.attach(move || order.borrow_mut().push(name));
+        }
+        let callbacks = anchor.into_iter().collect::<Vec<_>>();
+        assert!(order.borrow().is_empty());
+        callbacks.into_iter().for_each(|cb| cb());
+        assert_eq!(*order.borrow(), ["b", "a"]);
+    }
+}
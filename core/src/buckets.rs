@@ -0,0 +1,169 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Signal-safe hooks in priority buckets whose number and size are fixed at
+//! compile time. For embedded targets that need a deterministic order without
+//! sorting at runtime.
+
+use crate::signal_safe::{CapacityExceeded, SignalSafeHook};
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicPtr<SignalSafeHook> = AtomicPtr::new(ptr::null_mut());
+
+/// `BUCKETS` priorities with up to `CAPACITY` [`SignalSafeHook`]s each. The
+/// priority of a hook is a const generic parameter of [`Self::register`], so an
+/// invalid priority doesn't compile. Hooks of higher priorities run first, hooks
+/// of the same priority in reverse order of their registration. Lock-free like
+/// the global signal-safe tier and usable in statics.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::{SignalSafeBuckets, SignalSafeHook};
+/// use std::sync::atomic::AtomicBool;
+///
+/// const FLUSH_FLASH: usize = 2;
+/// const STATUS_LED: usize = 0;
+///
+/// static HOOKS: SignalSafeBuckets<3, 4> = SignalSafeBuckets::new();
+/// static FLUSHED: AtomicBool = AtomicBool::new(false);
+/// static LED_OFF: AtomicBool = AtomicBool::new(false);
+/// static FLUSH: SignalSafeHook = SignalSafeHook::store_bool(&FLUSHED, true);
+/// static LED: SignalSafeHook = SignalSafeHook::store_bool(&LED_OFF, true);
+///
+/// HOOKS.register::<STATUS_LED>(&LED).unwrap();
+/// HOOKS.register::<FLUSH_FLASH>(&FLUSH).unwrap();
+/// // FLUSH runs before LED
+/// HOOKS.run();
+/// ```
+///
+/// An invalid priority is rejected at compile time:
+/// ```compile_fail
+/// use simple_on_shutdown_core::{SignalSafeBuckets, SignalSafeHook};
+/// use std::sync::atomic::AtomicBool;
+///
+/// static HOOKS: SignalSafeBuckets<3, 4> = SignalSafeBuckets::new();
+/// static FLAG: AtomicBool = AtomicBool::new(false);
+/// static HOOK: SignalSafeHook = SignalSafeHook::store_bool(&FLAG, true);
+///
+/// HOOKS.register::<3>(&HOOK).unwrap();
+/// ```
+pub struct SignalSafeBuckets<const BUCKETS: usize, const CAPACITY: usize> {
+    /// Lock-free slots per priority. A null pointer marks a free slot.
+    buckets: [[AtomicPtr<SignalSafeHook>; CAPACITY]; BUCKETS],
+}
+
+impl<const BUCKETS: usize, const CAPACITY: usize> SignalSafeBuckets<BUCKETS, CAPACITY> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_BUCKET: [AtomicPtr<SignalSafeHook>; CAPACITY] = [EMPTY_SLOT; CAPACITY];
+
+    /// Constructor. Creates empty buckets.
+    pub const fn new() -> Self {
+        Self {
+            buckets: [Self::EMPTY_BUCKET; BUCKETS],
+        }
+    }
+
+    /// Registers a hook with priority `PRIORITY`, which must be less than
+    /// `BUCKETS`. Fails if the bucket is full.
+    pub fn register<const PRIORITY: usize>(
+        &self,
+        hook: &'static SignalSafeHook,
+    ) -> Result<(), CapacityExceeded> {
+        let () = PriorityInRange::<PRIORITY, BUCKETS>::OK;
+        let hook = hook as *const SignalSafeHook as *mut SignalSafeHook;
+        self.buckets[PRIORITY]
+            .iter()
+            .find(|slot| {
+                slot.compare_exchange(ptr::null_mut(), hook, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+            })
+            .map(|_| ())
+            .ok_or(CapacityExceeded)
+    }
+
+    /// Executes all hooks, the highest priority first, and unregisters them, so
+    /// every hook runs at most once. This is async-signal-safe and can be called
+    /// from inside a signal or interrupt handler.
+    pub fn run(&self) {
+        for slot in self.buckets.iter().rev().flat_map(|b| b.iter().rev()) {
+            let hook = slot.swap(ptr::null_mut(), Ordering::SeqCst);
+            // SAFETY: only `&'static SignalSafeHook` are stored in the slots
+            if let Some(hook) = unsafe { hook.as_ref() } {
+                hook.execute();
+            }
+        }
+    }
+}
+
+/// Fails the build if a priority doesn't fit into the buckets, see
+/// [`SignalSafeBuckets::register`].
+struct PriorityInRange<const PRIORITY: usize, const BUCKETS: usize>;
+
+impl<const PRIORITY: usize, const BUCKETS: usize> PriorityInRange<PRIORITY, BUCKETS> {
+    /// Evaluated when `register` is instantiated with these parameters.
+    const OK: () = assert!(PRIORITY < BUCKETS, "priority out of range");
+}
+
+impl<const BUCKETS: usize, const CAPACITY: usize> Default for SignalSafeBuckets<BUCKETS, CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    static ORDER: AtomicUsize = AtomicUsize::new(0);
+    static FIRST: AtomicUsize = AtomicUsize::new(0);
+    static SECOND: AtomicUsize = AtomicUsize::new(0);
+    static THIRD: AtomicUsize = AtomicUsize::new(0);
+
+    fn record(target: &AtomicUsize) {
+        target.store(ORDER.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+    }
+
+    // SAFETY: the functions only access atomics
+    static LOW: SignalSafeHook = unsafe { SignalSafeHook::from_fn(|| record(&THIRD)) };
+    static HIGH_OLD: SignalSafeHook = unsafe { SignalSafeHook::from_fn(|| record(&SECOND)) };
+    static HIGH_NEW: SignalSafeHook = unsafe { SignalSafeHook::from_fn(|| record(&FIRST)) };
+
+    #[test]
+    fn test_buckets_run_by_priority() {
+        let buckets = SignalSafeBuckets::<2, 2>::new();
+        buckets.register::<0>(&LOW).unwrap();
+        buckets.register::<1>(&HIGH_OLD).unwrap();
+        buckets.register::<1>(&HIGH_NEW).unwrap();
+        assert_eq!(buckets.register::<1>(&LOW), Err(CapacityExceeded));
+
+        buckets.run();
+        let order = [&FIRST, &SECOND, &THIRD].map(|a| a.load(Ordering::SeqCst));
+        assert_eq!(order, [1, 2, 3]);
+        // hooks run at most once
+        buckets.run();
+        assert_eq!(ORDER.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,175 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Types of [`simple_on_shutdown`](https://docs.rs/simple_on_shutdown) that work without `std`:
+//! the guard [`OnShutdownCallback`] (also with multiple owners, see [`SharedShutdownCallback`]
+//! and [`SyncShutdownCallback`]),
+//! the [`ShutdownAnchor`] and the tier of async-signal-safe hooks ([`SignalSafeHook`],
+//! [`SignalSafeBuckets`], [`ShutdownTrigger`], [`WatchdogFeeder`]).
+//!
+//! This crate has no dependencies apart from the optional `scopeguard` (feature `scopeguard`).
+//! Embedded users depend on it directly and don't pull any code that is related to `std`.
+//! Everybody else uses `simple_on_shutdown`, which re-exports all of it together with the
+//! registry, the macros and the integrations.
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(not(test))]
+extern crate alloc;
+#[cfg(not(test))]
+use alloc::boxed::Box;
+#[cfg(not(test))]
+use alloc::vec::Vec;
+
+mod anchor;
+#[cfg(target_has_atomic = "ptr")]
+mod buckets;
+#[cfg(feature = "scopeguard")]
+mod scopeguard_compat;
+mod shared;
+#[cfg(target_has_atomic = "ptr")]
+mod signal_safe;
+#[cfg(target_has_atomic = "ptr")]
+mod trigger;
+#[cfg(target_has_atomic = "ptr")]
+mod watchdog;
+
+pub use anchor::{AnchorHandle, ShutdownAnchor};
+#[cfg(target_has_atomic = "ptr")]
+pub use buckets::SignalSafeBuckets;
+pub use shared::SharedShutdownCallback;
+#[cfg(target_has_atomic = "ptr")]
+pub use shared::SyncShutdownCallback;
+#[cfg(target_has_atomic = "ptr")]
+pub use signal_safe::{
+    panic_shutdown, register_signal_safe, run_signal_safe_hooks, CapacityExceeded, SignalSafeHook,
+    SIGNAL_SAFE_CAPACITY,
+};
+#[cfg(target_has_atomic = "ptr")]
+pub use trigger::ShutdownTrigger;
+#[cfg(target_has_atomic = "ptr")]
+pub use watchdog::WatchdogFeeder;
+
+/// Guard created by `on_shutdown!` and `on_shutdown_expr!` of `simple_on_shutdown`. Only name
+/// this type, e.g. as a struct field, when using `on_shutdown_expr!`.
+///
+/// Simple type that holds a `FnOnce`-closure (callback). The `FnOnce`-closure gets invoked during `drop()`.
+/// This works also fine with applications that do gracefully shutdown via signals, like SIGTERM.
+pub struct OnShutdownCallback(Option<Box<dyn FnOnce()>>);
+
+impl OnShutdownCallback {
+    /// Constructor. Used by `on_shutdown!`.
+    ///
+    /// ## Parameters
+    /// * `cb` boxed(heap) callback function
+    ///
+    // THIS MUST BE PUBLIC, OTHERWISE THE MACROS DO NOT WORK!
+    pub fn new(cb: Box<dyn FnOnce()>) -> Self {
+        Self(Some(cb))
+    }
+}
+
+impl core::iter::FromIterator<Box<dyn FnOnce()>> for OnShutdownCallback {
+    /// Creates one guard that runs all callbacks in reverse order, like multiple
+    /// `on_shutdown!` invocations in one scope would. Useful if the cleanup steps
+    /// are collected dynamically before a single guard is armed.
+    fn from_iter<I: IntoIterator<Item = Box<dyn FnOnce()>>>(iter: I) -> Self {
+        let callbacks: Vec<_> = iter.into_iter().collect();
+        Self::new(Box::new(move || {
+            for cb in callbacks.into_iter().rev() {
+                cb();
+            }
+        }))
+    }
+}
+
+impl<F: FnOnce() + 'static> From<F> for OnShutdownCallback {
+    /// Arms a guard for `f`, like `on_shutdown_expr!` does. Also accepts
+    /// `Box<dyn FnOnce()>`, so generic code can take `impl Into<OnShutdownCallback>`.
+    fn from(f: F) -> Self {
+        Self::new(Box::new(f))
+    }
+}
+
+impl From<OnShutdownCallback> for Box<dyn FnOnce()> {
+    /// Disarms the guard and returns its callback, e.g. to hand it over to other
+    /// RAII types. The callback does nothing if a [`ShutdownAnchor`] adopted it.
+    fn from(mut guard: OnShutdownCallback) -> Self {
+        guard.0.take().unwrap_or_else(|| Box::new(|| {}))
+    }
+}
+
+impl Drop for OnShutdownCallback {
+    /// Executes the specified callback.
+    fn drop(&mut self) {
+        // take(): because I use a FnOnce here, I need to own the value
+        // in order for it to get executed. It's gone if a ShutdownAnchor
+        // adopted the callback.
+        if let Some(cb) = self.0.take() {
+            cb();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_from_iter() {
+        use super::OnShutdownCallback;
+        use std::sync::Mutex;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let callbacks = (0..3).map(|i| {
+            let order = order.clone();
+            Box::new(move || order.lock().unwrap().push(i)) as Box<dyn FnOnce()>
+        });
+        let guard = callbacks.collect::<OnShutdownCallback>();
+        assert!(order.lock().unwrap().is_empty());
+        drop(guard);
+        assert_eq!(*order.lock().unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_from() {
+        use super::OnShutdownCallback;
+
+        let called = Arc::new(AtomicBool::new(false));
+        let called_c = called.clone();
+        let boxed: Box<dyn FnOnce()> = Box::new(move || called_c.store(true, Ordering::Relaxed));
+        let guard = OnShutdownCallback::from(boxed);
+        // disarmed, the callback only runs when called
+        let cb: Box<dyn FnOnce()> = guard.into();
+        assert!(!called.load(Ordering::Relaxed));
+        cb();
+        assert!(called.load(Ordering::Relaxed));
+
+        let called_c = called.clone();
+        let guard: OnShutdownCallback = (move || called_c.store(false, Ordering::Relaxed)).into();
+        drop(guard);
+        assert!(!called.load(Ordering::Relaxed));
+    }
+}
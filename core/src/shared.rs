@@ -0,0 +1,223 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Guards with multiple owners, see [`OnShutdownCallback::into_shared`] and
+//! [`SyncShutdownCallback`].
+
+use crate::OnShutdownCallback;
+#[cfg(not(test))]
+use alloc::boxed::Box;
+#[cfg(not(test))]
+use alloc::rc::Rc;
+#[cfg(all(not(test), target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+use core::cell::RefCell;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(test)]
+use std::rc::Rc;
+#[cfg(test)]
+use std::sync::Arc;
+
+/// Cloneable form of an [`OnShutdownCallback`]. The callback runs exactly once:
+/// when the last handle drops or when any handle calls [`Self::trigger`],
+/// whatever happens first. The handles stay on one thread, see
+/// [`SyncShutdownCallback`] for handles that can be shared between threads.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::OnShutdownCallback;
+///
+/// let shared = OnShutdownCallback::from(|| println!("connection closed")).into_shared();
+/// let reader = shared.clone();
+/// let writer = shared;
+/// drop(reader);
+/// // prints "connection closed"
+/// drop(writer);
+/// ```
+#[derive(Clone)]
+pub struct SharedShutdownCallback(Rc<RefCell<OnShutdownCallback>>);
+
+impl OnShutdownCallback {
+    /// Turns the guard into a [`SharedShutdownCallback`] that can be cloned, e.g.
+    /// for the reading and the writing half of a connection.
+    pub fn into_shared(self) -> SharedShutdownCallback {
+        SharedShutdownCallback(Rc::new(RefCell::new(self)))
+    }
+}
+
+impl SharedShutdownCallback {
+    /// Runs the callback now, unless it already ran. The other handles stay
+    /// valid but won't run it again.
+    pub fn trigger(&self) {
+        // released before the callback runs, it may use the handles itself
+        let cb = self.0.borrow_mut().0.take();
+        if let Some(cb) = cb {
+            cb();
+        }
+    }
+
+    /// Whether the callback already ran (or a `ShutdownAnchor` adopted the
+    /// guard before it became shared).
+    pub fn is_triggered(&self) -> bool {
+        self.0.borrow().0.is_none()
+    }
+}
+
+/// Callback of a [`SyncShutdownCallback`]. Boxed twice, so it fits into an
+/// [`AtomicPtr`].
+#[cfg(target_has_atomic = "ptr")]
+type SendCallback = Box<dyn FnOnce() + Send>;
+
+/// Like [`SharedShutdownCallback`], but the handles can be sent to and shared
+/// between threads, e.g. the workers of a connection pool. Replaces
+/// `Arc<Mutex<Option<Box<dyn FnOnce()>>>>`. The callback runs exactly once:
+/// when the last handle drops or when any handle calls [`Self::trigger`],
+/// whatever happens first, on that thread.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::SyncShutdownCallback;
+///
+/// let shared = SyncShutdownCallback::new(|| println!("pool closed"));
+/// let worker = shared.clone();
+/// std::thread::spawn(move || drop(worker)).join().unwrap();
+/// // prints "pool closed"
+/// drop(shared);
+/// ```
+#[cfg(target_has_atomic = "ptr")]
+#[derive(Clone)]
+pub struct SyncShutdownCallback(Arc<SyncCallback>);
+
+/// Owned by all handles of a [`SyncShutdownCallback`]. Null once the callback
+/// was taken.
+#[cfg(target_has_atomic = "ptr")]
+struct SyncCallback(AtomicPtr<SendCallback>);
+
+#[cfg(target_has_atomic = "ptr")]
+impl SyncCallback {
+    /// Takes the callback, at most one caller gets it.
+    fn take(&self) -> Option<SendCallback> {
+        let cb = self.0.swap(core::ptr::null_mut(), Ordering::AcqRel);
+        if cb.is_null() {
+            None
+        } else {
+            // SAFETY: the pointer comes from `Box::into_raw` and the swap hands it
+            // out only once
+            Some(*unsafe { Box::from_raw(cb) })
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl Drop for SyncCallback {
+    fn drop(&mut self) {
+        if let Some(cb) = self.take() {
+            cb();
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl SyncShutdownCallback {
+    /// Constructor. `cb` runs when the last handle drops, see [`Self::trigger`].
+    pub fn new<F: FnOnce() + Send + 'static>(cb: F) -> Self {
+        let cb: SendCallback = Box::new(cb);
+        Self(Arc::new(SyncCallback(AtomicPtr::new(Box::into_raw(
+            Box::new(cb),
+        )))))
+    }
+
+    /// Runs the callback now, unless it already ran. The other handles stay
+    /// valid but won't run it again.
+    pub fn trigger(&self) {
+        if let Some(cb) = self.0.take() {
+            cb();
+        }
+    }
+
+    /// Whether the callback already ran or is running.
+    pub fn is_triggered(&self) -> bool {
+        self.0 .0.load(Ordering::Acquire).is_null()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_runs_once() {
+        let calls = Rc::new(Cell::new(0));
+        let calls_c = calls.clone();
+        let a = OnShutdownCallback::from(move || calls_c.set(calls_c.get() + 1)).into_shared();
+        let b = a.clone();
+        drop(a);
+        assert_eq!(calls.get(), 0);
+        drop(b);
+        assert_eq!(calls.get(), 1);
+
+        let calls_c = calls.clone();
+        let a = OnShutdownCallback::from(move || calls_c.set(calls_c.get() + 1)).into_shared();
+        let b = a.clone();
+        b.trigger();
+        assert!(a.is_triggered());
+        b.trigger();
+        drop((a, b));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_sync_runs_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_c = calls.clone();
+        let shared = SyncShutdownCallback::new(move || {
+            calls_c.fetch_add(1, Ordering::SeqCst);
+        });
+        let workers = (0..4)
+            .map(|_| {
+                let handle = shared.clone();
+                std::thread::spawn(move || handle.trigger())
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert!(shared.is_triggered());
+        drop(shared);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls_c = calls.clone();
+        let shared = SyncShutdownCallback::new(move || {
+            calls_c.fetch_add(1, Ordering::SeqCst);
+        });
+        let handle = shared.clone();
+        std::thread::spawn(move || drop(handle)).join().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        drop(shared);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,238 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Restricted tier of hooks that only perform async-signal-safe operations.
+//!
+//! Normal hooks may allocate, take locks and do I/O. That is forbidden inside a
+//! signal handler for fatal signals like `SIGSEGV` or `SIGABRT`. Hooks of this
+//! tier are plain data that describe a single async-signal-safe operation. They
+//! live in statics and are stored in a fixed number of lock-free slots, so
+//! [`run_signal_safe_hooks`] can be invoked from inside a signal handler.
+
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/// Maximum number of hooks that can be registered with
+/// [`register_signal_safe`].
+pub const SIGNAL_SAFE_CAPACITY: usize = 16;
+
+/// A hook that is allowed to run inside a signal handler. Construct it with one
+/// of the `const` constructors and put it into a `static`.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::{register_signal_safe, SignalSafeHook};
+/// use std::sync::atomic::AtomicBool;
+///
+/// static CRASHED: AtomicBool = AtomicBool::new(false);
+/// static SET_CRASHED: SignalSafeHook = SignalSafeHook::store_bool(&CRASHED, true);
+///
+/// register_signal_safe(&SET_CRASHED).unwrap();
+/// ```
+pub struct SignalSafeHook(Action);
+
+enum Action {
+    #[cfg(unix)]
+    WriteFd {
+        fd: i32,
+        msg: &'static [u8],
+    },
+    StoreBool {
+        flag: &'static AtomicBool,
+        value: bool,
+    },
+    StoreUsize {
+        target: &'static AtomicUsize,
+        value: usize,
+    },
+    Fn(fn()),
+}
+
+impl SignalSafeHook {
+    /// Writes `msg` to the already opened file descriptor `fd`, e.g. a crash
+    /// marker file or `2` for stderr.
+    #[cfg(unix)]
+    pub const fn write_fd(fd: i32, msg: &'static [u8]) -> Self {
+        Self(Action::WriteFd { fd, msg })
+    }
+
+    /// Stores `value` into `flag`.
+    pub const fn store_bool(flag: &'static AtomicBool, value: bool) -> Self {
+        Self(Action::StoreBool { flag, value })
+    }
+
+    /// Stores `value` into `target`.
+    pub const fn store_usize(target: &'static AtomicUsize, value: usize) -> Self {
+        Self(Action::StoreUsize { target, value })
+    }
+
+    /// Calls `f`.
+    ///
+    /// # Safety
+    /// `f` must only perform async-signal-safe operations: no allocations, no
+    /// locks, no buffered I/O and no panics.
+    pub const unsafe fn from_fn(f: fn()) -> Self {
+        Self(Action::Fn(f))
+    }
+
+    /// Performs the operation. This is async-signal-safe.
+    pub(crate) fn execute(&self) {
+        match self.0 {
+            #[cfg(unix)]
+            Action::WriteFd { fd, msg } => write_all(fd, msg),
+            Action::StoreBool { flag, value } => flag.store(value, Ordering::SeqCst),
+            Action::StoreUsize { target, value } => target.store(value, Ordering::SeqCst),
+            Action::Fn(f) => f(),
+        }
+    }
+}
+
+impl fmt::Debug for SignalSafeHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.0 {
+            #[cfg(unix)]
+            Action::WriteFd { .. } => "WriteFd",
+            Action::StoreBool { .. } => "StoreBool",
+            Action::StoreUsize { .. } => "StoreUsize",
+            Action::Fn(_) => "Fn",
+        };
+        f.debug_tuple("SignalSafeHook").field(&kind).finish()
+    }
+}
+
+/// Error of [`register_signal_safe`] and [`crate::SignalSafeBuckets::register`]:
+/// all slots are taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityExceeded;
+
+impl fmt::Display for CapacityExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "all signal-safe hook slots are taken")
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicPtr<SignalSafeHook> = AtomicPtr::new(ptr::null_mut());
+
+/// Lock-free slots. A null pointer marks a free slot.
+static SLOTS: [AtomicPtr<SignalSafeHook>; SIGNAL_SAFE_CAPACITY] =
+    [EMPTY_SLOT; SIGNAL_SAFE_CAPACITY];
+
+/// Registers a hook of the signal-safe tier. It is executed by
+/// [`run_signal_safe_hooks`].
+pub fn register_signal_safe(hook: &'static SignalSafeHook) -> Result<(), CapacityExceeded> {
+    let hook = hook as *const SignalSafeHook as *mut SignalSafeHook;
+    for slot in SLOTS.iter() {
+        if slot
+            .compare_exchange(ptr::null_mut(), hook, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    Err(CapacityExceeded)
+}
+
+/// Executes all hooks of the signal-safe tier in reverse order of their
+/// registration and unregisters them, so every hook runs at most once. This is
+/// async-signal-safe and can be called from inside a signal handler.
+pub fn run_signal_safe_hooks() {
+    for slot in SLOTS.iter().rev() {
+        let hook = slot.swap(ptr::null_mut(), Ordering::SeqCst);
+        // SAFETY: only `&'static SignalSafeHook` are stored in the slots
+        if let Some(hook) = unsafe { hook.as_ref() } {
+            hook.execute();
+        }
+    }
+}
+
+/// Runs the signal-safe tier from a `#[panic_handler]` and calls `halt`
+/// afterwards, e.g. to reset the MCU or to spin until a watchdog fires. For
+/// `no_std` targets that have no other way to run hooks on a panic.
+///
+/// If a hook panics itself, the nested panic handler continues with the
+/// remaining hooks; every hook runs at most once.
+///
+/// ## Example
+/// ```ignore
+/// #[panic_handler]
+/// fn panic(info: &core::panic::PanicInfo) -> ! {
+///     // e.g. write `info` to the UART
+///     simple_on_shutdown_core::panic_shutdown(cortex_m::peripheral::SCB::sys_reset)
+/// }
+/// ```
+pub fn panic_shutdown(halt: fn() -> !) -> ! {
+    run_signal_safe_hooks();
+    halt()
+}
+
+/// Writes all bytes with the `write` syscall. Errors are ignored, there is
+/// nothing sensible to do about them inside a signal handler.
+#[cfg(unix)]
+fn write_all(fd: i32, mut msg: &[u8]) {
+    extern "C" {
+        fn write(fd: i32, buf: *const u8, count: usize) -> isize;
+    }
+    while !msg.is_empty() {
+        // SAFETY: the buffer is valid for `msg.len()` bytes
+        let written = unsafe { write(fd, msg.as_ptr(), msg.len()) };
+        if written <= 0 {
+            return;
+        }
+        msg = &msg[written as usize..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static FLAG: AtomicBool = AtomicBool::new(false);
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    static SET_FLAG: SignalSafeHook = SignalSafeHook::store_bool(&FLAG, true);
+    static SET_COUNTER: SignalSafeHook = SignalSafeHook::store_usize(&COUNTER, 42);
+
+    #[test]
+    fn test_signal_safe_hooks() {
+        register_signal_safe(&SET_FLAG).unwrap();
+        register_signal_safe(&SET_COUNTER).unwrap();
+        run_signal_safe_hooks();
+        assert!(FLAG.load(Ordering::SeqCst));
+        assert_eq!(COUNTER.load(Ordering::SeqCst), 42);
+    }
+
+    static PANICKED: AtomicBool = AtomicBool::new(false);
+    static SET_PANICKED: SignalSafeHook = SignalSafeHook::store_bool(&PANICKED, true);
+
+    #[test]
+    fn test_panic_shutdown_runs_hooks_before_halt() {
+        register_signal_safe(&SET_PANICKED).unwrap();
+        fn halt() -> ! {
+            assert!(PANICKED.load(Ordering::SeqCst));
+            panic!("halted")
+        }
+        let halted = std::panic::catch_unwind(|| panic_shutdown(halt));
+        assert!(halted.is_err());
+    }
+}
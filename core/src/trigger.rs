@@ -0,0 +1,128 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Lock-free shutdown request for bare-metal applications, e.g. with RTIC.
+//!
+//! An interrupt handler can't run a long cleanup sequence: it would block other
+//! interrupts and can't wait for flash or peripherals. It only requests the
+//! shutdown, the idle task (or any other low-priority context) runs the hooks.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const IDLE: usize = 0;
+const REQUESTED: usize = 1;
+const RUNNING: usize = 2;
+const DONE: usize = 3;
+
+/// Shutdown request that is set from an interrupt handler and served from the
+/// idle task. Both sides are lock-free and never block, so they don't need an
+/// RTIC resource lock. Use it in a `static`.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::{SignalSafeBuckets, SignalSafeHook, ShutdownTrigger};
+/// use std::sync::atomic::AtomicBool;
+///
+/// static POWER_FAIL: ShutdownTrigger = ShutdownTrigger::new();
+/// static HOOKS: SignalSafeBuckets<2, 4> = SignalSafeBuckets::new();
+/// static FLUSHED: AtomicBool = AtomicBool::new(false);
+/// static FLUSH: SignalSafeHook = SignalSafeHook::store_bool(&FLUSHED, true);
+///
+/// HOOKS.register::<1>(&FLUSH).unwrap();
+///
+/// // in the power-fail interrupt handler
+/// POWER_FAIL.request();
+///
+/// // in the idle loop
+/// if POWER_FAIL.run_if_requested(|| HOOKS.run()) {
+///     // hooks are done, e.g. halt until the power is gone
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ShutdownTrigger(AtomicUsize);
+
+impl ShutdownTrigger {
+    /// Constructor. Can be used in statics.
+    pub const fn new() -> Self {
+        Self(AtomicUsize::new(IDLE))
+    }
+
+    /// Requests the shutdown. Can be called from interrupt handlers. Returns
+    /// `false` if the shutdown was already requested before.
+    pub fn request(&self) -> bool {
+        self.0
+            .compare_exchange(IDLE, REQUESTED, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// Whether the shutdown was requested, no matter if the hooks already ran.
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst) != IDLE
+    }
+
+    /// Whether the hooks ran completely.
+    pub fn is_done(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == DONE
+    }
+
+    /// Calls `hooks` if the shutdown was requested and they didn't run yet.
+    /// Returns whether they were called. Call this from the idle task; `hooks`
+    /// runs at most once, even if this is called from several contexts.
+    pub fn run_if_requested<F: FnOnce()>(&self, hooks: F) -> bool {
+        if self
+            .0
+            .compare_exchange(REQUESTED, RUNNING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return false;
+        }
+        hooks();
+        self.0.store(DONE, Ordering::SeqCst);
+        true
+    }
+}
+
+impl Default for ShutdownTrigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_run_once_after_request() {
+        let trigger = ShutdownTrigger::new();
+        let mut runs = 0;
+        assert!(!trigger.run_if_requested(|| runs += 1));
+        assert!(trigger.request());
+        assert!(!trigger.request());
+        assert!(trigger.is_requested() && !trigger.is_done());
+        assert!(trigger.run_if_requested(|| runs += 1));
+        assert!(!trigger.run_if_requested(|| runs += 1));
+        assert!(trigger.is_done());
+        assert_eq!(runs, 1);
+    }
+}
@@ -0,0 +1,101 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Resets of bare-metal applications through the hardware watchdog.
+//!
+//! Many MCUs restart by letting the watchdog expire. The firmware keeps feeding
+//! it while it works and stops once the cleanup is done, so the reset happens
+//! only after the last hook.
+
+use crate::SignalSafeHook;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Feeds a hardware watchdog until the shutdown hooks are done. Use it in a
+/// `static`, route all feeding through [`Self::feed`] and register
+/// [`Self::suspend_hook`] so that it runs last, i.e. in the lowest bucket of a
+/// [`crate::SignalSafeBuckets`] or as the first hook of the global signal-safe
+/// tier.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown_core::{SignalSafeBuckets, SignalSafeHook, WatchdogFeeder};
+///
+/// static WATCHDOG: WatchdogFeeder = WatchdogFeeder::new();
+/// static SUSPEND_FEEDING: SignalSafeHook = WATCHDOG.suspend_hook();
+/// static HOOKS: SignalSafeBuckets<2, 4> = SignalSafeBuckets::new();
+///
+/// // lowest priority, runs after all other hooks
+/// HOOKS.register::<0>(&SUSPEND_FEEDING).unwrap();
+///
+/// // main loop
+/// WATCHDOG.feed(|| { /* e.g. iwdg.feed() */ });
+///
+/// HOOKS.run();
+/// // the watchdog isn't fed anymore and resets the MCU
+/// assert!(!WATCHDOG.feed(|| unreachable!()));
+/// ```
+#[derive(Debug)]
+pub struct WatchdogFeeder {
+    suspended: AtomicBool,
+}
+
+impl WatchdogFeeder {
+    /// Constructor. Can be used in statics.
+    pub const fn new() -> Self {
+        Self {
+            suspended: AtomicBool::new(false),
+        }
+    }
+
+    /// Calls `feed`, which feeds the hardware watchdog, unless feeding was
+    /// suspended. Returns whether `feed` was called.
+    pub fn feed<F: FnOnce()>(&self, feed: F) -> bool {
+        if self.is_suspended() {
+            return false;
+        }
+        feed();
+        true
+    }
+
+    /// Stops feeding the watchdog for good. Lock-free, can be called from
+    /// interrupt handlers.
+    pub fn suspend(&self) {
+        self.suspended.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether feeding was suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::SeqCst)
+    }
+
+    /// Signal-safe hook that calls [`Self::suspend`].
+    pub const fn suspend_hook(&'static self) -> SignalSafeHook {
+        SignalSafeHook::store_bool(&self.suspended, true)
+    }
+}
+
+impl Default for WatchdogFeeder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,54 @@
+//! Registers hooks in the global registry and waits for `SIGINT`/`SIGTERM`. The hooks run
+//! on a dedicated thread before the process exits. A reload hook runs on every `SIGHUP`,
+//! a diagnostic hook on every `SIGUSR1`. With `SLOW_HOOK` set, a hook
+//! blocks the shutdown until a second signal forces the exit. With `LOG_REPEATED_SIGNALS`
+//! set, further signals are only logged. With `EXIT_CODE` set, the example doesn't wait but
+//! calls `exit()` with that code right away.
+//! Used by `tests/signals.rs`.
+//!
+//! Run with `cargo run --example signal_hooks --features signals` and press CTRL+C.
+
+use simple_on_shutdown::{
+    global, install, install_diagnostic_handlers, install_reload_handler,
+    set_repeated_signal_action, Event, HookConfig, RepeatedSignalAction,
+};
+use std::thread::sleep;
+use std::time::Duration;
+
+fn main() {
+    install().unwrap();
+    install_reload_handler().unwrap();
+    install_diagnostic_handlers().unwrap();
+    if std::env::var_os("LOG_REPEATED_SIGNALS").is_some() {
+        set_repeated_signal_action(RepeatedSignalAction::Log);
+    }
+    global().register_event(Event::User1, HookConfig::new("dump state"), || {
+        println!("state: {} hooks", global().len());
+    });
+    let mut reloads = 0;
+    global().register_event(Event::Reload, HookConfig::new("reload"), move || {
+        reloads += 1;
+        println!("reload {}", reloads);
+    });
+    global().register_with_context(HookConfig::new("flush"), |ctx| {
+        println!("flush hook ran: {:?}", ctx.reason());
+    });
+    if std::env::var_os("SLOW_HOOK").is_some() {
+        global().register(HookConfig::new("slow"), || {
+            println!("slow hook started");
+            sleep(Duration::from_secs(60));
+        });
+    }
+    if let Some(code) = std::env::var_os("EXIT_CODE") {
+        global().register(HookConfig::new("pause"), || {
+            println!("exit hooks started");
+            sleep(Duration::from_millis(500));
+        });
+        println!("ready");
+        simple_on_shutdown::exit(code.to_str().unwrap().parse().unwrap());
+    }
+    println!("ready");
+    loop {
+        sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,261 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Compile-time checks for hook closures, see [`crate::assert_shutdown_safe`]
+//! and [`crate::on_shutdown_sized`].
+//!
+//! Each registration mode has a trait that is implemented for all closures that
+//! fit. A mismatch is reported with the mode and the reason instead of a raw
+//! trait-bound failure deep inside a generic function. Compilers older than
+//! Rust 1.78 report the raw trait bound, see the table of
+//! [`crate::assert_shutdown_safe`].
+
+/// Closures that can be used with [`crate::on_shutdown`] and
+/// [`crate::OnShutdownCallback`].
+#[cfg_attr(
+    has_diagnostic_namespace,
+    diagnostic::on_unimplemented(
+        message = "`{Self}` can't be used as an `on_shutdown!` callback",
+        label = "not a `FnOnce() + 'static` closure",
+        note = "the callback is boxed; it must not take arguments or borrow from the enclosing scope"
+    )
+)]
+pub trait GuardCallback {}
+
+impl<F: FnOnce() + 'static> GuardCallback for F {}
+
+/// Closures that can be used with `Registry::register`.
+#[cfg(feature = "std")]
+#[cfg_attr(
+    has_diagnostic_namespace,
+    diagnostic::on_unimplemented(
+        message = "`{Self}` can't be registered as a shutdown hook",
+        label = "not a `FnOnce() + Send + 'static` closure",
+        note = "hooks may run on another thread after the current scope was left; use `move` \
+                and `Arc` instead of references and `Rc`"
+    )
+)]
+pub trait RegistryCallback {}
+
+#[cfg(feature = "std")]
+impl<F: FnOnce() + Send + 'static> RegistryCallback for F {}
+
+/// Closures that can be used with `Registry::register_with_context`.
+#[cfg(feature = "std")]
+#[cfg_attr(
+    has_diagnostic_namespace,
+    diagnostic::on_unimplemented(
+        message = "`{Self}` can't be registered as a context-aware shutdown hook",
+        label = "not a `FnOnce(&ShutdownContext) + Send + 'static` closure",
+        note = "hooks may run on another thread after the current scope was left; use `move` \
+                and `Arc` instead of references and `Rc`"
+    )
+)]
+pub trait ContextCallback {}
+
+#[cfg(feature = "std")]
+impl<F: FnOnce(&crate::ShutdownContext) + Send + 'static> ContextCallback for F {}
+
+/// Closures that can be used with `Registry::register_event`.
+#[cfg(feature = "std")]
+#[cfg_attr(
+    has_diagnostic_namespace,
+    diagnostic::on_unimplemented(
+        message = "`{Self}` can't be registered as an event hook",
+        label = "not a `FnMut() + Send + 'static` closure",
+        note = "event hooks run on every emission, so they can't consume their captures"
+    )
+)]
+pub trait EventCallback {}
+
+#[cfg(feature = "std")]
+impl<F: FnMut() + Send + 'static> EventCallback for F {}
+
+/// PRIVATE! Used by [`crate::assert_shutdown_safe`].
+#[doc(hidden)]
+pub const fn assert_guard<F: GuardCallback>(_: &F) {}
+
+/// PRIVATE! Used by [`crate::assert_shutdown_safe`].
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub const fn assert_registry<F: RegistryCallback>(_: &F) {}
+
+/// PRIVATE! Used by [`crate::assert_shutdown_safe`].
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub const fn assert_context<F: ContextCallback>(_: &F) {}
+
+/// PRIVATE! Used by [`crate::assert_shutdown_safe`].
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub const fn assert_event<F: EventCallback>(_: &F) {}
+
+/// Verifies at compile time that a closure fits a registration mode. The closure
+/// is only borrowed, so pass it by name and register it afterwards. Without a
+/// mode, `registry` is checked.
+///
+/// | Mode       | Used with                         | Bound                                        |
+/// |------------|-----------------------------------|----------------------------------------------|
+/// | `guard`    | `on_shutdown!`                    | `FnOnce() + 'static`                         |
+/// | `registry` | `Registry::register`              | `FnOnce() + Send + 'static`                  |
+/// | `context`  | `Registry::register_with_context` | `FnOnce(&ShutdownContext) + Send + 'static`  |
+/// | `event`    | `Registry::register_event`        | `FnMut() + Send + 'static`                   |
+///
+/// Whether a closure blocks can't be checked by the compiler; give such hooks a
+/// timeout instead.
+///
+/// ## Example
+/// ```rust
+/// use simple_on_shutdown::{assert_shutdown_safe, global, HookConfig};
+/// use std::sync::Arc;
+///
+/// let state = Arc::new(42);
+/// let flush = move || println!("flushing {}", state);
+/// assert_shutdown_safe!(flush);
+/// global().register(HookConfig::new("flush"), flush);
+/// ```
+///
+/// A closure that captures an `Rc` is rejected with an explanation:
+/// ```compile_fail
+/// use simple_on_shutdown::assert_shutdown_safe;
+/// use std::rc::Rc;
+///
+/// let state = Rc::new(42);
+/// let flush = move || println!("flushing {}", state);
+/// assert_shutdown_safe!(registry: flush);
+/// ```
+#[macro_export]
+macro_rules! assert_shutdown_safe {
+    (guard: $closure:expr) => {
+        $crate::__private::assert_guard(&$closure)
+    };
+    (registry: $closure:expr) => {
+        $crate::__private::assert_registry(&$closure)
+    };
+    (context: $closure:expr) => {
+        $crate::__private::assert_context(&$closure)
+    };
+    (event: $closure:expr) => {
+        $crate::__private::assert_event(&$closure)
+    };
+    ($closure:expr) => {
+        $crate::assert_shutdown_safe!(registry: $closure)
+    };
+}
+
+/// Returns `f` if its captures take at most `MAX` bytes, fails the build
+/// otherwise. Used by [`crate::on_shutdown_sized`].
+#[doc(hidden)]
+pub const fn assert_capture_size<const MAX: usize, F: FnOnce() + 'static>(f: F) -> F {
+    let () = CaptureSize::<MAX, F>::OK;
+    f
+}
+
+/// Fails the build if the captures of `F` take more than `MAX` bytes, see
+/// [`assert_capture_size`].
+struct CaptureSize<const MAX: usize, F>(core::marker::PhantomData<F>);
+
+impl<const MAX: usize, F> CaptureSize<MAX, F> {
+    /// Evaluated when `assert_capture_size` is instantiated with `F`.
+    const OK: () = assert!(
+        core::mem::size_of::<F>() <= MAX,
+        "the captures of the shutdown callback exceed the size budget"
+    );
+}
+
+/// Like [`crate::on_shutdown`], but fails the build if the captures of the
+/// callback take more than the given number of bytes. Keeps embedded and
+/// low-memory builds from accidentally moving large buffers into a callback.
+///
+/// The check happens when the code is compiled to machine code, so
+/// `cargo check` doesn't catch it, `cargo build` does.
+///
+/// ## Example
+/// ```rust
+/// use simple_on_shutdown::on_shutdown_sized;
+///
+/// let port = 8080_u16;
+/// on_shutdown_sized!(<= 16 bytes, move || println!("closing port {}", port));
+/// ```
+///
+/// Capturing a buffer by value fails the build:
+/// ```compile_fail
+/// use simple_on_shutdown::on_shutdown_sized;
+///
+/// let buffer = [0_u8; 1024];
+/// on_shutdown_sized!(<= 64 bytes, move || println!("{}", buffer.len()));
+/// ```
+#[macro_export]
+macro_rules! on_shutdown_sized {
+    // a identifier that must point to a valid closure
+    (<= $max:literal bytes, $closure:ident) => {
+        let closure = $crate::__private::assert_capture_size::<$max, _>($closure);
+        $crate::on_shutdown!(closure);
+    };
+    // move closure expression
+    (<= $max:literal bytes, move || $cb:expr) => {
+        let closure = $crate::__private::assert_capture_size::<$max, _>(move || $cb);
+        $crate::on_shutdown!(closure);
+    };
+    // closure expression
+    (<= $max:literal bytes, || $cb:expr) => {
+        let closure = $crate::__private::assert_capture_size::<$max, _>(|| $cb);
+        $crate::on_shutdown!(closure);
+    };
+    (<= $max:literal bytes, $cb:expr) => {
+        let closure = $crate::__private::assert_capture_size::<$max, _>(|| $cb);
+        $crate::on_shutdown!(closure);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ShutdownContext;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_closures_fit_their_modes() {
+        let local = Rc::new(0);
+        let guard = move || drop(local);
+        assert_shutdown_safe!(guard: guard);
+        let hook = || {};
+        assert_shutdown_safe!(hook);
+        assert_shutdown_safe!(context: |_ctx: &ShutdownContext| {});
+        let mut reloads = Vec::new();
+        assert_shutdown_safe!(event: move || reloads.push(()));
+        // still usable afterwards
+        crate::on_shutdown!(guard);
+    }
+
+    #[test]
+    fn test_sized_callback() {
+        let called = Rc::new(core::cell::Cell::new(false));
+        {
+            let called = called.clone();
+            on_shutdown_sized!(<= 8 bytes, move || called.set(true));
+        }
+        assert!(called.get());
+        // nothing captured
+        on_shutdown_sized!(<= 0 bytes, println!("shut down"));
+    }
+}
@@ -0,0 +1,196 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Typed values that hooks of the same shutdown sequence pass to each other.
+
+use crate::sync::Mutex;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Key of a value on the [`ShutdownBlackboard`]. The type of the value is part
+/// of the key, so values can't be read as the wrong type. Usually a `const`
+/// that the hooks share.
+pub struct BlackboardKey<T> {
+    name: &'static str,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> BlackboardKey<T> {
+    /// Constructor. Keys with the same name but different value types don't
+    /// collide.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _value: PhantomData,
+        }
+    }
+
+    /// Name of the key.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for BlackboardKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BlackboardKey<T> {}
+
+impl<T> fmt::Debug for BlackboardKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("BlackboardKey").field(&self.name).finish()
+    }
+}
+
+/// Values that earlier hooks leave for later ones, e.g. the drain hook leaves
+/// a summary for the hook that writes the final report. Available to
+/// context-aware hooks via [`crate::ShutdownContext::blackboard`].
+///
+/// Each shutdown sequence starts with an empty blackboard; hooks that run late
+/// (see [`crate::LateRegistrationPolicy::RunImmediately`]) share the one of the
+/// sequence. Child registries have their own. Cheap to clone; all clones share
+/// the values.
+///
+/// ## Example
+/// ```rust
+/// use simple_on_shutdown::{BlackboardKey, HookConfig, Registry, ShutdownReason};
+///
+/// const DRAINED: BlackboardKey<usize> = BlackboardKey::new("drained connections");
+///
+/// let registry = Registry::new();
+/// registry.register_with_context(HookConfig::new("report"), |ctx| {
+///     let drained = ctx.blackboard().get(&DRAINED).unwrap_or(0);
+///     println!("drained {} connections", drained);
+/// });
+/// registry.register_with_context(HookConfig::new("drain"), |ctx| {
+///     ctx.blackboard().set(&DRAINED, 42);
+/// });
+/// registry.run(ShutdownReason::Exit, None);
+/// ```
+#[derive(Clone)]
+pub struct ShutdownBlackboard(Arc<Mutex<HashMap<(&'static str, TypeId), Entry>>>);
+
+type Entry = Box<dyn Any + Send>;
+
+impl ShutdownBlackboard {
+    /// Constructor. Creates an empty blackboard.
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(HashMap::new())))
+    }
+
+    /// Stores `value` under `key` and returns the previous value.
+    pub fn set<T: Send + 'static>(&self, key: &BlackboardKey<T>, value: T) -> Option<T> {
+        self.0
+            .lock()
+            .insert(Self::slot(key), Box::new(value))
+            .map(Self::unbox)
+    }
+
+    /// Copy of the value under `key`.
+    pub fn get<T: Clone + Send + 'static>(&self, key: &BlackboardKey<T>) -> Option<T> {
+        self.0.lock().get(&Self::slot(key)).map(|v| {
+            v.downcast_ref::<T>()
+                .expect("type is part of the key")
+                .clone()
+        })
+    }
+
+    /// Removes the value under `key` and returns it.
+    pub fn take<T: Send + 'static>(&self, key: &BlackboardKey<T>) -> Option<T> {
+        self.0.lock().remove(&Self::slot(key)).map(Self::unbox)
+    }
+
+    /// Changes the value under `key` in place, e.g. to add to a counter. `f`
+    /// gets `None` if there is no value yet and can remove the value by leaving
+    /// `None` behind. The blackboard is locked while `f` runs.
+    pub fn update<T, R, F>(&self, key: &BlackboardKey<T>, f: F) -> R
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Option<T>) -> R,
+    {
+        let mut values = self.0.lock();
+        let mut value = values.remove(&Self::slot(key)).map(Self::unbox);
+        let result = f(&mut value);
+        if let Some(value) = value {
+            values.insert(Self::slot(key), Box::new(value));
+        }
+        result
+    }
+
+    /// Whether there is a value under `key`.
+    pub fn contains<T: 'static>(&self, key: &BlackboardKey<T>) -> bool {
+        self.0.lock().contains_key(&Self::slot(key))
+    }
+
+    fn slot<T: 'static>(key: &BlackboardKey<T>) -> (&'static str, TypeId) {
+        (key.name, TypeId::of::<T>())
+    }
+
+    fn unbox<T: 'static>(value: Entry) -> T {
+        *value.downcast::<T>().expect("type is part of the key")
+    }
+}
+
+impl Default for ShutdownBlackboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ShutdownBlackboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self.0.lock();
+        f.debug_set()
+            .entries(values.keys().map(|(name, _)| name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_keys() {
+        const COUNT: BlackboardKey<u32> = BlackboardKey::new("count");
+        const COUNT_TEXT: BlackboardKey<String> = BlackboardKey::new("count");
+        let blackboard = ShutdownBlackboard::new();
+        assert_eq!(blackboard.set(&COUNT, 1), None);
+        assert_eq!(blackboard.set(&COUNT, 2), Some(1));
+        assert!(!blackboard.contains(&COUNT_TEXT));
+        blackboard.set(&COUNT_TEXT, "two".to_string());
+
+        blackboard.update(&COUNT, |count| *count = count.map(|c| c + 1));
+        assert_eq!(blackboard.get(&COUNT), Some(3));
+        assert_eq!(blackboard.take(&COUNT_TEXT).as_deref(), Some("two"));
+        assert_eq!(blackboard.get(&COUNT_TEXT), None);
+        blackboard.update(&COUNT, |count| count.take());
+        assert!(!blackboard.contains(&COUNT));
+    }
+}
@@ -0,0 +1,131 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Shutdown callbacks of web apps compiled with `wasm-bindgen`.
+//!
+//! In the browser, the start function returns right after the setup, so guards
+//! created with [`crate::on_shutdown`] would run immediately. Guards that are
+//! handed to [`keep_until_unload`] live until the page is hidden for good
+//! instead (`pagehide`). Pages that go into the back/forward cache
+//! (`PageTransitionEvent.persisted`) may be restored, their guards stay. The
+//! crate adds the event listener on first use and
+//! keeps the `Closure` of it alive until [`remove_unload_handler`]; users don't
+//! need to leak or store any closures.
+//!
+//! The [`crate::Registry`] is not available on `wasm32-unknown-unknown`, as
+//! `std::time::Instant` isn't.
+//!
+//! ## Example
+//! ```rust,ignore
+//! use simple_on_shutdown::{browser, on_shutdown_expr};
+//! use wasm_bindgen::prelude::*;
+//!
+//! #[wasm_bindgen(start)]
+//! pub fn start() -> Result<(), JsValue> {
+//!     browser::keep_until_unload(on_shutdown_expr!(save_draft()))?;
+//!     browser::attach_until_unload(|| send_beacon("session ended"))?;
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{OnShutdownCallback, ShutdownAnchor};
+use std::cell::RefCell;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// Fires reliably when a page is left, also on mobile browsers, unlike
+/// `unload` and `beforeunload`.
+const EVENT: &str = "pagehide";
+
+type Listener = Closure<dyn FnMut(web_sys::Event)>;
+
+thread_local! {
+    /// Callbacks that run on `pagehide`.
+    static ANCHOR: RefCell<Option<ShutdownAnchor>> = const { RefCell::new(None) };
+    /// The installed event listener. Dropping it invalidates the JS function.
+    static LISTENER: RefCell<Option<Listener>> = const { RefCell::new(None) };
+}
+
+/// Keeps `guard` alive until the page is hidden for good and runs it then.
+/// Guards run in reverse order of keeping. Fails if there is no `window`, e.g.
+/// in a web worker.
+pub fn keep_until_unload(guard: OnShutdownCallback) -> Result<(), JsValue> {
+    install()?;
+    ANCHOR.with(|anchor| {
+        anchor
+            .borrow_mut()
+            .get_or_insert_with(ShutdownAnchor::new)
+            .adopt(guard)
+    });
+    Ok(())
+}
+
+/// Like [`keep_until_unload`] for a plain callback.
+pub fn attach_until_unload(cb: impl FnOnce() + 'static) -> Result<(), JsValue> {
+    keep_until_unload(OnShutdownCallback::from(cb))
+}
+
+/// Runs the kept callbacks now, e.g. before navigating away programmatically.
+/// Callbacks that are kept afterwards run on `pagehide` again.
+pub fn run_now() {
+    // taken out first: callbacks may keep new ones
+    let anchor = ANCHOR.with(|anchor| anchor.borrow_mut().take());
+    drop(anchor);
+}
+
+/// Removes the event listener. Kept callbacks stay and run with [`run_now`],
+/// or on `pagehide` after the next [`keep_until_unload`]. Must not be called
+/// from a kept callback.
+pub fn remove_unload_handler() -> Result<(), JsValue> {
+    let listener = LISTENER.with(|listener| listener.borrow_mut().take());
+    if let Some(listener) = listener {
+        window()?.remove_event_listener_with_callback(EVENT, listener.as_ref().unchecked_ref())?;
+    }
+    Ok(())
+}
+
+/// Adds the event listener, unless it's already there.
+fn install() -> Result<(), JsValue> {
+    LISTENER.with(|listener| {
+        let mut listener = listener.borrow_mut();
+        if listener.is_some() {
+            return Ok(());
+        }
+        let closure = Listener::new(|event: web_sys::Event| {
+            let cached = event
+                .dyn_ref::<web_sys::PageTransitionEvent>()
+                .is_some_and(web_sys::PageTransitionEvent::persisted);
+            // the page may come back from the back/forward cache
+            if !cached {
+                run_now();
+            }
+        });
+        window()?.add_event_listener_with_callback(EVENT, closure.as_ref().unchecked_ref())?;
+        *listener = Some(closure);
+        Ok(())
+    })
+}
+
+fn window() -> Result<web_sys::Window, JsValue> {
+    web_sys::window().ok_or_else(|| JsValue::from_str("no window, e.g. in a web worker"))
+}
@@ -0,0 +1,162 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Fault injection for hooks. Verifies that a shutdown sequence copes with a
+//! single misbehaving hook, e.g. that the remaining hooks still run or that the
+//! escalation policy kicks in.
+
+use crate::shuffle::splitmix64;
+use std::time::Duration;
+
+/// Misbehavior that [`Chaos`] injects into a hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The hook starts late by the given duration and then runs normally.
+    Delay(Duration),
+    /// The hook panics instead of running.
+    Panic,
+    /// The hook doesn't run but blocks until its timeout or the global deadline
+    /// has passed and is reported as [`crate::HookOutcome::TimedOut`]. Without a
+    /// timeout and a budget, it blocks forever, like a stuck hook would.
+    Hang,
+}
+
+/// Seeded, deterministic fault injection, see [`crate::Registry::set_chaos`].
+/// The same seed injects the same faults into the same hooks in every run, so a
+/// failing sequence can be reproduced.
+///
+/// ## Example
+/// ```rust
+/// use simple_on_shutdown::{Chaos, Registry};
+/// use std::time::Duration;
+///
+/// let registry = Registry::new();
+/// registry.set_chaos(Chaos::new(42).probability(50).max_delay(Duration::from_millis(100)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chaos {
+    seed: u64,
+    probability: u8,
+    max_delay: Duration,
+    panics: bool,
+    hangs: bool,
+}
+
+impl Chaos {
+    /// Constructor. By default, 20% of the hooks misbehave, delays are up to one
+    /// second and all kinds of [`Fault`]s are injected.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            probability: 20,
+            max_delay: Duration::from_secs(1),
+            panics: true,
+            hangs: true,
+        }
+    }
+
+    /// Percentage of hooks that misbehave, clamped to 100.
+    pub fn probability(mut self, percent: u8) -> Self {
+        self.probability = percent.min(100);
+        self
+    }
+
+    /// Upper bound for [`Fault::Delay`].
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Whether [`Fault::Panic`] is injected.
+    pub fn panics(mut self, panics: bool) -> Self {
+        self.panics = panics;
+        self
+    }
+
+    /// Whether [`Fault::Hang`] is injected.
+    pub fn hangs(mut self, hangs: bool) -> Self {
+        self.hangs = hangs;
+        self
+    }
+
+    /// The fault that is injected into hooks with the given name, if any. Hooks
+    /// with the same name get the same fault.
+    pub fn fault_for(&self, name: &str) -> Option<Fault> {
+        let random = splitmix64(self.seed ^ fnv1a(name.as_bytes()));
+        if random % 100 >= u64::from(self.probability) {
+            return None;
+        }
+        let mut faults = vec![Fault::Delay(Duration::from_secs(0))];
+        if self.panics {
+            faults.push(Fault::Panic);
+        }
+        if self.hangs {
+            faults.push(Fault::Hang);
+        }
+        let random = random / 100;
+        Some(match faults[(random % faults.len() as u64) as usize] {
+            Fault::Delay(_) => {
+                let max = self.max_delay.as_millis() as u64;
+                Fault::Delay(Duration::from_millis(
+                    (random / faults.len() as u64) % (max + 1),
+                ))
+            }
+            fault => fault,
+        })
+    }
+}
+
+/// FNV-1a hash, stable across Rust versions unlike the std hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_faults_are_deterministic() {
+        let names: Vec<String> = (0..200).map(|i| format!("hook-{}", i)).collect();
+        let faults = |chaos: &Chaos| names.iter().map(|n| chaos.fault_for(n)).collect::<Vec<_>>();
+        let chaos = Chaos::new(7).probability(50);
+        assert_eq!(faults(&chaos), faults(&chaos.clone()));
+        assert_ne!(faults(&chaos), faults(&Chaos::new(8).probability(50)));
+
+        let injected = faults(&chaos).into_iter().flatten().count();
+        assert!((50..150).contains(&injected), "{}", injected);
+        assert!(faults(&chaos.clone().probability(0))
+            .iter()
+            .all(Option::is_none));
+        let tame = chaos
+            .panics(false)
+            .hangs(false)
+            .max_delay(Duration::from_millis(5));
+        assert!(faults(&tame).into_iter().flatten().all(|f| match f {
+            Fault::Delay(d) => d <= Duration::from_millis(5),
+            _ => false,
+        }));
+    }
+}
@@ -0,0 +1,134 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Child registries for subsystems that need their own teardown.
+
+use crate::{Registry, ShutdownReason, ShutdownReport};
+use core::ops::Deref;
+use std::sync::Arc;
+
+/// A [`Registry`] that belongs to a subsystem and is nested in a parent
+/// registry, see [`Registry::child`]. Dereferences to [`Registry`].
+///
+/// Triggering the child, or dropping it, runs only the hooks of the child.
+/// Triggering the parent runs the hooks of all its living children before the
+/// own hooks.
+pub struct ChildRegistry(Arc<Registry>);
+
+impl Registry {
+    /// Creates a child registry, e.g. for a subsystem of a modular application.
+    /// Hooks of the child run when the child is triggered or dropped, or before
+    /// the hooks of this registry when it is triggered, whatever happens first.
+    ///
+    /// ## Example
+    /// ```
+    /// use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+    ///
+    /// let app = Registry::new();
+    /// app.register(HookConfig::new("close db"), || println!("db closed"));
+    /// let http = app.child();
+    /// http.register(HookConfig::new("stop server"), || println!("server stopped"));
+    ///
+    /// // stops the server, then closes the db
+    /// app.run(ShutdownReason::Exit, None);
+    /// ```
+    pub fn child(&self) -> ChildRegistry {
+        let child = Arc::new(Registry::new());
+        self.add_child(Arc::downgrade(&child));
+        ChildRegistry(child)
+    }
+}
+
+impl ChildRegistry {
+    /// Runs the hooks of the child only, like [`Registry::run`] with
+    /// [`ShutdownReason::Requested`] and no budget.
+    pub fn shutdown(self) -> ShutdownReport {
+        self.0.run(ShutdownReason::Requested, None)
+    }
+}
+
+impl Deref for ChildRegistry {
+    type Target = Registry;
+
+    fn deref(&self) -> &Registry {
+        &self.0
+    }
+}
+
+impl Drop for ChildRegistry {
+    /// Runs the hooks of the child, unless it was already triggered.
+    fn drop(&mut self) {
+        if !self.0.is_triggered() {
+            self.0.run(ShutdownReason::Requested, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HookConfig;
+    use std::sync::Mutex;
+
+    /// Registers a hook that pushes `name` to `order`.
+    fn push(registry: &Registry, order: &Arc<Mutex<Vec<&'static str>>>, name: &'static str) {
+        let order = order.clone();
+        registry.register(HookConfig::new(name), move || {
+            order.lock().unwrap().push(name)
+        });
+    }
+
+    #[test]
+    fn test_children_run_before_parent() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let parent = Registry::new();
+        push(&parent, &order, "parent");
+        let http = parent.child();
+        push(&http, &order, "http");
+        let jobs = parent.child();
+        push(&jobs, &order, "jobs");
+        let jobs_worker = jobs.child();
+        push(&jobs_worker, &order, "jobs worker");
+
+        let report = parent.run(ShutdownReason::Exit, None);
+        assert_eq!(report.results.len(), 4);
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["jobs worker", "jobs", "http", "parent"]
+        );
+    }
+
+    #[test]
+    fn test_dropping_child_runs_only_its_hooks() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let parent = Registry::new();
+        push(&parent, &order, "parent");
+        let child = parent.child();
+        push(&child, &order, "child");
+
+        drop(child);
+        assert_eq!(*order.lock().unwrap(), ["child"]);
+        parent.run(ShutdownReason::Exit, None);
+        assert_eq!(*order.lock().unwrap(), ["child", "parent"]);
+    }
+}
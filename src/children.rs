@@ -0,0 +1,175 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Terminates tracked child processes at shutdown, so wrappers and supervisors
+//! don't leak orphans. UNIX only, Windows uses a `JobObject` instead.
+
+use crate::sync::Mutex;
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use std::process::Child;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time the processes get to exit after `SIGTERM` if the hook has no deadline.
+pub const DEFAULT_CHILD_GRACE: Duration = Duration::from_secs(5);
+
+/// How often the processes are checked for termination.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Child processes and process groups that get terminated at shutdown. Cheap to
+/// clone; all clones track the same processes.
+///
+/// At shutdown, all of them receive `SIGTERM`. Processes that are still alive at
+/// the deadline of the hook receive `SIGKILL`.
+#[derive(Debug, Clone, Default)]
+pub struct ChildProcesses(Arc<Mutex<Tracked>>);
+
+#[derive(Debug, Default)]
+struct Tracked {
+    children: Vec<Child>,
+    groups: Vec<libc::pid_t>,
+}
+
+impl ChildProcesses {
+    /// Constructor. Tracks nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tracks a spawned child.
+    pub fn track(&self, child: Child) {
+        self.0.lock().children.push(child);
+    }
+
+    /// Tracks the whole process group `pgid`.
+    pub fn track_process_group(&self, pgid: u32) {
+        self.0.lock().groups.push(pgid as libc::pid_t);
+    }
+
+    /// Sends `SIGTERM` to everything that is tracked, waits until `deadline` and
+    /// sends `SIGKILL` to what's left. Returns the PIDs (or negated process group
+    /// IDs) that had to be killed.
+    pub fn terminate(&self, deadline: Instant) -> Vec<i32> {
+        let mut tracked = self.0.lock();
+        let Tracked { children, groups } = &mut *tracked;
+        for child in children.iter() {
+            send(child.id() as libc::pid_t, libc::SIGTERM);
+        }
+        for &pgid in groups.iter() {
+            send(-pgid, libc::SIGTERM);
+        }
+        loop {
+            // reap our children first, otherwise they keep their group alive as zombies
+            children.retain_mut(|c| !matches!(c.try_wait(), Ok(Some(_))));
+            groups.retain(|&pgid| send(-pgid, 0));
+            if (children.is_empty() && groups.is_empty()) || Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        let mut killed = Vec::new();
+        for mut child in children.drain(..) {
+            killed.push(child.id() as i32);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        for pgid in groups.drain(..) {
+            killed.push(-pgid);
+            send(-pgid, libc::SIGKILL);
+        }
+        killed
+    }
+}
+
+/// Sends `sig` to `pid`. Returns whether the process (group) exists.
+fn send(pid: libc::pid_t, sig: libc::c_int) -> bool {
+    // SAFETY: plain syscall without pointers
+    unsafe { libc::kill(pid, sig) == 0 }
+}
+
+impl Registry {
+    /// Registers a hook that terminates `children` at shutdown. They get until the
+    /// deadline of the hook (or [`DEFAULT_CHILD_GRACE`]) to exit after `SIGTERM`.
+    /// The hook fails if processes had to be killed with `SIGKILL`.
+    #[track_caller]
+    pub fn register_child_processes(&self, config: HookConfig, children: ChildProcesses) {
+        self.register_outcome(config, move |ctx| terminate(&children, ctx));
+    }
+}
+
+/// Body of the hook created by [`Registry::register_child_processes`].
+fn terminate(children: &ChildProcesses, ctx: &ShutdownContext) -> HookOutcome {
+    let deadline = ctx
+        .deadline()
+        .unwrap_or_else(|| Instant::now() + DEFAULT_CHILD_GRACE);
+    let killed = children.terminate(deadline);
+    if killed.is_empty() {
+        HookOutcome::Completed
+    } else {
+        HookOutcome::Failed(format!("had to kill {:?} with SIGKILL", killed).into())
+    }
+}
+
+/// Creates a [`ChildProcesses`] set that is terminated by a hook in the
+/// [`crate::global`] registry.
+pub fn terminate_children_on_shutdown() -> ChildProcesses {
+    let children = ChildProcesses::new();
+    crate::global()
+        .register_child_processes(HookConfig::new("terminate children"), children.clone());
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shell_command, ShutdownReason};
+
+    #[test]
+    fn test_children_are_terminated() {
+        let children = ChildProcesses::new();
+        children.track(shell_command("sleep 10").spawn().unwrap());
+        // ignores SIGTERM
+        children.track(
+            shell_command("trap '' TERM; sleep 10 & wait")
+                .spawn()
+                .unwrap(),
+        );
+        // give the shell time to install the trap
+        thread::sleep(Duration::from_millis(100));
+
+        let registry = Registry::new();
+        registry.register_child_processes(
+            HookConfig::new("children").timeout(Duration::from_millis(200)),
+            children,
+        );
+        let begin = Instant::now();
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(begin.elapsed() < Duration::from_secs(5));
+        assert!(!report.is_success());
+        assert!(report.results[0]
+            .outcome
+            .to_string()
+            .contains("had to kill"));
+    }
+}
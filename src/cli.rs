@@ -0,0 +1,164 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Status line that shows the progress of the shutdown sequence in a terminal.
+
+use crate::{HookUpdate, Registry};
+use std::io::{self, IsTerminal, Write};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Frames of the spinner.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// How often the spinner turns.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Shows a single status line on stderr while the hooks of `registry` run: a
+/// spinner, the hook that currently runs and how many hooks are done, e.g.
+/// `/ shutting down: flush cache (3 done)`. The line is rewritten in place and
+/// removed once no hook runs anymore. Users of long-running CLI tools see that
+/// something happens instead of pressing `CTRL+C` twice.
+///
+/// Does nothing if stderr is no terminal. Call it once at startup; the status
+/// line is rendered by a background thread, see [`Registry::subscribe`].
+///
+/// ```rust,no_run
+/// simple_on_shutdown::show_progress(simple_on_shutdown::global()).unwrap();
+/// ```
+pub fn show_progress(registry: &Registry) -> io::Result<()> {
+    if !io::stderr().is_terminal() {
+        return Ok(());
+    }
+    let updates = registry.subscribe();
+    thread::Builder::new()
+        .name("shutdown-progress".to_string())
+        .spawn(move || render(updates, io::stderr()))?;
+    Ok(())
+}
+
+/// Renders updates until the registry is gone.
+fn render(updates: Receiver<HookUpdate>, mut out: impl Write) {
+    let mut line = StatusLine::default();
+    loop {
+        match updates.recv_timeout(TICK) {
+            Ok(update) => line.update(update),
+            Err(RecvTimeoutError::Timeout) => line.tick(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        // nothing useful to do if stderr is broken
+        let _ = out
+            .write_all(line.render().as_bytes())
+            .and_then(|_| out.flush());
+    }
+}
+
+/// State of the status line.
+#[derive(Debug, Default)]
+struct StatusLine {
+    /// Names of the hooks that currently run, with their last progress.
+    running: Vec<(String, Option<u64>)>,
+    /// Number of finished hooks.
+    done: usize,
+    /// Current frame of the spinner.
+    frame: usize,
+}
+
+impl StatusLine {
+    fn update(&mut self, update: HookUpdate) {
+        match update {
+            HookUpdate::Started(info) => self.running.push((info.name, None)),
+            HookUpdate::Progress(info, progress) => {
+                if let Some(hook) = self.running.iter_mut().find(|(name, _)| *name == info.name) {
+                    hook.1 = Some(progress);
+                }
+            }
+            HookUpdate::Finished(info, _) => {
+                if let Some(i) = self.running.iter().position(|(name, _)| *name == info.name) {
+                    self.running.remove(i);
+                }
+                self.done += 1;
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        self.frame = (self.frame + 1) % SPINNER.len();
+    }
+
+    /// Escape sequence that replaces the current terminal line.
+    fn render(&self) -> String {
+        // carriage return and "erase line"
+        let mut line = "\r\x1b[2K".to_string();
+        if let Some((name, progress)) = self.running.last() {
+            line += &format!("{} shutting down: {}", SPINNER[self.frame], name);
+            if let Some(progress) = progress {
+                line += &format!(" [{}]", progress);
+            }
+            line += &format!(" ({} done)", self.done);
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HookConfig, HookInfo, ShutdownReason};
+
+    #[test]
+    fn test_status_line() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("a"), || {});
+        registry.register(HookConfig::new("b"), || {});
+        let updates = registry.subscribe();
+        registry.run(ShutdownReason::Exit, None);
+
+        let mut line = StatusLine::default();
+        let mut rendered = updates
+            .try_iter()
+            .map(|update| {
+                line.update(update);
+                line.render()
+            })
+            .collect::<Vec<_>>();
+        line.tick();
+        line.update(HookUpdate::Started(HookInfo::from(&HookConfig::new("c"))));
+        line.update(HookUpdate::Progress(
+            HookInfo::from(&HookConfig::new("c")),
+            7,
+        ));
+        rendered.push(line.render());
+        assert_eq!(
+            rendered,
+            [
+                "\r\x1b[2K| shutting down: b (0 done)",
+                "\r\x1b[2K",
+                "\r\x1b[2K| shutting down: a (1 done)",
+                "\r\x1b[2K",
+                "\r\x1b[2K/ shutting down: c [7] (2 done)",
+            ]
+        );
+    }
+}
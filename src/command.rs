@@ -0,0 +1,167 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! External commands as shutdown hooks. Handy for glue scripts that can't be
+//! rewritten in Rust.
+
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use std::fmt;
+use std::process::{Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running command is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Error of a command hook that didn't exit successfully. It's part of the
+/// [`crate::ShutdownReport`] and can be obtained via `downcast_ref()`.
+#[derive(Debug)]
+pub enum CommandError {
+    /// The command couldn't be started.
+    Spawn(std::io::Error),
+    /// The command exited with a non-zero status.
+    Exit(ExitStatus),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn(err) => write!(f, "couldn't spawn command: {}", err),
+            Self::Exit(status) => write!(f, "command exited with {}", status),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Creates a [`Command`] that runs `script` in the shell of the platform
+/// (`sh -c` on UNIX, `cmd /C` on Windows).
+pub fn shell_command(script: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(script);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        cmd
+    }
+}
+
+/// Registers `cmd` in the [`crate::global`] registry. The hook is named after the
+/// program. See [`Registry::register_command`].
+#[track_caller]
+pub fn command_on_shutdown(cmd: Command) {
+    let name = cmd.get_program().to_string_lossy().into_owned();
+    crate::global().register_command(HookConfig::new(name), cmd);
+}
+
+impl Registry {
+    /// Registers a hook that spawns `cmd` at shutdown and waits for it. If the
+    /// hook has a deadline (see [`ShutdownContext::deadline`]) and the command
+    /// is still running by then, it is killed. The exit status ends up in the
+    /// [`crate::ShutdownReport`].
+    #[track_caller]
+    pub fn register_command(&self, config: HookConfig, mut cmd: Command) {
+        self.register_outcome(config, move |ctx| run_command(&mut cmd, ctx));
+    }
+}
+
+/// Spawns the command and waits for it until the deadline of the context.
+fn run_command(cmd: &mut Command, ctx: &ShutdownContext) -> HookOutcome {
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => return HookOutcome::Failed(Box::new(CommandError::Spawn(err))),
+    };
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return HookOutcome::Completed,
+            Ok(Some(status)) => return HookOutcome::Failed(Box::new(CommandError::Exit(status))),
+            Ok(None) => {}
+            Err(err) => return HookOutcome::Failed(Box::new(err)),
+        }
+        if ctx.deadline().is_some_and(|d| Instant::now() >= d) {
+            // the child may have exited in the meantime; nothing to do about errors here
+            let _ = child.kill();
+            let _ = child.wait();
+            return HookOutcome::TimedOut;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Registers a shell command in the [`crate::global`] registry. It is executed
+/// with `sh -c` (UNIX) or `cmd /C` (Windows) at shutdown. See
+/// [`crate::command_on_shutdown`].
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown::on_shutdown_cmd;
+///
+/// on_shutdown_cmd!("sync && echo done");
+/// ```
+#[macro_export]
+macro_rules! on_shutdown_cmd {
+    ($script:expr) => {
+        $crate::command_on_shutdown($crate::shell_command($script))
+    };
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+
+    #[test]
+    fn test_command_exit_status_is_reported() {
+        let registry = Registry::new();
+        registry.register_command(HookConfig::new("ok"), shell_command("exit 0"));
+        registry.register_command(HookConfig::new("fails"), shell_command("exit 3"));
+        let report = registry.run(ShutdownReason::Exit, None);
+
+        assert_eq!(report.results[0].name, "fails");
+        match &report.results[0].outcome {
+            HookOutcome::Failed(err) => match err.downcast_ref::<CommandError>() {
+                Some(CommandError::Exit(status)) => assert_eq!(status.code(), Some(3)),
+                _ => panic!("unexpected error: {}", err),
+            },
+            outcome => panic!("unexpected outcome: {}", outcome),
+        }
+        assert!(report.results[1].outcome.is_success());
+    }
+
+    #[test]
+    fn test_command_is_killed_at_timeout() {
+        let registry = Registry::new();
+        registry.register_command(
+            HookConfig::new("sleep").timeout(Duration::from_millis(50)),
+            shell_command("sleep 10"),
+        );
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+        assert!(report.results[0].duration < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,170 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Futures that complete when hooks or whole shutdown sequences are done, so
+//! async code can wait for the cleanup, e.g. to print a final message.
+
+use crate::sync::Mutex;
+use crate::{HookInfo, HookObserver, HookOutcome, OnShutdownCallback, Registry};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Future that completes once something has finished, see
+/// [`shutdown_complete`], [`Registry::shutdown_complete`],
+/// [`Registry::hook_complete`] and [`Self::for_guard`]. Cheap to clone; all
+/// clones complete together. Works with any executor.
+#[derive(Debug, Clone, Default)]
+pub struct Completion(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    complete: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Completion {
+    /// Constructor. Not complete yet.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// A completion that is already complete.
+    pub(crate) fn completed() -> Self {
+        let completion = Self::new();
+        completion.complete();
+        completion
+    }
+
+    /// Completes and wakes up everyone who awaits it.
+    pub(crate) fn complete(&self) {
+        self.0.complete.store(true, Ordering::SeqCst);
+        for waker in self.0.wakers.lock().drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Whether it completed.
+    pub fn is_complete(&self) -> bool {
+        self.0.complete.load(Ordering::SeqCst)
+    }
+
+    /// Wraps `guard` into a guard that completes the returned [`Completion`]
+    /// after its callback ran.
+    pub fn for_guard(guard: OnShutdownCallback) -> (OnShutdownCallback, Self) {
+        let completion = Self::new();
+        let done = completion.clone();
+        let cb: Box<dyn FnOnce()> = guard.into();
+        let guard = OnShutdownCallback::from(move || {
+            cb();
+            done.complete();
+        });
+        (guard, completion)
+    }
+}
+
+impl Future for Completion {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_complete() {
+            return Poll::Ready(());
+        }
+        self.0.wakers.lock().push(cx.waker().clone());
+        // complete() may have happened in between
+        if self.is_complete() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Completes its [`Completion`] when the hook with the given name finished.
+struct HookCompletion {
+    name: String,
+    completion: Completion,
+}
+
+impl HookObserver for HookCompletion {
+    fn after(&self, info: &HookInfo, _outcome: &HookOutcome) {
+        if info.name == self.name {
+            self.completion.complete();
+        }
+    }
+}
+
+impl Registry {
+    /// Completes when the hook `name` of this registry finished, no matter how,
+    /// e.g. to flush an exporter once the hook that produced the last data
+    /// ran. Only hooks that finish after the call count.
+    pub fn hook_complete(&self, name: &str) -> Completion {
+        let completion = Completion::new();
+        self.add_observer(Arc::new(HookCompletion {
+            name: name.to_string(),
+            completion: completion.clone(),
+        }));
+        completion
+    }
+}
+
+/// Completes when the shutdown sequence of the [`crate::global`] registry
+/// finished, see [`Registry::shutdown_complete`].
+pub fn shutdown_complete() -> Completion {
+    crate::global().shutdown_complete()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::block_on_guard;
+    use crate::{HookConfig, ShutdownReason};
+    use std::time::Duration;
+
+    #[test]
+    fn test_await_completion() {
+        let registry = Arc::new(Registry::new());
+        registry.register(HookConfig::new("flush"), || {
+            std::thread::sleep(Duration::from_millis(20))
+        });
+        let hook = registry.hook_complete("flush");
+        let sequence = registry.shutdown_complete();
+        assert!(!sequence.is_complete());
+        let runner = registry.clone();
+        let handle = std::thread::spawn(move || runner.run(ShutdownReason::Exit, None));
+        block_on_guard(async {
+            hook.await;
+            sequence.await;
+        });
+        handle.join().unwrap();
+        // already finished
+        assert!(registry.shutdown_complete().is_complete());
+
+        let (guard, completion) = Completion::for_guard(OnShutdownCallback::from(|| {}));
+        assert!(!completion.is_complete());
+        drop(guard);
+        block_on_guard(completion);
+    }
+}
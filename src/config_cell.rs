@@ -0,0 +1,195 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! A configuration that is swapped on every [`Event::Reload`] and flushed at
+//! shutdown.
+
+use crate::sync::Mutex;
+use crate::{Event, HookConfig, HookError, Registry, ShutdownContext};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared value that is replaced on every [`Event::Reload`] (e.g. `SIGHUP`
+/// after `install_reload_handler()`) and whose final state is passed to a
+/// hook at shutdown, see [`Registry::register_config`]. Cheap to clone; all
+/// clones share the value.
+///
+/// Readers get an `Arc` of the value at that moment, so a reload never changes
+/// a configuration that is in use.
+pub struct ConfigCell<T>(Arc<Inner<T>>);
+
+struct Inner<T> {
+    value: Mutex<Arc<T>>,
+    /// Set when the shutdown hook received the final state.
+    flushed: AtomicBool,
+}
+
+impl<T> ConfigCell<T> {
+    /// Constructor. Not tied to any registry, see [`Registry::register_config`].
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(Inner {
+            value: Mutex::new(Arc::new(value)),
+            flushed: AtomicBool::new(false),
+        }))
+    }
+
+    /// The current value.
+    pub fn get(&self) -> Arc<T> {
+        self.0.value.lock().clone()
+    }
+
+    /// Replaces the value and returns the previous one.
+    pub fn set(&self, value: T) -> Arc<T> {
+        core::mem::replace(&mut *self.0.value.lock(), Arc::new(value))
+    }
+
+    /// Whether the final state was passed to the shutdown hook. Later reloads
+    /// are rejected, as their result would be lost.
+    pub fn is_flushed(&self) -> bool {
+        self.0.flushed.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Clone for ConfigCell<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ConfigCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigCell")
+            .field("value", &self.get())
+            .field("flushed", &self.is_flushed())
+            .finish()
+    }
+}
+
+impl Registry {
+    /// Creates a [`ConfigCell`] with `initial` and registers two hooks with
+    /// `config`:
+    /// - an event hook for [`Event::Reload`] that replaces the value with the
+    ///   result of `reload`, which gets the current value. If `reload` fails,
+    ///   the value stays and the error ends up in the report of
+    ///   [`Self::emit`].
+    /// - a shutdown hook that passes the final value to `flush`, e.g. to persist
+    ///   settings that changed at runtime. Reloads after it are rejected.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use simple_on_shutdown::{Event, HookConfig, Registry, ShutdownReason};
+    ///
+    /// let registry = Registry::new();
+    /// let config = registry.register_config(
+    ///     HookConfig::new("config"),
+    ///     1,
+    ///     |current: &u32| Ok::<_, std::io::Error>(current + 1),
+    ///     |last: &u32, _ctx| {
+    ///         assert_eq!(*last, 2);
+    ///         Ok::<_, std::io::Error>(())
+    ///     },
+    /// );
+    /// registry.emit(Event::Reload);
+    /// assert_eq!(*config.get(), 2);
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// ```
+    #[track_caller]
+    pub fn register_config<T, L, F, E1, E2>(
+        &self,
+        config: HookConfig,
+        initial: T,
+        mut reload: L,
+        flush: F,
+    ) -> ConfigCell<T>
+    where
+        T: Send + Sync + 'static,
+        L: FnMut(&T) -> Result<T, E1> + Send + 'static,
+        F: FnOnce(&T, &ShutdownContext) -> Result<(), E2> + Send + 'static,
+        E1: Into<HookError>,
+        E2: Into<HookError>,
+    {
+        let cell = ConfigCell::new(initial);
+        let reloaded = cell.clone();
+        self.register_event_fallible(Event::Reload, config.clone(), move || {
+            if reloaded.is_flushed() {
+                return Err("the configuration was already flushed at shutdown".into());
+            }
+            let next = reload(&reloaded.get()).map_err(Into::into)?;
+            reloaded.set(next);
+            Ok::<_, HookError>(())
+        });
+        let flushed = cell.clone();
+        self.register_fallible(config, move |ctx| {
+            // reloads that start now are rejected, a running one may still land
+            flushed.0.flushed.store(true, Ordering::SeqCst);
+            flush(&flushed.get(), ctx)
+        });
+        cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_reload_and_flush() {
+        let registry = Registry::new();
+        let flushed = Arc::new(Mutex::new(None));
+        let flushed_c = flushed.clone();
+        let config = registry.register_config(
+            HookConfig::new("config"),
+            String::from("v1"),
+            |current: &String| match current.as_str() {
+                "v1" => Ok(String::from("v2")),
+                _ => Err("invalid configuration"),
+            },
+            move |last: &String, _ctx| {
+                *flushed_c.lock().unwrap() = Some(last.clone());
+                Ok::<_, HookError>(())
+            },
+        );
+        let before = config.get();
+        assert!(registry.emit(Event::Reload).is_success());
+        assert_eq!(*before, "v1");
+        assert_eq!(*config.get(), "v2");
+        // a failed reload keeps the value
+        assert_eq!(registry.emit(Event::Reload).failures().count(), 1);
+        assert_eq!(*config.get(), "v2");
+
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        assert_eq!(flushed.lock().unwrap().as_deref(), Some("v2"));
+        assert!(config.is_flushed());
+        let report = registry.emit(Event::Reload);
+        assert!(report
+            .failures()
+            .next()
+            .unwrap()
+            .outcome
+            .to_string()
+            .contains("flushed"));
+    }
+}
@@ -0,0 +1,150 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Information that is passed to context-aware hooks.
+
+use crate::{HookConfig, HookInfo, HookObserver, ShutdownBlackboard};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Why the shutdown sequence was started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ShutdownReason {
+    /// The program ends regularly, e.g. `main()` returns.
+    Exit,
+    /// The shutdown was requested explicitly by the application.
+    Requested,
+    /// The process received the given signal number.
+    Signal(i32),
+    /// The program panicked.
+    Panic,
+    /// The operating system shuts the machine down or reboots it, see
+    /// [`crate::is_system_shutting_down`]. Hooks that e.g. hand work over to
+    /// other instances of the application can skip it then, as those go down
+    /// as well.
+    SystemShutdown,
+    /// Only a rehearsal, see [`crate::Registry::rehearse`]. Nothing shuts down.
+    Rehearsal,
+}
+
+/// Passed by reference to context-aware hooks. Allows a hook to adapt its
+/// work, e.g. to skip an expensive flush if only 500ms remain.
+pub struct ShutdownContext<'a> {
+    reason: ShutdownReason,
+    deadline: Option<Instant>,
+    config: &'a HookConfig,
+    info: &'a HookInfo,
+    observers: &'a [Arc<dyn HookObserver>],
+    cancelled: &'a AtomicBool,
+    blackboard: &'a ShutdownBlackboard,
+}
+
+impl<'a> ShutdownContext<'a> {
+    /// Constructor. The deadline of the context is the earlier one of the global
+    /// deadline and the timeout of the hook (counted from now).
+    pub(crate) fn new(
+        reason: ShutdownReason,
+        global_deadline: Option<Instant>,
+        config: &'a HookConfig,
+        info: &'a HookInfo,
+        observers: &'a [Arc<dyn HookObserver>],
+        cancelled: &'a AtomicBool,
+        blackboard: &'a ShutdownBlackboard,
+    ) -> Self {
+        let hook_deadline = config.get_timeout().map(|t| Instant::now() + t);
+        let deadline = match (global_deadline, hook_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Self {
+            reason,
+            deadline,
+            config,
+            info,
+            observers,
+            cancelled,
+            blackboard,
+        }
+    }
+
+    /// Why the shutdown sequence was started.
+    pub fn reason(&self) -> ShutdownReason {
+        self.reason
+    }
+
+    /// Whether this is only a rehearsal, see [`crate::Registry::rehearse`]. The
+    /// hook should check its preconditions without side effects then.
+    pub fn is_rehearsal(&self) -> bool {
+        self.reason == ShutdownReason::Rehearsal
+    }
+
+    /// Point in time at which the hook should be done, if there is one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Remaining time budget of the hook. `None` means unlimited.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// Configuration of the hook that is currently executed.
+    pub fn config(&self) -> &HookConfig {
+        self.config
+    }
+
+    /// Whether the hook was asked to stop early because it exceeded its timeout,
+    /// see [`crate::EscalationPolicy`]. Long-running hooks should check this
+    /// regularly and return.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Values that the hooks of this shutdown sequence pass to each other.
+    pub fn blackboard(&self) -> &ShutdownBlackboard {
+        self.blackboard
+    }
+
+    /// Reports progress of a long-running hook to the [`HookObserver`]s of the
+    /// registry, e.g. the number of completed steps.
+    pub fn report_progress(&self, progress: u64) {
+        for observer in self.observers {
+            observer.progress(self.info, progress);
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownContext<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownContext")
+            .field("reason", &self.reason)
+            .field("deadline", &self.deadline)
+            .field("config", &self.config)
+            .finish()
+    }
+}
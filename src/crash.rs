@@ -0,0 +1,123 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Minimal cleanup for fatal signals like `SIGSEGV` or `SIGBUS`.
+//!
+//! Only available with the `unsafe-crash-handlers` feature. The process is in an
+//! undefined state when such a signal arrives, therefore only the signal-safe hook
+//! tier ([`crate::SignalSafeHook`]) is executed. Afterwards, the previous signal
+//! disposition is restored and the signal is delivered again, so the process still
+//! crashes (and dumps core) as it would without this crate.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io;
+
+/// Fatal signals that are handled by [`install_crash_handlers`].
+pub const CRASH_SIGNALS: [i32; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGABRT,
+    libc::SIGILL,
+    libc::SIGFPE,
+];
+
+/// Signal dispositions that were active before [`install_crash_handlers`].
+/// Written once during installation, read inside the signal handler.
+struct OldActions(UnsafeCell<[MaybeUninit<libc::sigaction>; CRASH_SIGNALS.len()]>);
+
+// SAFETY: only written by the thread that won the race on `INSTALLED`, before the
+// corresponding handler is active
+unsafe impl Sync for OldActions {}
+
+static OLD_ACTIONS: OldActions = OldActions(UnsafeCell::new(
+    [MaybeUninit::uninit(); CRASH_SIGNALS.len()],
+));
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs handlers for all [`CRASH_SIGNALS`]. They run the signal-safe hook
+/// tier (e.g. write a crash marker to a file descriptor) and re-raise the signal
+/// afterwards. Calling this more than once has no effect.
+///
+/// The handlers replace the ones of the Rust standard library. Those are still
+/// invoked afterwards, so stack overflows are reported as usual.
+pub fn install_crash_handlers() -> io::Result<()> {
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    for (i, &sig) in CRASH_SIGNALS.iter().enumerate() {
+        // SAFETY: the action is fully initialized and the handler is async-signal-safe
+        unsafe {
+            let mut action: libc::sigaction = core::mem::zeroed();
+            action.sa_sigaction = handle_crash
+                as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+                as libc::sighandler_t;
+            // stack overflows can only be handled on the alternate signal stack
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            let old = (*OLD_ACTIONS.0.get())[i].as_mut_ptr();
+            if libc::sigaction(sig, &action, old) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Signal handler for all [`CRASH_SIGNALS`].
+extern "C" fn handle_crash(sig: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    crate::run_signal_safe_hooks();
+    if let Some(i) = CRASH_SIGNALS.iter().position(|&s| s == sig) {
+        // SAFETY: the old action was written when this handler was installed
+        unsafe {
+            libc::sigaction(sig, (*OLD_ACTIONS.0.get())[i].as_ptr(), ptr::null_mut());
+        }
+    }
+    // A fault caused by an instruction happens again as soon as this handler
+    // returns, this time with the restored disposition and the original signal
+    // info. Signals sent by a process must be raised again. They are blocked
+    // while this handler runs and get delivered when it returns.
+    // SAFETY: `info` is provided by the kernel; raise() is async-signal-safe
+    unsafe {
+        if sent_by_process(sig, &*info) {
+            libc::raise(sig);
+        }
+    }
+}
+
+/// Whether the signal was sent by a process (kill(), raise(), abort()) rather than
+/// caused by a faulting instruction.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn sent_by_process(_sig: libc::c_int, info: &libc::siginfo_t) -> bool {
+    // SI_USER, SI_TKILL, SI_QUEUE, ... are <= 0, kernel-generated codes are > 0
+    info.si_code <= 0
+}
+
+/// Whether the signal was sent by a process (kill(), raise(), abort()) rather than
+/// caused by a faulting instruction.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn sent_by_process(sig: libc::c_int, _info: &libc::siginfo_t) -> bool {
+    sig == libc::SIGABRT
+}
@@ -0,0 +1,96 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Hooks that close database connection pools.
+//!
+//! Register the pool right after it was created. Hooks run in reverse order of
+//! their registration, so the pool is closed after all hooks of components that
+//! use it. Each adapter respects the deadline of its hook.
+
+use crate::{HookConfig, Registry};
+
+#[cfg(feature = "sqlx")]
+impl Registry {
+    /// Registers a hook that closes the [`sqlx::Pool`]. It waits until all
+    /// connections are returned and closed.
+    ///
+    /// If this is called inside a Tokio runtime, the pool is closed on that
+    /// runtime, because closing connections needs its I/O driver. The runtime must
+    /// still be alive at shutdown and must not be a current-thread runtime.
+    #[track_caller]
+    pub fn register_sqlx_pool<DB>(&self, config: HookConfig, pool: sqlx::Pool<DB>)
+    where
+        DB: sqlx::Database,
+    {
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        self.register_async(config, move |_token| async move {
+            match runtime {
+                Some(runtime) => {
+                    // a JoinError only occurs if the runtime is gone; nothing left to close then
+                    let _ = runtime.spawn(async move { pool.close().await }).await;
+                }
+                None => pool.close().await,
+            }
+        });
+    }
+}
+
+#[cfg(feature = "deadpool")]
+impl Registry {
+    /// Registers a hook that closes the [`deadpool::managed::Pool`]. Idle objects
+    /// are dropped immediately, objects in use are dropped when they are returned.
+    #[track_caller]
+    pub fn register_deadpool<M>(&self, config: HookConfig, pool: deadpool::managed::Pool<M>)
+    where
+        M: deadpool::managed::Manager + 'static,
+    {
+        self.register(config, move || pool.close());
+    }
+}
+
+#[cfg(feature = "r2d2")]
+impl Registry {
+    /// Registers a hook that waits until all connections of the [`r2d2::Pool`]
+    /// are returned and drops it. r2d2 closes the connections once the last clone
+    /// of the pool is dropped. The hook times out if connections are still in use
+    /// at its deadline.
+    #[track_caller]
+    pub fn register_r2d2_pool<M>(&self, config: HookConfig, pool: r2d2::Pool<M>)
+    where
+        M: r2d2::ManageConnection,
+    {
+        use crate::HookOutcome;
+        use std::time::{Duration, Instant};
+
+        self.register_outcome(config, move |ctx| loop {
+            let state = pool.state();
+            if state.connections == state.idle_connections {
+                return HookOutcome::Completed;
+            }
+            if ctx.deadline().is_some_and(|d| Instant::now() >= d) {
+                return HookOutcome::TimedOut;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        });
+    }
+}
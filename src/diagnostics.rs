@@ -0,0 +1,117 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Output sink for the messages of the crate itself, e.g. the hooks that didn't
+//! finish before a forced exit.
+
+#[cfg(not(test))]
+use alloc::string::ToString;
+use core::fmt;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Severity of a diagnostic message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Something noteworthy happened, e.g. repeated shutdown signals.
+    Info,
+    /// Probably a mistake in the application, e.g. a hook registered twice.
+    Warn,
+    /// The shutdown didn't go as planned, e.g. hooks were lost.
+    Error,
+}
+
+/// Receives all diagnostic messages of the crate. Messages have no trailing
+/// newline and no `shutdown: ` prefix.
+pub type DiagnosticsSink = fn(Level, &str);
+
+/// The installed sink, null for [`DEFAULT_SINK`].
+static SINK: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Used until a sink is installed.
+#[cfg(feature = "std")]
+const DEFAULT_SINK: DiagnosticsSink = stderr_sink;
+#[cfg(not(feature = "std"))]
+const DEFAULT_SINK: DiagnosticsSink = |_, _| {};
+
+/// Installs `sink` for all diagnostic messages of the crate, e.g. to forward
+/// them to a logger or a file in apps without a console. Replaces the previous
+/// sink. The sink may be called from any thread, also while hooks run.
+///
+/// By default, messages go to stderr with `std` (see [`stderr_sink`]) and are
+/// discarded without it.
+pub fn set_diagnostics_sink(sink: DiagnosticsSink) {
+    SINK.store(sink as *mut (), Ordering::SeqCst);
+}
+
+/// The default sink with `std`: prints `shutdown: <message>` to stderr.
+#[cfg(feature = "std")]
+pub fn stderr_sink(_level: Level, msg: &str) {
+    eprintln!("shutdown: {}", msg);
+}
+
+/// Passes a message to the installed sink.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn emit(level: Level, args: fmt::Arguments) {
+    let ptr = SINK.load(Ordering::SeqCst);
+    let sink: DiagnosticsSink = if ptr.is_null() {
+        DEFAULT_SINK
+    } else {
+        // SAFETY: only `set_diagnostics_sink` stores non-null values, which are
+        // function pointers of this type
+        unsafe { core::mem::transmute::<*mut (), DiagnosticsSink>(ptr) }
+    };
+    match args.as_str() {
+        Some(msg) => sink(level, msg),
+        None => sink(level, &args.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    static MESSAGES: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+    fn capture(level: Level, msg: &str) {
+        if msg.starts_with("diagnostics test") {
+            MESSAGES.lock().unwrap().push((level, msg.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_sink() {
+        set_diagnostics_sink(capture);
+        emit(Level::Warn, format_args!("diagnostics test {}", 1));
+        emit(Level::Error, format_args!("diagnostics test"));
+        set_diagnostics_sink(stderr_sink);
+        emit(Level::Info, format_args!("diagnostics test on stderr"));
+        assert_eq!(
+            *MESSAGES.lock().unwrap(),
+            [
+                (Level::Warn, "diagnostics test 1".to_string()),
+                (Level::Error, "diagnostics test".to_string())
+            ]
+        );
+    }
+}
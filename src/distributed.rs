@@ -0,0 +1,100 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Hooks that are collected at link time from across the whole crate graph.
+//!
+//! Library crates can contribute hooks with [`crate::distributed_shutdown_hook`]
+//! without requiring the binary to call an init function. The hooks are added to
+//! the [`crate::global`] registry when it is accessed the first time. Hence, they
+//! run after all hooks that the application registers at runtime.
+
+use crate::{HookConfig, Registry};
+
+/// A hook that was declared with [`crate::distributed_shutdown_hook`].
+#[derive(Debug)]
+pub struct DistributedHook {
+    name: &'static str,
+    f: fn(),
+    origin: Option<&'static str>,
+}
+
+impl DistributedHook {
+    /// Constructor. Used by [`crate::distributed_shutdown_hook`].
+    pub const fn new(name: &'static str, f: fn()) -> Self {
+        Self {
+            name,
+            f,
+            origin: None,
+        }
+    }
+
+    /// See [`HookConfig::origin`]. Used by [`crate::distributed_shutdown_hook`].
+    pub const fn origin(self, module_path: &'static str) -> Self {
+        Self {
+            origin: Some(module_path),
+            ..self
+        }
+    }
+}
+
+/// All hooks declared with [`crate::distributed_shutdown_hook`] in the crate
+/// graph.
+#[linkme::distributed_slice]
+pub static DISTRIBUTED_HOOKS: [DistributedHook] = [..];
+
+/// Registers all [`DISTRIBUTED_HOOKS`] in `registry`.
+pub(crate) fn register_all(registry: &Registry) {
+    for hook in DISTRIBUTED_HOOKS {
+        let config = HookConfig::new(hook.name);
+        let config = match hook.origin {
+            Some(origin) => config.origin(origin),
+            None => config,
+        };
+        registry.register(config, hook.f);
+    }
+}
+
+/// Declares a hook that is collected at link time and added to the
+/// [`crate::global`] registry automatically. The first argument is the name of
+/// the hook, the second one a function (pointer) without arguments.
+///
+/// ## Example
+/// ```ignore
+/// use simple_on_shutdown::distributed_shutdown_hook;
+///
+/// fn flush_metrics() {
+///     println!("flushing metrics");
+/// }
+///
+/// distributed_shutdown_hook!(FLUSH_METRICS, flush_metrics);
+/// ```
+#[macro_export]
+macro_rules! distributed_shutdown_hook {
+    ($name:ident, $f:expr) => {
+        #[$crate::__private::linkme::distributed_slice($crate::DISTRIBUTED_HOOKS)]
+        #[linkme(crate = $crate::__private::linkme)]
+        static $name: $crate::DistributedHook =
+            $crate::DistributedHook::new(::core::stringify!($name), $f)
+                .origin(::core::module_path!());
+    };
+}
@@ -0,0 +1,258 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Graceful draining of a [`TcpListener`] for servers that don't use a big
+//! framework.
+
+use crate::sync::{Condvar, Mutex};
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Wraps a [`TcpListener`] and tracks the connections it accepted. At shutdown,
+/// the drain hook (see [`Registry::register_drain`]) stops accepting new
+/// connections and waits until all open connections are closed or the deadline
+/// of the hook expires. Subsequent hooks run afterwards.
+///
+/// ## Example
+/// ```no_run
+/// use simple_on_shutdown::{global, DrainGuard, HookConfig};
+/// use std::net::TcpListener;
+///
+/// let guard = DrainGuard::new(TcpListener::bind("localhost:8080").unwrap());
+/// global().register_drain(HookConfig::new("drain http"), &guard);
+/// // ends once the shutdown started
+/// while let Some(stream) = guard.accept().unwrap() {
+///     std::thread::spawn(move || {
+///         // the connection counts as open until `stream` is dropped
+///         drop(stream);
+///     });
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DrainGuard {
+    listener: TcpListener,
+    state: Arc<DrainState>,
+}
+
+/// State that is shared between the [`DrainGuard`], its connections and the hook.
+#[derive(Debug)]
+struct DrainState {
+    draining: AtomicBool,
+    /// Address to connect to in order to wake up a blocking `accept()`.
+    wake_addr: SocketAddr,
+    open: Mutex<usize>,
+    all_closed: Condvar,
+}
+
+impl DrainGuard {
+    /// Constructor.
+    pub fn new(listener: TcpListener) -> Self {
+        let wake_addr = listener.local_addr().map_or_else(
+            |_| SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            |mut addr| {
+                // connecting to the unspecified address doesn't work everywhere
+                if addr.ip().is_unspecified() {
+                    match addr {
+                        SocketAddr::V4(_) => addr.set_ip(Ipv4Addr::LOCALHOST.into()),
+                        SocketAddr::V6(_) => addr.set_ip(Ipv6Addr::LOCALHOST.into()),
+                    }
+                }
+                addr
+            },
+        );
+        Self {
+            listener,
+            state: Arc::new(DrainState {
+                draining: AtomicBool::new(false),
+                wake_addr,
+                open: Mutex::new(0),
+                all_closed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Accepts a new connection. Returns `None` once the shutdown started; the
+    /// accept loop should end then.
+    pub fn accept(&self) -> io::Result<Option<TrackedStream>> {
+        if self.is_draining() {
+            return Ok(None);
+        }
+        let (stream, _addr) = self.listener.accept()?;
+        // may be the connection that only wakes us up
+        if self.is_draining() {
+            return Ok(None);
+        }
+        *self.state.open.lock() += 1;
+        Ok(Some(TrackedStream {
+            stream,
+            state: self.state.clone(),
+        }))
+    }
+
+    /// Whether the shutdown started. Connection handlers can use this to stop
+    /// keeping connections alive.
+    pub fn is_draining(&self) -> bool {
+        self.state.draining.load(Ordering::SeqCst)
+    }
+
+    /// Number of accepted connections that are still open.
+    pub fn open_connections(&self) -> usize {
+        *self.state.open.lock()
+    }
+
+    /// The wrapped listener.
+    pub fn listener(&self) -> &TcpListener {
+        &self.listener
+    }
+}
+
+impl DrainState {
+    /// Stops accepting and waits until all connections are closed or `deadline`
+    /// expires. Returns whether all connections were closed.
+    fn drain(&self, deadline: Option<Instant>) -> bool {
+        self.draining.store(true, Ordering::SeqCst);
+        // wake up a blocking accept(); fails if nobody listens anymore, that's fine
+        let _ = TcpStream::connect(self.wake_addr);
+        let open = self.open.lock();
+        let open = match deadline {
+            None => self.all_closed.wait_while(open, |open| *open > 0),
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                self.all_closed
+                    .wait_timeout_while(open, timeout, |open| *open > 0)
+            }
+        };
+        *open == 0
+    }
+}
+
+/// A connection accepted by a [`DrainGuard`]. It counts as open until it is
+/// dropped.
+#[derive(Debug)]
+pub struct TrackedStream {
+    stream: TcpStream,
+    state: Arc<DrainState>,
+}
+
+impl Deref for TrackedStream {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        &self.stream
+    }
+}
+
+impl Read for TrackedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for TrackedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        let mut open = self.state.open.lock();
+        *open -= 1;
+        if *open == 0 {
+            self.state.all_closed.notify_all();
+        }
+    }
+}
+
+impl Registry {
+    /// Registers the drain hook of `guard`. It stops accepting new connections and
+    /// waits until all open connections are closed. If the deadline of the hook
+    /// expires first, it is reported as [`HookOutcome::TimedOut`] and the
+    /// remaining hooks run anyway.
+    #[track_caller]
+    pub fn register_drain(&self, config: HookConfig, guard: &DrainGuard) {
+        let state = guard.state.clone();
+        self.register_outcome(config, move |ctx: &ShutdownContext| {
+            if state.drain(ctx.deadline()) {
+                HookOutcome::Completed
+            } else {
+                HookOutcome::TimedOut
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_drain() {
+        let guard = Arc::new(DrainGuard::new(TcpListener::bind("127.0.0.1:0").unwrap()));
+        let registry = Registry::new();
+        registry.register_drain(HookConfig::new("drain"), &guard);
+
+        let addr = guard.listener().local_addr().unwrap();
+        let _client = TcpStream::connect(addr).unwrap();
+        let conn = guard.accept().unwrap().unwrap();
+        assert_eq!(guard.open_connections(), 1);
+
+        let guard_c = guard.clone();
+        let acceptor = thread::spawn(move || guard_c.accept().unwrap().is_none());
+        let closer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(conn);
+        });
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(report.is_success());
+        assert!(acceptor.join().unwrap());
+        closer.join().unwrap();
+        assert_eq!(guard.open_connections(), 0);
+    }
+
+    #[test]
+    fn test_drain_times_out() {
+        let guard = DrainGuard::new(TcpListener::bind("127.0.0.1:0").unwrap());
+        let registry = Registry::new();
+        registry.register_drain(
+            HookConfig::new("drain").timeout(Duration::from_millis(20)),
+            &guard,
+        );
+        let _client = TcpStream::connect(guard.listener().local_addr().unwrap()).unwrap();
+        let _conn = guard.accept().unwrap().unwrap();
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+    }
+}
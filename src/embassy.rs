@@ -0,0 +1,158 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Async hooks for embedded firmware that runs on an embassy executor.
+//!
+//! Unlike the [`crate::Registry`], this doesn't need `std`: hooks are awaited by
+//! an executor task once a shutdown or reset was requested, e.g. from an
+//! interrupt handler.
+
+#[cfg(not(test))]
+use alloc::{boxed::Box, vec::Vec};
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+
+/// Boxed async hook.
+type AsyncHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()>>> + Send>;
+
+/// Async hooks that an embassy task awaits after [`Self::request`] was called.
+/// Hooks run one after another in reverse order of their registration. Use
+/// `CriticalSectionRawMutex` as `M` if hooks are registered or the shutdown is
+/// requested from interrupts.
+///
+/// Hooks have no timeout; wrap their body with `embassy_time::with_timeout` if
+/// they may hang.
+///
+/// ## Example
+/// ```ignore
+/// use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+/// use simple_on_shutdown::EmbassyShutdown;
+///
+/// static SHUTDOWN: EmbassyShutdown<CriticalSectionRawMutex> = EmbassyShutdown::new();
+///
+/// #[embassy_executor::task]
+/// async fn shutdown_task() {
+///     SHUTDOWN.wait_and_run().await;
+///     cortex_m::peripheral::SCB::sys_reset();
+/// }
+///
+/// // during initialization
+/// SHUTDOWN.register(|| async { flash.flush().await });
+/// spawner.spawn(shutdown_task()).unwrap();
+///
+/// // e.g. in a reset button interrupt
+/// SHUTDOWN.request();
+/// ```
+pub struct EmbassyShutdown<M: RawMutex> {
+    requested: Signal<M, ()>,
+    hooks: Mutex<M, RefCell<Vec<AsyncHook>>>,
+    yielding: AtomicBool,
+}
+
+impl<M: RawMutex> EmbassyShutdown<M> {
+    /// Constructor. Can be used in statics.
+    pub const fn new() -> Self {
+        Self {
+            requested: Signal::new(),
+            hooks: Mutex::new(RefCell::new(Vec::new())),
+            yielding: AtomicBool::new(false),
+        }
+    }
+
+    /// Registers an async hook. `f` is called when the hooks are run and
+    /// returns the future that is awaited.
+    pub fn register<F, Fut>(&self, f: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let hook: AsyncHook = Box::new(move || Box::pin(f()));
+        self.hooks.lock(|hooks| hooks.borrow_mut().push(hook));
+    }
+
+    /// Requests the shutdown. Doesn't block and can be called from interrupt
+    /// handlers; the hooks are run by the task that awaits
+    /// [`Self::wait_and_run`].
+    pub fn request(&self) {
+        self.requested.signal(());
+    }
+
+    /// Whether [`Self::request`] was called and the hooks didn't run yet.
+    pub fn is_requested(&self) -> bool {
+        self.requested.signaled()
+    }
+
+    /// Waits for [`Self::request`] and runs the hooks afterwards.
+    pub async fn wait_and_run(&self) {
+        self.requested.wait().await;
+        self.run().await;
+    }
+
+    /// Yields to the executor between two hooks, so other tasks, e.g. ones that
+    /// keep a watchdog or a radio link alive, run during a long shutdown
+    /// sequence. Disabled by default.
+    pub fn set_yield_between_hooks(&self, yielding: bool) {
+        self.yielding.store(yielding, Ordering::Relaxed);
+    }
+
+    /// Runs all registered hooks one after another and removes them. Hooks that
+    /// are registered meanwhile run next.
+    pub async fn run(&self) {
+        let mut first = true;
+        while let Some(hook) = self.hooks.lock(|hooks| hooks.borrow_mut().pop()) {
+            if !first && self.yielding.load(Ordering::Relaxed) {
+                YieldNow(false).await;
+            }
+            first = false;
+            hook().await;
+        }
+    }
+}
+
+/// Returns `Pending` once, so the executor polls other tasks first.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+impl<M: RawMutex> Default for EmbassyShutdown<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,199 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Configuration overrides via environment variables. They allow operators to
+//! tune the shutdown behavior without a rebuild.
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Prefix of the environment variables that override the timeout of a hook.
+/// The hook `"flush db"` is addressed by `SHUTDOWN_TIMEOUT__FLUSH_DB`.
+pub const TIMEOUT_ENV_PREFIX: &str = "SHUTDOWN_TIMEOUT__";
+
+/// Name of the environment variable that overrides the timeout of the hook with
+/// the given name. Letters are upper-cased, everything that is not alphanumeric
+/// becomes `_`.
+pub fn timeout_env_var(hook_name: &str) -> String {
+    let suffix: String = hook_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", TIMEOUT_ENV_PREFIX, suffix)
+}
+
+/// Looks up the timeout override for the hook with the given name. Invalid
+/// values are ignored.
+pub(crate) fn timeout_override(hook_name: &str) -> Option<Duration> {
+    var(&timeout_env_var(hook_name)).and_then(|val| parse_duration(&val))
+}
+
+/// Reads an environment variable. `None` if it isn't set or isn't valid
+/// unicode.
+fn var(name: &str) -> Option<String> {
+    // tests must not call `std::env::set_var` while other tests read the
+    // environment on other threads
+    #[cfg(test)]
+    if let Some(val) = tests::var(name) {
+        return Some(val);
+    }
+    std::env::var(name).ok()
+}
+
+/// Environment variables and the time at the registration of a hook, see
+/// [`crate::HookConfig::capture_env`]. Helps to debug hooks whose behavior
+/// depends on configuration that changed before the shutdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EnvSnapshot {
+    /// When the hook was registered.
+    pub registered_at: SystemTime,
+    /// The captured variables in the order they were configured. `None` if a
+    /// variable wasn't set or wasn't valid unicode.
+    pub vars: Vec<(String, Option<String>)>,
+}
+
+impl EnvSnapshot {
+    /// Captures the current values of `vars`.
+    pub(crate) fn capture(vars: &[String]) -> Self {
+        Self {
+            registered_at: SystemTime::now(),
+            vars: vars.iter().map(|name| (name.clone(), var(name))).collect(),
+        }
+    }
+}
+
+impl fmt::Display for EnvSnapshot {
+    /// E.g. `registered at 1700000000.250s (unix time), DB_URL=db:5432, LOG unset`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self
+            .registered_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        write!(
+            f,
+            "registered at {}.{:03}s (unix time)",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis()
+        )?;
+        for (var, val) in &self.vars {
+            match val {
+                Some(val) => write!(f, ", {}={}", var, val)?,
+                None => write!(f, ", {} unset", var)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses durations like `5s`, `500ms`, `2m` or `1h`. A plain number is
+/// interpreted as seconds.
+pub(crate) fn parse_duration(val: &str) -> Option<Duration> {
+    let val = val.trim();
+    let split = val.find(|c: char| !c.is_ascii_digit()).unwrap_or(val.len());
+    let (num, unit) = val.split_at(split);
+    let num: u64 = num.parse().ok()?;
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(num)),
+        "" | "s" => Some(Duration::from_secs(num)),
+        "m" => Some(Duration::from_secs(num * 60)),
+        "h" => Some(Duration::from_secs(num * 60 * 60)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Variables that are set for the tests, see [`set_var`].
+    static VARS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+    /// Replaces [`std::env::set_var`] in tests. Each test uses its own names.
+    pub(crate) fn set_var(name: &str, val: &str) {
+        let mut vars = VARS.lock().unwrap();
+        vars.retain(|(n, _)| n != name);
+        vars.push((name.to_string(), val.to_string()));
+    }
+
+    pub(super) fn var(name: &str) -> Option<String> {
+        let vars = VARS.lock().unwrap();
+        vars.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn test_timeout_env_var() {
+        assert_eq!(timeout_env_var("flush_db"), "SHUTDOWN_TIMEOUT__FLUSH_DB");
+        assert_eq!(
+            timeout_env_var("flush-db 2"),
+            "SHUTDOWN_TIMEOUT__FLUSH_DB_2"
+        );
+    }
+
+    #[test]
+    fn test_env_snapshot() {
+        set_var("SHUTDOWN_TEST_SNAPSHOT", "db:5432");
+        let snapshot = EnvSnapshot::capture(&[
+            "SHUTDOWN_TEST_SNAPSHOT".to_string(),
+            "SHUTDOWN_TEST_UNSET".to_string(),
+        ]);
+        set_var("SHUTDOWN_TEST_SNAPSHOT", "changed");
+        assert_eq!(
+            snapshot.vars,
+            [
+                (
+                    "SHUTDOWN_TEST_SNAPSHOT".to_string(),
+                    Some("db:5432".to_string())
+                ),
+                ("SHUTDOWN_TEST_UNSET".to_string(), None)
+            ]
+        );
+        let snapshot = EnvSnapshot {
+            registered_at: UNIX_EPOCH + Duration::from_millis(1_700_000_000_250),
+            ..snapshot
+        };
+        assert_eq!(
+            snapshot.to_string(),
+            "registered at 1700000000.250s (unix time), SHUTDOWN_TEST_SNAPSHOT=db:5432, \
+             SHUTDOWN_TEST_UNSET unset"
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5s"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_duration("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration(" 1h "), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("5 days"), None);
+        assert_eq!(parse_duration("s"), None);
+    }
+}
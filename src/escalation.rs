@@ -0,0 +1,277 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Escalation ladder for hooks that exceed their timeout.
+
+use crate::diagnostics::{self, Level};
+use crate::{HookInfo, HookObserver, HookOutcome};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Steps of an [`EscalationPolicy`]. Reported to
+/// [`HookObserver::escalated`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EscalationStage {
+    /// The hook exceeded the warning threshold.
+    Warn,
+    /// The hook is asked to stop, see [`crate::ShutdownContext::is_cancelled`].
+    Cancel,
+    /// The hook keeps running on its thread but the shutdown sequence continues
+    /// without it. It is reported as [`HookOutcome::TimedOut`].
+    Detach,
+    /// The process is aborted.
+    Abort,
+}
+
+/// What happens if a hook takes longer than its timeout `T` (or the remaining
+/// global budget, if that is shorter). The thresholds are percentages of `T`.
+///
+/// The default ladder warns at `T`, asks for cooperative cancellation at `1.5T`
+/// and detaches the hook at `2T`. Aborting the process is opt-in, see
+/// [`Self::abort_after`].
+///
+/// Can be configured globally with [`crate::Registry::set_escalation_policy`]
+/// and per hook with [`crate::HookConfig::escalation`]. Hooks with a policy run
+/// on a dedicated thread, so the sequence can continue without them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EscalationPolicy {
+    warn_at: u32,
+    cancel_at: u32,
+    detach_at: u32,
+    abort_after: Option<Duration>,
+}
+
+impl EscalationPolicy {
+    /// Constructor. Creates the default ladder.
+    pub const fn new() -> Self {
+        Self {
+            warn_at: 100,
+            cancel_at: 150,
+            detach_at: 200,
+            abort_after: None,
+        }
+    }
+
+    /// Sets when [`EscalationStage::Warn`] happens, in percent of the timeout.
+    pub fn warn_at(mut self, percent: u32) -> Self {
+        self.warn_at = percent;
+        self
+    }
+
+    /// Sets when [`EscalationStage::Cancel`] happens, in percent of the timeout.
+    pub fn cancel_at(mut self, percent: u32) -> Self {
+        self.cancel_at = percent;
+        self
+    }
+
+    /// Sets when [`EscalationStage::Detach`] happens, in percent of the timeout.
+    pub fn detach_at(mut self, percent: u32) -> Self {
+        self.detach_at = percent;
+        self
+    }
+
+    /// Aborts the process if the hook is still running after `limit`, counted
+    /// from the start of the hook. This is the hard limit for hooks that must not
+    /// be left behind, e.g. because they hold a lock that other hooks need.
+    ///
+    /// The limit may lie before or after [`EscalationStage::Detach`]. If it lies
+    /// after, the sequence continues without the hook and a watchdog thread
+    /// aborts the process once the detached hook reaches the limit.
+    pub fn abort_after(mut self, limit: Duration) -> Self {
+        self.abort_after = Some(limit);
+        self
+    }
+
+    /// Points in time, relative to the start of the hook, at which the stages
+    /// happen. Sorted by time.
+    fn ladder(&self, timeout: Duration) -> Vec<(Duration, EscalationStage)> {
+        let at = |percent: u32| timeout * percent / 100;
+        let mut ladder = vec![
+            (at(self.warn_at), EscalationStage::Warn),
+            (at(self.cancel_at), EscalationStage::Cancel),
+            (at(self.detach_at), EscalationStage::Detach),
+        ];
+        if let Some(limit) = self.abort_after {
+            ladder.push((limit, EscalationStage::Abort));
+        }
+        ladder.sort_by_key(|&(at, _)| at);
+        ladder
+    }
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs `f` on a dedicated thread and escalates according to `policy` while
+/// waiting for it. Panics of `f` are propagated.
+///
+/// ## Parameters
+/// * `timeout` time after which the hook is late
+/// * `cancelled` flag that is set at [`EscalationStage::Cancel`]
+pub(crate) fn run_with_escalation<F>(
+    policy: EscalationPolicy,
+    timeout: Duration,
+    info: &HookInfo,
+    observers: &[Arc<dyn HookObserver>],
+    cancelled: &AtomicBool,
+    f: F,
+) -> HookOutcome
+where
+    F: FnOnce() -> HookOutcome + Send + 'static,
+{
+    let begin = Instant::now();
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::Builder::new()
+        .name("shutdown-hook".to_string())
+        .spawn(move || {
+            // the receiver is gone if the hook was detached
+            let _ = tx.send(f());
+        })
+        .expect("should spawn hook thread");
+
+    for (at, stage) in policy.ladder(timeout) {
+        match rx.recv_timeout((begin + at).saturating_duration_since(Instant::now())) {
+            Ok(outcome) => return outcome,
+            Err(RecvTimeoutError::Disconnected) => {
+                let panic = handle
+                    .join()
+                    .expect_err("hook thread only ends without a result by panicking");
+                std::panic::resume_unwind(panic)
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        for observer in observers {
+            observer.escalated(info, stage);
+        }
+        match stage {
+            EscalationStage::Warn => diagnostics::emit(
+                Level::Warn,
+                format_args!(
+                    "hook '{}' still runs after {:?}, its timeout is {:?}",
+                    info.name,
+                    begin.elapsed(),
+                    timeout
+                ),
+            ),
+            EscalationStage::Cancel => cancelled.store(true, Ordering::SeqCst),
+            EscalationStage::Detach => {
+                if let Some(limit) = policy.abort_after {
+                    watch_detached(rx, begin + limit, info.clone(), observers.to_vec(), abort);
+                }
+                return HookOutcome::TimedOut;
+            }
+            EscalationStage::Abort => abort(),
+        }
+    }
+    unreachable!("the ladder always contains EscalationStage::Detach")
+}
+
+/// Keeps watching a detached hook and calls `on_abort` if it hasn't finished
+/// by `deadline`. A hook that finishes or panics in time ends the watchdog.
+fn watch_detached<T: Send + 'static>(
+    rx: mpsc::Receiver<T>,
+    deadline: Instant,
+    info: HookInfo,
+    observers: Vec<Arc<dyn HookObserver>>,
+    on_abort: fn(),
+) {
+    thread::Builder::new()
+        .name("shutdown-watchdog".to_string())
+        .spawn(move || {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(remaining) {
+                for observer in &observers {
+                    observer.escalated(&info, EscalationStage::Abort);
+                }
+                on_abort();
+            }
+        })
+        .expect("should spawn watchdog thread");
+}
+
+fn abort() {
+    crate::global::report_lost_hooks();
+    std::process::abort()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ladder() {
+        let ladder = EscalationPolicy::new()
+            .abort_after(Duration::from_millis(120))
+            .ladder(Duration::from_millis(100));
+        assert_eq!(
+            ladder,
+            [
+                (Duration::from_millis(100), EscalationStage::Warn),
+                (Duration::from_millis(120), EscalationStage::Abort),
+                (Duration::from_millis(150), EscalationStage::Cancel),
+                (Duration::from_millis(200), EscalationStage::Detach),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_abort_after_detach() {
+        static ABORTED: AtomicBool = AtomicBool::new(false);
+        fn on_abort() {
+            ABORTED.store(true, Ordering::SeqCst);
+        }
+        let info = HookInfo::from(&crate::HookConfig::new("late"));
+
+        // finishes before the limit
+        let (tx, rx) = mpsc::channel();
+        watch_detached(
+            rx,
+            Instant::now() + Duration::from_millis(100),
+            info.clone(),
+            vec![],
+            on_abort,
+        );
+        tx.send(()).unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert!(!ABORTED.load(Ordering::SeqCst));
+
+        // still running at the limit
+        let (_tx, rx) = mpsc::channel::<()>();
+        watch_detached(
+            rx,
+            Instant::now() + Duration::from_millis(50),
+            info,
+            vec![],
+            on_abort,
+        );
+        thread::sleep(Duration::from_millis(200));
+        assert!(ABORTED.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,184 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Recurring events like a configuration reload. Unlike shutdown hooks, event
+//! hooks are not consumed: they run on every emission of their event.
+
+use crate::sync::Mutex;
+use crate::{HookConfig, HookError, HookInfo, HookOutcome, HookResult, Registry, ShutdownReport};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// An event that is emitted with [`Registry::emit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Event {
+    /// Re-read the configuration, rotate log files etc. Emitted on `SIGHUP`
+    /// after `install_reload_handler()` (feature `signals`, UNIX).
+    Reload,
+    /// First diagnostic event, e.g. "dump internal state". Emitted on `SIGUSR1`
+    /// after `install_diagnostic_handlers()` (feature `signals`, UNIX).
+    User1,
+    /// Second diagnostic event. Emitted on `SIGUSR2` after
+    /// `install_diagnostic_handlers()` (feature `signals`, UNIX).
+    User2,
+    /// The system is low on memory; caches should be dropped. Emitted by
+    /// `lifecycle::memory_warning()` (feature `mobile`).
+    LowMemory,
+    /// The app moved to the background and may be suspended or killed without
+    /// further notice; state should be persisted. Emitted by
+    /// `lifecycle::entered_background()` (feature `mobile`).
+    Background,
+    /// Application-defined event.
+    Custom(&'static str),
+}
+
+/// Boxed event hook. Can be called multiple times.
+type EventFn = Box<dyn FnMut() -> HookOutcome + Send>;
+
+/// A hook that was registered for an [`Event`].
+pub(crate) struct EventHook {
+    event: Event,
+    config: HookConfig,
+    /// Locked while the hook runs.
+    f: Mutex<EventFn>,
+}
+
+impl Registry {
+    /// Registers a hook that runs every time `event` is emitted, see
+    /// [`Self::emit`]. Event hooks stay registered when the registry is
+    /// triggered.
+    pub fn register_event<F>(&self, event: Event, config: HookConfig, mut f: F)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.register_event_outcome(event, config, move || {
+            f();
+            HookOutcome::Completed
+        });
+    }
+
+    /// Registers a hook that can fail and runs every time `event` is emitted. The
+    /// error ends up in the report of [`Self::emit`].
+    pub fn register_event_fallible<F, E>(&self, event: Event, config: HookConfig, mut f: F)
+    where
+        F: FnMut() -> Result<(), E> + Send + 'static,
+        E: Into<HookError>,
+    {
+        self.register_event_outcome(event, config, move || match f() {
+            Ok(()) => HookOutcome::Completed,
+            Err(err) => HookOutcome::Failed(err.into()),
+        });
+    }
+
+    /// Registers an event hook that determines its [`HookOutcome`] by itself.
+    fn register_event_outcome<F>(&self, event: Event, config: HookConfig, f: F)
+    where
+        F: FnMut() -> HookOutcome + Send + 'static,
+    {
+        let hook = EventHook {
+            event,
+            config,
+            f: Mutex::new(Box::new(f)),
+        };
+        self.event_hooks().lock().push(Arc::new(hook));
+    }
+
+    /// Executes all hooks registered for `event` in registration order. The
+    /// observers of the registry are notified like for shutdown hooks. If the
+    /// same event is emitted concurrently, each hook runs once per emission but
+    /// never in parallel with itself.
+    pub fn emit(&self, event: Event) -> ShutdownReport {
+        // don't hold the lock while hooks run, they may register new hooks
+        let hooks: Vec<_> = self
+            .event_hooks()
+            .lock()
+            .iter()
+            .filter(|h| h.event == event)
+            .cloned()
+            .collect();
+        let observers = self.observers();
+        let results = hooks
+            .iter()
+            .map(|hook| {
+                let mut f = hook.f.lock();
+                let info = HookInfo::from(&hook.config);
+                for observer in &observers {
+                    observer.before(&info);
+                }
+                let begin = Instant::now();
+                let outcome = f();
+                let duration = begin.elapsed();
+                for observer in &observers {
+                    observer.after(&info, &outcome);
+                }
+                HookResult {
+                    name: info.name,
+                    outcome,
+                    duration,
+                    env: None,
+                }
+            })
+            .collect();
+        ShutdownReport {
+            results,
+            exit: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_hooks_run_on_every_emission() {
+        let registry = Registry::new();
+        let reloads = Arc::new(Mutex::new(0));
+        let reloads_c = reloads.clone();
+        registry.register_event(
+            Event::Reload,
+            HookConfig::new("re-read config"),
+            move || *reloads_c.lock().unwrap() += 1,
+        );
+        registry.register_event_fallible(
+            Event::Custom("rotate"),
+            HookConfig::new("rotate logs"),
+            || Err("read-only file system"),
+        );
+
+        for _ in 0..3 {
+            assert!(registry.emit(Event::Reload).is_success());
+        }
+        assert_eq!(*reloads.lock().unwrap(), 3);
+        let report = registry.emit(Event::Custom("rotate"));
+        assert_eq!(report.failures().count(), 1);
+
+        // event hooks are not shutdown hooks
+        assert!(registry.is_empty());
+        registry.run(ShutdownReason::Exit, None);
+        assert_eq!(registry.emit(Event::Reload).results.len(), 1);
+    }
+}
@@ -0,0 +1,88 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Minimal executor that drives async hooks on the thread that runs the hooks.
+
+use crate::CancellationToken;
+use core::future::Future;
+use core::task::{Context, Poll, Waker};
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+/// Wakes the thread that executes [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `fut` on the current thread until it completes, without any deadline.
+/// Used by [`crate::on_shutdown_async`].
+pub fn block_on_guard<F>(fut: F)
+where
+    F: Future<Output = ()>,
+{
+    block_on(fut, None, Duration::from_secs(0), &CancellationToken::new());
+}
+
+/// Polls `fut` on the current thread until it completes. If `deadline` expires
+/// first, `token` gets cancelled and the future has `grace` more time to finish.
+/// After that it gets dropped. Returns whether the future completed.
+pub(crate) fn block_on<F>(
+    fut: F,
+    deadline: Option<Instant>,
+    grace: Duration,
+    token: &CancellationToken,
+) -> bool
+where
+    F: Future<Output = ()>,
+{
+    let mut fut = Box::pin(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    // only set after the token was cancelled
+    let mut hard_deadline = None;
+    loop {
+        if let Poll::Ready(()) = fut.as_mut().poll(&mut cx) {
+            return true;
+        }
+        let now = Instant::now();
+        match (hard_deadline, deadline) {
+            (Some(hard), _) if now >= hard => return false,
+            (None, Some(soft)) if now >= soft => {
+                token.cancel();
+                hard_deadline = Some(now + grace);
+                continue;
+            }
+            _ => {}
+        }
+        match hard_deadline.or(deadline) {
+            Some(until) => thread::park_timeout(until.saturating_duration_since(now)),
+            None => thread::park(),
+        }
+    }
+}
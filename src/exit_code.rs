@@ -0,0 +1,77 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Exit codes that tell init systems and wrappers why the process ended.
+
+use crate::sync::Mutex;
+use crate::ShutdownReason;
+
+/// Exit code of a Rust program whose main thread panicked.
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Maps the reason of a shutdown to an exit code.
+type ExitCodeFn = fn(ShutdownReason) -> i32;
+
+/// Custom mapping installed with [`set_exit_code_fn`].
+static EXIT_CODE_FN: Mutex<Option<ExitCodeFn>> = Mutex::new(None);
+
+/// Replaces the conventional mapping of [`exit_code_for`], e.g. to report all
+/// signal-initiated shutdowns as success.
+pub fn set_exit_code_fn(f: ExitCodeFn) {
+    *EXIT_CODE_FN.lock() = Some(f);
+}
+
+/// Exit code the process should end with after a shutdown for `reason`. Unless
+/// changed with [`set_exit_code_fn`], this follows the usual conventions:
+/// - `0` for a regular or requested shutdown and a shutdown of the machine
+/// - `128 + signal` for a signal-initiated shutdown, e.g. `143` for `SIGTERM`
+/// - [`PANIC_EXIT_CODE`] after a panic
+///
+/// The built-in signal handling exits with this code after the hooks ran.
+pub fn exit_code_for(reason: ShutdownReason) -> i32 {
+    if let Some(f) = *EXIT_CODE_FN.lock() {
+        return f(reason);
+    }
+    match reason {
+        ShutdownReason::Exit
+        | ShutdownReason::Requested
+        | ShutdownReason::SystemShutdown
+        | ShutdownReason::Rehearsal => 0,
+        ShutdownReason::Signal(sig) => 128 + sig,
+        ShutdownReason::Panic => PANIC_EXIT_CODE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conventional_exit_codes() {
+        assert_eq!(exit_code_for(ShutdownReason::Exit), 0);
+        assert_eq!(exit_code_for(ShutdownReason::SystemShutdown), 0);
+        assert_eq!(exit_code_for(ShutdownReason::Signal(15)), 143);
+        assert_eq!(exit_code_for(ShutdownReason::Signal(2)), 130);
+        assert_eq!(exit_code_for(ShutdownReason::Panic), 101);
+    }
+}
@@ -0,0 +1,203 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Hooks that make written data durable before the process exits.
+//!
+//! A file is only durable after `fsync()` of the file itself and of its parent
+//! directory, which holds the directory entry of a new file. Both calls are
+//! retried on `EINTR`.
+
+use crate::sync::Mutex;
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Files and directories that are synced to disk at shutdown, see
+/// [`Registry::register_fsync`]. Cloned handles share the same paths, so the
+/// application can add files as it creates them.
+#[derive(Clone)]
+pub struct FsyncPaths(Arc<Mutex<Vec<PathBuf>>>);
+
+impl FsyncPaths {
+    /// Constructor. Creates an empty set.
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Vec::new())))
+    }
+
+    /// Adds a file or directory. Paths that were already added are ignored.
+    pub fn add(&self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let mut paths = self.0.lock();
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    /// Removes a path, e.g. after the file was deleted.
+    pub fn remove(&self, path: &Path) {
+        self.0.lock().retain(|p| p != path);
+    }
+
+    /// Number of paths.
+    pub fn len(&self) -> usize {
+        self.0.lock().len()
+    }
+
+    /// Whether no paths were added.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for FsyncPaths {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Into<PathBuf>> core::iter::FromIterator<P> for FsyncPaths {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let paths = Self::new();
+        iter.into_iter().for_each(|p| paths.add(p));
+        paths
+    }
+}
+
+impl Registry {
+    /// Registers a hook that syncs all `paths` to disk: first the files and
+    /// directories themselves, then their parent directories, each directory
+    /// only once. All of them share the deadline of the hook; paths that are
+    /// left when it expires are not synced and the hook is reported as
+    /// [`HookOutcome::TimedOut`]. A path that can't be synced doesn't stop the
+    /// others, the hook fails with all errors afterwards.
+    ///
+    /// Directories can only be synced on UNIX. On other platforms, only files
+    /// are synced.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use simple_on_shutdown::{FsyncPaths, HookConfig, Registry, ShutdownReason};
+    /// use std::time::Duration;
+    ///
+    /// let registry = Registry::new();
+    /// let paths = FsyncPaths::new();
+    /// registry.register_fsync(HookConfig::new("fsync").timeout(Duration::from_secs(5)), paths.clone());
+    ///
+    /// let log = std::env::temp_dir().join("fsync-example.log");
+    /// std::fs::write(&log, "done").unwrap();
+    /// paths.add(&log);
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// # std::fs::remove_file(log).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn register_fsync(&self, config: HookConfig, paths: FsyncPaths) {
+        self.register_outcome(config, move |ctx| sync_paths(ctx, &paths.0.lock()));
+    }
+}
+
+/// Syncs `paths` and their parent directories within the deadline of `ctx`.
+fn sync_paths(ctx: &ShutdownContext, paths: &[PathBuf]) -> HookOutcome {
+    let parents = paths
+        .iter()
+        .filter_map(|p| p.parent())
+        .filter(|p| cfg!(unix) && !p.as_os_str().is_empty())
+        .collect::<BTreeSet<_>>();
+    let all = paths
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|p| !parents.contains(p))
+        .chain(parents.iter().copied());
+    let mut errors = Vec::new();
+    for path in all {
+        if matches!(ctx.remaining(), Some(r) if r == Duration::from_secs(0)) {
+            return HookOutcome::TimedOut;
+        }
+        if let Err(err) = sync(path) {
+            errors.push(format!("{}: {}", path.display(), err));
+        }
+    }
+    if errors.is_empty() {
+        HookOutcome::Completed
+    } else {
+        HookOutcome::Failed(format!("fsync failed for {}", errors.join(", ")).into())
+    }
+}
+
+/// `fsync()` of a single file or directory, retried on `EINTR`.
+fn sync(path: &Path) -> io::Result<()> {
+    loop {
+        match File::open(path).and_then(|f| f.sync_all()) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fsync-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, name).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_files_are_synced() {
+        let registry = Registry::new();
+        let paths = [temp_file("a"), temp_file("b")]
+            .iter()
+            .collect::<FsyncPaths>();
+        paths.add(temp_file("a"));
+        assert_eq!(paths.len(), 2);
+        registry.register_fsync(HookConfig::new("fsync"), paths);
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    }
+
+    #[test]
+    fn test_missing_file_fails_hook() {
+        let registry = Registry::new();
+        let missing = temp_file("c").with_file_name("missing");
+        let paths = FsyncPaths::new();
+        paths.add(&missing);
+        paths.add(temp_file("d"));
+        registry.register_fsync(HookConfig::new("fsync"), paths);
+        let report = registry.run(ShutdownReason::Exit, None);
+        let outcome = report.results[0].outcome.to_string();
+        assert!(
+            outcome.starts_with("failed: fsync failed for "),
+            "{}",
+            outcome
+        );
+        assert!(outcome.contains("missing"), "{}", outcome);
+        assert!(!outcome.contains(", "), "{}", outcome);
+    }
+}
@@ -0,0 +1,238 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Process-wide [`Registry`] for hooks that live until the program ends.
+//!
+//! All global state of the crate is initialized lazily on first use, from
+//! whichever thread gets there first. Libraries can use it without any
+//! cooperation from `main()`.
+
+use crate::diagnostics::{self, Level};
+use crate::sync::Mutex;
+use crate::{Registry, ShutdownError, ShutdownReason, ShutdownReport};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, OnceLock};
+
+/// The process-wide registry.
+static GLOBAL: OnceLock<Registry> = OnceLock::new();
+
+/// Returns the process-wide [`Registry`]. Libraries can register their hooks
+/// here without getting a handle passed from the application.
+pub fn global() -> &'static Registry {
+    GLOBAL.get_or_init(|| {
+        let registry = Registry::new();
+        #[cfg(feature = "distributed")]
+        crate::distributed::register_all(&registry);
+        registry
+    })
+}
+
+/// Registries created with [`Registry::named`].
+static NAMED: OnceLock<Mutex<HashMap<String, Arc<Registry>>>> = OnceLock::new();
+
+impl Registry {
+    /// Returns the registry with the given name and creates it on first use.
+    /// Crates of a workspace can target a specific registry, e.g. `"http"` or
+    /// `"jobs"`, without passing handles through every constructor.
+    ///
+    /// Named registries are children of the [`global`] registry (see
+    /// [`Registry::child`]): their hooks run before the hooks of the global
+    /// registry. Each of them can also be triggered on its own.
+    pub fn named(name: &str) -> Arc<Registry> {
+        let mut named = NAMED.get_or_init(|| Mutex::new(HashMap::new())).lock();
+        if let Some(registry) = named.get(name) {
+            return registry.clone();
+        }
+        let registry = Arc::new(Registry::new());
+        global().add_child(Arc::downgrade(&registry));
+        named.insert(name.to_string(), registry.clone());
+        registry
+    }
+}
+
+/// How the process ends. Decided by whoever starts to end the process first:
+/// [`exit`], a shutdown signal, a panic of the main thread or the `atexit()`
+/// bridge. All later attempts respect this decision, so the hooks of the
+/// [`global`] registry run exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExitDecision {
+    /// Why the process ends.
+    pub reason: ShutdownReason,
+    /// The exit code. `None` if the process was ended outside of this crate,
+    /// e.g. by [`std::process::exit`], see [`crate::install_atexit_bridge`].
+    pub code: Option<i32>,
+}
+
+/// The decision of the process, see [`ExitDecision`].
+static EXIT: OnceLock<ExitDecision> = OnceLock::new();
+
+/// Returns how the process ends, if that was already decided.
+pub fn exit_decision() -> Option<ExitDecision> {
+    EXIT.get().copied()
+}
+
+/// Records `decision` unless a decision was made before. Returns the decision
+/// that is in effect: `Ok` if it is `decision`, `Err` with the earlier one
+/// otherwise.
+pub(crate) fn claim_exit(decision: ExitDecision) -> Result<ExitDecision, ExitDecision> {
+    claim(&EXIT, decision)
+}
+
+fn claim(
+    cell: &OnceLock<ExitDecision>,
+    decision: ExitDecision,
+) -> Result<ExitDecision, ExitDecision> {
+    match cell.set(decision) {
+        Ok(()) => Ok(decision),
+        Err(_) => Err(*cell.get().expect("should be set")),
+    }
+}
+
+/// Runs the hooks of the [`global`] registry for a claimed `decision`.
+pub(crate) fn run_exit_hooks(decision: ExitDecision) -> ShutdownReport {
+    global().run_exit(decision)
+}
+
+/// Runs the hooks of the [`global`] registry with [`ShutdownReason::Exit`] and
+/// ends the process with `code`. Replacement for [`std::process::exit`].
+///
+/// If the process is already ending, e.g. because a shutdown signal arrived,
+/// the hooks don't run a second time and the earlier exit code wins: this
+/// blocks until the process ends. Called by a hook while the process ends, it
+/// ends the process immediately, see [`force_exit`].
+pub fn exit(code: i32) -> ! {
+    let decision = ExitDecision {
+        reason: ShutdownReason::Exit,
+        code: Some(code),
+    };
+    match claim_exit(decision) {
+        Ok(decision) => {
+            // the report can't be returned anymore; failed hooks don't change the exit code
+            let _ = run_exit_hooks(decision);
+            std::process::exit(code)
+        }
+        Err(earlier) if crate::registry::is_in_hook() => {
+            diagnostics::emit(
+                Level::Error,
+                format_args!(
+                    "a hook called exit({}) while the process ends ({:?})",
+                    code, earlier.reason
+                ),
+            );
+            force_exit(earlier.code.unwrap_or(code))
+        }
+        Err(_) => loop {
+            // whoever decided ends the process
+            std::thread::park();
+        },
+    }
+}
+
+/// Ends the process immediately with `code`, without waiting for hooks that are
+/// still running, e.g. after a second `CTRL+C` or when a watchdog fires. The
+/// names of the hooks that didn't finish (see [`Registry::pending_hooks`]) of
+/// the [`global`] and all named registries go to the diagnostics sink first, so
+/// operators know which cleanup steps were lost.
+pub fn force_exit(code: i32) -> ! {
+    report_lost_hooks();
+    std::process::exit(code)
+}
+
+/// Reports the names of the hooks that didn't finish to the diagnostics sink.
+pub(crate) fn report_lost_hooks() {
+    let mut lost = Vec::new();
+    if let Some(named) = NAMED.get() {
+        let named = named.lock();
+        for registry in named.values() {
+            lost.extend(registry.pending_hooks());
+        }
+    }
+    lost.extend(global().pending_hooks());
+    if !lost.is_empty() {
+        diagnostics::emit(
+            Level::Error,
+            format_args!("forced exit, hooks that didn't finish: {}", lost.join(", ")),
+        );
+    }
+}
+
+/// Executes all hooks of the [`global`] registry. Hooks that are registered
+/// afterwards are handled according to the [`crate::LateRegistrationPolicy`].
+///
+/// Returns a [`ShutdownError`] with the results of all hooks if at least one of
+/// them didn't finish successfully.
+pub fn run_global_hooks(reason: ShutdownReason) -> Result<ShutdownReport, ShutdownError> {
+    global().run(reason, None).into_result()
+}
+
+/// Runs the fallible initialization `init` exactly once, even if multiple
+/// threads call this concurrently. All callers get the same result, also
+/// callers after a failed initialization.
+pub(crate) fn init_once<F>(cell: &OnceLock<io::Result<()>>, init: F) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<()>,
+{
+    match cell.get_or_init(init) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named() {
+        let http = Registry::named("http");
+        assert!(Arc::ptr_eq(&http, &Registry::named("http")));
+        assert!(!Arc::ptr_eq(&http, &Registry::named("jobs")));
+    }
+
+    #[test]
+    fn test_first_exit_decision_wins() {
+        static CELL: OnceLock<ExitDecision> = OnceLock::new();
+        let signal = ExitDecision {
+            reason: ShutdownReason::Signal(15),
+            code: Some(143),
+        };
+        let exit = ExitDecision {
+            reason: ShutdownReason::Exit,
+            code: Some(3),
+        };
+        assert_eq!(claim(&CELL, signal), Ok(signal));
+        assert_eq!(claim(&CELL, exit), Err(signal));
+    }
+
+    #[test]
+    fn test_init_once() {
+        static CELL: OnceLock<io::Result<()>> = OnceLock::new();
+        let fail = || Err(io::Error::other("no pipe"));
+        assert_eq!(init_once(&CELL, fail).unwrap_err().to_string(), "no pipe");
+        // the failure is sticky, the initialization doesn't run again
+        let err = init_once(&CELL, || unreachable!()).unwrap_err();
+        assert_eq!(err.to_string(), "no pipe");
+    }
+}
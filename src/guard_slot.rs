@@ -0,0 +1,173 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Guards that are parked in statics and run when the process ends.
+
+use crate::diagnostics::{self, Level};
+use crate::sync::Mutex;
+use std::io;
+use std::sync::OnceLock;
+
+/// Callback inside a [`StaticGuardSlot`].
+pub type SlotCallback = Box<dyn FnOnce() + Send>;
+
+/// Slots that were armed, in arming order.
+static ARMED: Mutex<Vec<&'static StaticGuardSlot>> = Mutex::new(Vec::new());
+
+/// A place for a single guard in a `static`. Library initialization code parks
+/// its cleanup there once and the callback runs when the process ends, like a
+/// guard with a global lifetime. Unlike the [`crate::global`] registry, there
+/// is no configuration, report or timeout: the callback simply runs in the
+/// `atexit()` handler, i.e. when `main()` returns or the process exits, also
+/// after the signal handling of this crate.
+///
+/// The callback can be taken out or replaced at any time, e.g. when the library
+/// is re-initialized. Slots run in reverse order of their first use.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown::StaticGuardSlot;
+///
+/// static POOL_CLEANUP: StaticGuardSlot = StaticGuardSlot::new();
+///
+/// fn init_pool(size: usize) {
+///     // the cleanup of an earlier initialization isn't needed anymore
+///     let _old = POOL_CLEANUP.replace(move || println!("closing {} connections", size));
+/// }
+///
+/// init_pool(4);
+/// init_pool(8);
+/// assert!(POOL_CLEANUP.is_occupied());
+/// ```
+pub struct StaticGuardSlot {
+    callback: Mutex<Option<SlotCallback>>,
+    /// Whether the slot is part of [`ARMED`].
+    armed: OnceLock<()>,
+}
+
+impl StaticGuardSlot {
+    /// Constructor. Creates an empty slot.
+    pub const fn new() -> Self {
+        Self {
+            callback: Mutex::new(None),
+            armed: OnceLock::new(),
+        }
+    }
+
+    /// Parks `f` in the slot, so it runs when the process ends. Returns the
+    /// callback that was parked before; it doesn't run unless the caller calls
+    /// it.
+    pub fn replace<F>(&'static self, f: F) -> Option<SlotCallback>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.arm();
+        self.callback.lock().replace(Box::new(f))
+    }
+
+    /// Takes the callback out of the slot. It doesn't run at the end of the
+    /// process anymore.
+    pub fn take(&self) -> Option<SlotCallback> {
+        self.callback.lock().take()
+    }
+
+    /// Whether a callback is parked in the slot.
+    pub fn is_occupied(&self) -> bool {
+        self.callback.lock().is_some()
+    }
+
+    /// Runs the callback now, if there is one. It doesn't run a second time at
+    /// the end of the process.
+    pub fn run(&self) {
+        // don't hold the lock, the callback may park a new one
+        let callback = self.take();
+        if let Some(callback) = callback {
+            callback();
+        }
+    }
+
+    /// Adds the slot to [`ARMED`] on first use.
+    fn arm(&'static self) {
+        self.armed.get_or_init(|| {
+            if let Err(err) = install() {
+                diagnostics::emit(
+                    Level::Error,
+                    format_args!("static guards won't run at exit: {}", err),
+                );
+            }
+            ARMED.lock().push(self);
+        });
+    }
+}
+
+impl Default for StaticGuardSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registers [`run_slots`] with `atexit()` once.
+fn install() -> io::Result<()> {
+    static INSTALL: OnceLock<io::Result<()>> = OnceLock::new();
+    crate::global::init_once(&INSTALL, || crate::install::at_exit(run_slots))
+}
+
+/// `atexit()` handler that runs all armed slots.
+extern "C" fn run_slots() {
+    let slots = core::mem::take(&mut *ARMED.lock());
+    for slot in slots.into_iter().rev() {
+        slot.run();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static SLOT: StaticGuardSlot = StaticGuardSlot::new();
+
+    #[test]
+    fn test_replace_and_take() {
+        let count = || {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        };
+        assert!(SLOT.replace(count).is_none());
+        // the previous callback is handed back without running
+        let previous = SLOT.replace(count).unwrap();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+        previous();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        SLOT.run();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+        assert!(!SLOT.is_occupied());
+        SLOT.run();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+
+        SLOT.replace(count);
+        assert!(SLOT.take().is_some());
+        assert!(SLOT.take().is_none());
+    }
+}
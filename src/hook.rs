@@ -0,0 +1,340 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Configuration that belongs to a single hook of a [`crate::Registry`].
+
+use crate::{EnvSnapshot, EscalationPolicy};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configuration of a single hook inside a [`crate::Registry`].
+///
+/// A hook always has a name. It is used in diagnostics and allows to address
+/// the hook from the outside.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookConfig {
+    name: String,
+    timeout: Option<Duration>,
+    cancel_grace: Duration,
+    priority: i32,
+    niceness: Option<i32>,
+    tags: Vec<String>,
+    importance: Importance,
+    escalation: Option<EscalationPolicy>,
+    origin: Option<&'static str>,
+    capture_env: Vec<String>,
+    env_snapshot: Option<EnvSnapshot>,
+    external: bool,
+    working_dir: Option<PathBuf>,
+    condition: Option<Condition>,
+}
+
+/// Predicate of [`HookConfig::enabled_if`]. Two conditions are equal if they
+/// are the same closure.
+#[derive(Clone)]
+struct Condition(Arc<dyn Fn() -> bool + Send + Sync>);
+
+impl fmt::Debug for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Condition(..)")
+    }
+}
+
+impl PartialEq for Condition {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Condition {}
+
+/// How important it is that a hook runs. When time gets short, less important
+/// hooks are skipped, see [`crate::Registry::set_best_effort_threshold`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Importance {
+    /// Nice to have, e.g. sending telemetry. Skipped when the remaining global
+    /// budget drops below the threshold.
+    BestEffort,
+    /// The default.
+    #[default]
+    Normal,
+    /// Must run, e.g. an `fsync` of important data.
+    Critical,
+}
+
+/// Information about a hook that is handed to a [`crate::HookObserver`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HookInfo {
+    /// See [`HookConfig::name`].
+    pub name: String,
+    /// See [`HookConfig::get_timeout`].
+    pub timeout: Option<Duration>,
+    /// See [`HookConfig::get_priority`].
+    pub priority: i32,
+    /// See [`HookConfig::get_tags`].
+    pub tags: Vec<String>,
+    /// See [`HookConfig::get_importance`].
+    pub importance: Importance,
+    /// See [`HookConfig::get_origin`].
+    pub origin: Option<String>,
+}
+
+impl From<&HookConfig> for HookInfo {
+    fn from(config: &HookConfig) -> Self {
+        Self {
+            name: config.name().to_string(),
+            timeout: config.get_timeout(),
+            priority: config.get_priority(),
+            tags: config.get_tags().to_vec(),
+            importance: config.get_importance(),
+            origin: config.get_origin().map(String::from),
+        }
+    }
+}
+
+/// Default for [`HookConfig::cancel_grace`].
+pub const DEFAULT_CANCEL_GRACE: Duration = Duration::from_secs(1);
+
+impl HookConfig {
+    /// Constructor.
+    ///
+    /// ## Parameters
+    /// * `name` human readable name of the hook, e.g. `"flush_db"`
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            timeout: None,
+            cancel_grace: DEFAULT_CANCEL_GRACE,
+            priority: 0,
+            niceness: None,
+            tags: Vec::new(),
+            importance: Importance::Normal,
+            escalation: None,
+            origin: None,
+            capture_env: Vec::new(),
+            env_snapshot: None,
+            external: false,
+            working_dir: None,
+            condition: None,
+        }
+    }
+
+    /// Sets the time the hook is allowed to take. Operators can override it with
+    /// an environment variable, see [`crate::timeout_env_var`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the time an async hook gets to finish after its
+    /// `CancellationToken` was cancelled. Afterwards the hook is dropped.
+    pub fn cancel_grace(mut self, grace: Duration) -> Self {
+        self.cancel_grace = grace;
+        self
+    }
+
+    /// Sets the priority of the hook. Only relevant with
+    /// [`crate::ExecutionOrder::Priority`]: hooks with a higher priority run first.
+    /// The default is `0`.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Runs the hook on a dedicated thread with the given niceness (`-20` highest
+    /// to `19` lowest priority). Requires the `thread-priority` feature and Linux,
+    /// which supports a niceness per thread. Ignored otherwise.
+    pub fn niceness(mut self, niceness: i32) -> Self {
+        self.niceness = Some(niceness);
+        self
+    }
+
+    /// Adds a tag like `"network"`, `"disk"` or `"telemetry"`. Tags allow to run
+    /// only some of the hooks, see [`crate::TagFilter`]. Can be called multiple
+    /// times.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Runs the hook only if `condition` holds when the shutdown starts, e.g. a
+    /// feature flag of a subsystem that is toggled at runtime. Otherwise the hook
+    /// is reported as [`crate::HookOutcome::Skipped`]. The conditions of all
+    /// hooks are evaluated once, before the first hook runs.
+    pub fn enabled_if<F>(mut self, condition: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.condition = Some(Condition(Arc::new(condition)));
+        self
+    }
+
+    /// Marks the hook as one that touches external systems, e.g. a database or
+    /// the network. In tests, such hooks can be replaced by recorders, see
+    /// [`crate::Registry::set_external_hooks`].
+    pub fn external(mut self) -> Self {
+        self.external = true;
+        self
+    }
+
+    /// Runs the hook with `dir` as working directory, e.g. a cleanup script that
+    /// uses relative paths. The previous working directory is entered again
+    /// afterwards. The hook fails if `dir` can't be entered.
+    ///
+    /// The working directory belongs to the whole process: threads that run in
+    /// parallel to the hook see the change.
+    pub fn working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets how important it is that the hook runs. The default is
+    /// [`Importance::Normal`].
+    pub fn importance(mut self, importance: Importance) -> Self {
+        self.importance = importance;
+        self
+    }
+
+    /// Sets what happens if the hook exceeds its timeout. Overrides the policy of
+    /// the registry, see [`crate::Registry::set_escalation_policy`].
+    pub fn escalation(mut self, policy: EscalationPolicy) -> Self {
+        self.escalation = Some(policy);
+        self
+    }
+
+    /// Sets the module that registered the hook, usually `module_path!()`. The
+    /// [`crate::hook_config`] macro does this automatically. Otherwise, the
+    /// registry records the source file that registered the hook, e.g.
+    /// `.../noisy-crate-0.1.0/src/exporter.rs`. Allows to see which crate
+    /// contributed a hook and to filter by it, see
+    /// [`crate::TagFilter::exclude_origin`].
+    pub fn origin(mut self, module_path: &'static str) -> Self {
+        self.origin = Some(module_path);
+        self
+    }
+
+    /// Captures the value of the environment variable `var` when the hook is
+    /// registered. If the hook doesn't finish successfully, the values and the
+    /// time of the registration are part of the report, see
+    /// [`crate::HookResult::env`]. Can be called multiple times.
+    pub fn capture_env(mut self, var: impl Into<String>) -> Self {
+        self.capture_env.push(var.into());
+        self
+    }
+
+    /// Takes the snapshot of [`Self::capture_env`]. Called on registration.
+    pub(crate) fn snapshot_env(&mut self) {
+        if !self.capture_env.is_empty() {
+            self.env_snapshot = Some(EnvSnapshot::capture(&self.capture_env));
+        }
+    }
+
+    /// The snapshot of [`Self::capture_env`], once the hook is registered.
+    pub(crate) fn env_snapshot(&self) -> Option<&EnvSnapshot> {
+        self.env_snapshot.as_ref()
+    }
+
+    /// Name of the hook.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The time the hook is allowed to take, if configured.
+    pub fn get_timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// The time an async hook gets to finish after its cancellation.
+    pub fn get_cancel_grace(&self) -> Duration {
+        self.cancel_grace
+    }
+
+    /// The niceness of the thread the hook runs on, if configured.
+    pub fn get_niceness(&self) -> Option<i32> {
+        self.niceness
+    }
+
+    /// The priority of the hook.
+    pub fn get_priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// How important it is that the hook runs.
+    pub fn get_importance(&self) -> Importance {
+        self.importance
+    }
+
+    /// The escalation policy of the hook, if configured.
+    pub fn get_escalation(&self) -> Option<EscalationPolicy> {
+        self.escalation
+    }
+
+    /// The tags of the hook.
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// The working directory of the hook, if configured.
+    pub fn get_working_dir(&self) -> Option<&Path> {
+        self.working_dir.as_deref()
+    }
+
+    /// Evaluates the condition of [`Self::enabled_if`]. `true` without a
+    /// condition.
+    pub fn is_enabled(&self) -> bool {
+        self.condition.as_ref().map_or(true, |c| (c.0)())
+    }
+
+    /// Whether the hook touches external systems, see [`Self::external`].
+    pub fn is_external(&self) -> bool {
+        self.external
+    }
+
+    /// The module or source file that registered the hook, see [`Self::origin`].
+    /// Always known once the hook is registered.
+    pub fn get_origin(&self) -> Option<&'static str> {
+        self.origin
+    }
+}
+
+/// Creates a [`HookConfig`] with the given name whose
+/// [`HookConfig::origin`] is the module of the call site.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown::{hook_config, Registry};
+///
+/// let registry = Registry::new();
+/// registry.register(hook_config!("flush"), || {});
+/// assert_eq!(registry.hooks()[0].origin.as_deref(), Some(module_path!()));
+/// ```
+#[macro_export]
+macro_rules! hook_config {
+    ($name:expr) => {
+        $crate::HookConfig::new($name).origin(::core::module_path!())
+    };
+}
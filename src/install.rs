@@ -0,0 +1,114 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Installs the bridges that start the shutdown sequence of the
+//! [`crate::global`] registry: panic hook, `atexit()` and signal handlers.
+
+use crate::{ExitDecision, ShutdownReason, PANIC_EXIT_CODE};
+use std::io;
+use std::panic;
+use std::sync::OnceLock;
+
+/// Installs everything that is available with the enabled features:
+/// - [`install_panic_hook`]
+/// - [`install_atexit_bridge`]
+/// - `install_signal_handlers()` (feature `signals`, UNIX)
+/// - `install_system_shutdown_handler()` (Windows)
+///
+/// With the `auto-init` feature, this runs automatically at program start.
+/// Calling this more than once has no effect.
+pub fn install() -> io::Result<()> {
+    install_panic_hook();
+    install_atexit_bridge()?;
+    #[cfg(all(unix, feature = "signals"))]
+    crate::install_signal_handlers()?;
+    #[cfg(windows)]
+    crate::install_system_shutdown_handler()?;
+    Ok(())
+}
+
+/// Installs a panic hook that runs the hooks of the [`crate::global`] registry
+/// with [`ShutdownReason::Panic`] if the main thread panics. The previous panic
+/// hook is invoked first, so the panic message is printed as usual.
+pub fn install_panic_hook() {
+    static INSTALL: OnceLock<()> = OnceLock::new();
+    INSTALL.get_or_init(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            // caught panics of hooks don't end the process
+            let caught = crate::registry::is_catching_panics();
+            if std::thread::current().name() == Some("main") && !caught {
+                let decision = ExitDecision {
+                    reason: ShutdownReason::Panic,
+                    code: Some(PANIC_EXIT_CODE),
+                };
+                // the process is going down anyway; otherwise, the hooks already run
+                if let Ok(decision) = crate::global::claim_exit(decision) {
+                    crate::global::run_exit_hooks(decision);
+                }
+            }
+        }));
+    });
+}
+
+/// Registers an `atexit()` handler that runs the hooks of the [`crate::global`]
+/// registry with [`ShutdownReason::Exit`]. It is invoked when `main()` returns
+/// and when [`std::process::exit`] is called.
+pub fn install_atexit_bridge() -> io::Result<()> {
+    static INSTALL: OnceLock<io::Result<()>> = OnceLock::new();
+    crate::global::init_once(&INSTALL, || at_exit(run_at_exit))
+}
+
+/// Registers `cb` with `atexit()`.
+pub(crate) fn at_exit(cb: extern "C" fn()) -> io::Result<()> {
+    extern "C" {
+        fn atexit(cb: extern "C" fn()) -> core::ffi::c_int;
+    }
+    // SAFETY: the callback is a plain function without arguments
+    if unsafe { atexit(cb) } != 0 {
+        return Err(io::Error::other("atexit() failed"));
+    }
+    Ok(())
+}
+
+/// Callback of [`install_atexit_bridge`].
+extern "C" fn run_at_exit() {
+    // the exit code was already decided by the caller of exit()
+    let decision = ExitDecision {
+        reason: ShutdownReason::Exit,
+        code: None,
+    };
+    // hooks that already run for crate::exit() or a signal aren't run again
+    if let Ok(decision) = crate::global::claim_exit(decision) {
+        crate::global::run_exit_hooks(decision);
+    }
+}
+
+/// Installs everything at program start.
+#[cfg(feature = "auto-init")]
+#[ctor::ctor]
+fn auto_init() {
+    // nobody could handle the error before main()
+    let _ = install();
+}
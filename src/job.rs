@@ -0,0 +1,217 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Windows counterpart of [`crate::ChildProcesses`]: tracked children are put
+//! into a job object that kills them when it's closed.
+
+use crate::sync::Mutex;
+use crate::{HookConfig, HookOutcome, Registry};
+use std::ffi::c_void;
+use std::io;
+use std::os::windows::io::AsRawHandle;
+use std::process::Child;
+use std::ptr;
+use std::sync::Arc;
+
+type Handle = *mut c_void;
+
+const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION: i32 = 9;
+const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+
+// the fields are read by Windows
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct BasicLimitInformation {
+    per_process_user_time_limit: i64,
+    per_job_user_time_limit: i64,
+    limit_flags: u32,
+    minimum_working_set_size: usize,
+    maximum_working_set_size: usize,
+    active_process_limit: u32,
+    affinity: usize,
+    priority_class: u32,
+    scheduling_class: u32,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct IoCounters {
+    read_operation_count: u64,
+    write_operation_count: u64,
+    other_operation_count: u64,
+    read_transfer_count: u64,
+    write_transfer_count: u64,
+    other_transfer_count: u64,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct ExtendedLimitInformation {
+    basic: BasicLimitInformation,
+    io: IoCounters,
+    process_memory_limit: usize,
+    job_memory_limit: usize,
+    peak_process_memory_used: usize,
+    peak_job_memory_used: usize,
+}
+
+extern "system" {
+    fn CreateJobObjectW(attributes: *mut c_void, name: *const u16) -> Handle;
+    fn SetInformationJobObject(job: Handle, class: i32, info: *mut c_void, len: u32) -> i32;
+    fn AssignProcessToJobObject(job: Handle, process: Handle) -> i32;
+    fn CloseHandle(handle: Handle) -> i32;
+}
+
+/// Owned handle of the job object. Closing it kills all processes in the job.
+#[derive(Debug)]
+struct JobHandle(Handle);
+
+// SAFETY: kernel handles can be used and closed from any thread
+unsafe impl Send for JobHandle {}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        // SAFETY: the handle is owned and closed only once
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Job object configured to kill all its processes when it's closed. Cheap to
+/// clone; all clones refer to the same job. Windows only.
+///
+/// The job is closed by the hook of [`Registry::register_job_object`], or by
+/// Windows if the process ends without running the hooks, e.g. when it crashes
+/// or is killed. Either way, no assigned child outlives the process.
+#[derive(Debug, Clone)]
+pub struct JobObject(Arc<Mutex<Option<JobHandle>>>);
+
+impl JobObject {
+    /// Creates an anonymous job object with kill-on-close.
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: no attributes and no name
+        let handle = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let handle = JobHandle(handle);
+        let mut info = ExtendedLimitInformation::default();
+        info.basic.limit_flags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        // SAFETY: `info` is a valid JOBOBJECT_EXTENDED_LIMIT_INFORMATION
+        let ok = unsafe {
+            SetInformationJobObject(
+                handle.0,
+                JOB_OBJECT_EXTENDED_LIMIT_INFORMATION,
+                &mut info as *mut ExtendedLimitInformation as *mut c_void,
+                core::mem::size_of::<ExtendedLimitInformation>() as u32,
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(Arc::new(Mutex::new(Some(handle)))))
+    }
+
+    /// Puts `child` into the job. Processes that `child` spawns later belong to
+    /// the job as well. Fails after [`Self::close`].
+    pub fn assign(&self, child: &Child) -> io::Result<()> {
+        let job = self.0.lock();
+        let job = job
+            .as_ref()
+            .ok_or_else(|| io::Error::other("the job object is closed"))?;
+        // SAFETY: both handles are valid while `child` and the lock are held
+        if unsafe { AssignProcessToJobObject(job.0, child.as_raw_handle()) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Closes the job, which kills all processes in it. Does nothing if it's
+    /// already closed.
+    pub fn close(&self) {
+        self.0.lock().take();
+    }
+
+    /// Whether [`Self::close`] was called.
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().is_none()
+    }
+}
+
+impl Registry {
+    /// Registers a hook that closes `job` and thereby kills its processes.
+    /// Closing the job should be the final step of the shutdown, after the hooks
+    /// that still talk to the children: register it first with
+    /// [`crate::ExecutionOrder::Lifo`], or give it the lowest priority with
+    /// [`crate::ExecutionOrder::Priority`]. Windows only.
+    #[track_caller]
+    pub fn register_job_object(&self, config: HookConfig, job: JobObject) {
+        self.register_outcome(config, move |_ctx| {
+            job.close();
+            HookOutcome::Completed
+        });
+    }
+}
+
+/// Creates a [`JobObject`] that is closed by a hook in the [`crate::global`]
+/// registry. The hook has the lowest priority, see
+/// [`Registry::register_job_object`]. Windows only.
+pub fn kill_children_on_shutdown() -> io::Result<JobObject> {
+    let job = JobObject::new()?;
+    crate::global().register_job_object(
+        HookConfig::new("close job object").priority(i32::MIN),
+        job.clone(),
+    );
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shell_command, ShutdownReason};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_children_are_killed_on_close() {
+        let job = JobObject::new().unwrap();
+        let mut child = shell_command("ping -n 30 127.0.0.1").spawn().unwrap();
+        job.assign(&child).unwrap();
+
+        let registry = Registry::new();
+        registry.register_job_object(HookConfig::new("job"), job.clone());
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        assert!(job.is_closed());
+
+        let begin = Instant::now();
+        while child.try_wait().unwrap().is_none() {
+            assert!(begin.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let other = shell_command("exit 0").spawn().unwrap();
+        assert!(job.assign(&other).is_err());
+    }
+}
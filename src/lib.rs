@@ -25,7 +25,7 @@ SOFTWARE.
 //! It takes code that should be executed when your program exits (gracefully).
 //!
 //! Internally it creates a `FnOnce`-closure that gets executed when the context gets dropped.
-//! This macro can be called multiple times (at least with stable Rust 1.48.0) without problems.
+//! This macro can be called multiple times without problems.
 //!
 //! In theory this macro can be used everywhere where the context gets dropped. But it has a nice
 //! expressive name so that one exactly knows what it should achieve in code. A good example
@@ -36,52 +36,404 @@ SOFTWARE.
 //! like when receiving `CTRL+C / SIGINT / SIGTERM`. This depends on whether your application
 //! properly handles signals and if the operating system gives the application time before it gets
 //! totally killed/stopped.
+//!
+//! With the `std` feature (enabled by default) hooks can also be collected in a [`Registry`],
+//! e.g. the process-wide one returned by [`global`]. Running a registry produces a
+//! [`ShutdownReport`]. Context-aware hooks receive a [`ShutdownContext`] that tells them why
+//! the shutdown happens and how much time is left. [`install`] makes sure the hooks of the
+//! global registry run on panics, on `exit()` and (feature `signals`) on `SIGINT`/`SIGTERM`.
+//!
+//! A second, restricted tier of hooks ([`SignalSafeHook`]) only performs async-signal-safe
+//! operations. It can run directly inside a signal handler, e.g. for `SIGSEGV` or `SIGABRT`.
+//! [`SignalSafeBuckets`] sorts such hooks into priorities that are fixed at compile time, also
+//! on `no_std` targets. On bare metal (e.g. with RTIC), an interrupt handler requests the
+//! shutdown with a [`ShutdownTrigger`] and the idle task runs the hooks. A `#[panic_handler]`
+//! runs the tier with [`panic_shutdown`]. [`WatchdogFeeder`] stops feeding the hardware watchdog
+//! after the last hook, so the MCU resets once the cleanup is done.
+//!
+//! To decouple the callbacks from the scope they were declared in, attach them to a
+//! [`ShutdownAnchor`] at the top of `main()`.
+//!
+//! The guard, the anchor and the signal-safe tier live in the `simple-on-shutdown-core` crate and
+//! are re-exported here. It has no features that pull in `std`, for the tightest `no_std` builds.
+//!
+//! The minimum supported Rust version (MSRV) is 1.74.
+//!
+//! ## Helpers
+//! - [`on_shutdown_cmd`]: runs an external command at shutdown
+//! - [`exit`]: runs the hooks and ends the process; races with signals run the hooks only once
+//! - [`Registry::register_session_log`]: summary of a run of a CLI tool (reason, duration,
+//!   counters) in a log file
+//! - [`Registry::register_process_state_restore`]: restores working directory and umask at
+//!   shutdown; [`HookConfig::working_dir`] runs a hook in a chosen directory
+//! - [`Registry::register_fsync`]: makes files durable before the exit, incl. their directories
+//! - [`DrainGuard`]: stops accepting TCP connections and waits for open ones to close
+//! - [`Shutdownable`]: components (incl. common std types) that shut themselves down
+//! - [`ShutdownBlackboard`]: typed values that earlier hooks pass to later ones, e.g. a summary
+//!   for a final report
+//! - [`StepHook`]: long cleanups in small steps with deadline checks in between
+//! - [`Event`]: recurring events like a reload whose hooks run on every emission
+//! - [`Registry::register_config`]: a [`ConfigCell`] that is swapped on every reload and flushed
+//!   at shutdown
+//! - [`StaticShutdownHook`]: hooks in statics that are registered lazily on first use
+//! - [`StaticGuardSlot`]: a guard in a `static` that runs at process end and can be replaced
+//! - [`assert_shutdown_safe`]: checks at compile time that a closure fits a registration mode
+//! - [`on_shutdown_sized`]: fails the build if a callback captures more than a size budget
+//! - `exec_upgrade()`: runs the `"pre-exec"` hooks and replaces the process with a new binary
+//!   (UNIX)
+//! - [`PreallocatedRegistry`]: trigger path without heap allocations for realtime apps
+//! - [`PluginRegistrar`]: stable ABI for dynamically loaded plugins that register hooks
+//! - [`hook_config`]: records the module that registered a hook, to see and filter which crate
+//!   contributed it
+//! - [`Registry::subscribe`]: live updates while the hooks run, e.g. for a supervisor or a TUI
+//! - [`is_system_shutting_down`]: tells a shutdown of the machine from a stop of the application
+//!   (systemd, Windows)
+//! - [`Registry::register_with`]: prepares a hook at registration, e.g. lookups, so the cleanup
+//!   at shutdown is faster
+//! - [`Registry::rehearse`]: dry run of the hooks to check the shutdown readiness of a live
+//!   service
+//! - [`Quarantine`]: hooks that failed in the previous runs are demoted or skipped, so they
+//!   don't eat the grace period on every restart
+//! - [`set_diagnostics_sink`]: redirects the messages of the crate itself, e.g. for apps without
+//!   a console
+//!
+//! ## Cargo Features
+//! - `std` (default): [`Registry`] and everything that needs an operating system
+//! - `minimal`: fails the build if any feature that adds dependencies is enabled as well; only
+//!   the guard, the anchor and the macros remain, for audited builds. The signal-safe tier and
+//!   the diagnostics sink are left out
+//! - `signals`: graceful shutdown on `SIGINT`/`SIGTERM`, see `install_signal_handlers()`, and
+//!   events on `SIGHUP`/`SIGUSR1`/`SIGUSR2`, see `install_reload_handler()` and
+//!   `install_diagnostic_handlers()` (UNIX)
+//! - `auto-init`: calls [`install`] at program start, so depending on the crate and registering
+//!   hooks is enough
+//! - `async`: async hooks; they get a `CancellationToken` to finish early when their time is up.
+//!   `shutdown_complete().await` waits until the shutdown sequence finished
+//! - `unsafe-crash-handlers`: runs the signal-safe tier on fatal signals, see
+//!   `install_crash_handlers()` (UNIX)
+//! - `child-processes`: terminates tracked child processes at shutdown, see
+//!   `ChildProcesses` (UNIX) and `JobObject` (Windows), and shuts forked workers down before
+//!   the own hooks, see `Registry::coordinate_workers()` (UNIX)
+//! - `sqlx`, `deadpool`, `r2d2`: hooks that close database connection pools, e.g.
+//!   `Registry::register_sqlx_pool()`
+//! - `tokio`: runs the hooks before a Tokio runtime is torn down, see
+//!   `Registry::shutdown_runtime()`, and `ShutdownScope` for tasks that are stopped and awaited
+//!   at shutdown
+//! - `testing`: `testing::ShutdownProbe` for integration tests that send signals to a binary
+//!   and check its output (UNIX) and `Chaos` to inject faults into hooks
+//! - `thread-priority`: hooks with [`HookConfig::niceness`] run on a dedicated thread with that
+//!   niceness (Linux)
+//! - `serde`: [`HookInfo`] and [`ShutdownReport`] are serializable, e.g. to expose
+//!   [`Registry::hooks`] and reports on a debug endpoint
+//! - `distributed`: library crates contribute hooks at link time with
+//!   `distributed_shutdown_hook!`
+//! - `embassy`: async hooks for embedded firmware that are awaited by an embassy task, see
+//!   `EmbassyShutdown` (`no_std`)
+//! - `mobile`: `lifecycle` forwards lifecycle callbacks of Android and iOS apps to the global
+//!   registry
+//! - `wasi`: `wasi::proc_exit()` runs the hooks before a WASI program ends (WASI); escalation
+//!   and the runner thread are ignored on targets without threads
+//! - `wasm-bindgen`: `browser::keep_until_unload()` keeps guards of web apps alive until the
+//!   page is hidden for good and manages the event listener (`wasm32`)
+//! - `parking_lot`: the registry uses the non-poisoning locks of `parking_lot` instead of the
+//!   ones of std
+//! - `scopeguard-compat`: guards of the `scopeguard` crate convert into [`OnShutdownCallback`]s
+//!   and can be registered with `Registry::register_scopeguard()`
+//! - `cli`: `show_progress()` renders a status line with the hook that currently runs, for CLI
+//!   tools
 
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+// every feature that pulls in a dependency or `std` must be listed here
+#[cfg(all(
+    feature = "minimal",
+    any(
+        feature = "std",
+        feature = "signals",
+        feature = "auto-init",
+        feature = "testing",
+        feature = "async",
+        feature = "unsafe-crash-handlers",
+        feature = "child-processes",
+        feature = "sqlx",
+        feature = "deadpool",
+        feature = "r2d2",
+        feature = "tokio",
+        feature = "thread-priority",
+        feature = "serde",
+        feature = "distributed",
+        feature = "embassy",
+        feature = "mobile",
+        feature = "wasi",
+        feature = "wasm-bindgen",
+        feature = "parking_lot",
+        feature = "cli",
+        feature = "scopeguard-compat",
+    )
+))]
+compile_error!(
+    "feature `minimal` of simple_on_shutdown doesn't allow other features, \
+     disable the default features and check which crate enables them"
+);
 
 #[cfg(not(test))]
 extern crate alloc;
-#[cfg(not(test))]
-use alloc::boxed::Box;
 
-/// PRIVATE! Use [`on_shutdown`].
-///
-/// Simple type that holds a `FnOnce`-closure (callback). The `FnOnce`-closure gets invoked during `drop()`.
-/// This works also fine with applications that do gracefully shutdown via signals, like SIGTERM.
-pub struct OnShutdownCallback(Option<Box<dyn FnOnce()>>);
-
-impl OnShutdownCallback {
-    /// Constructor. Used by [`on_shutdown`].
-    ///
-    /// ## Parameters
-    /// * `cb` boxed(heap) callback function
-    ///
-    // THIS MUST BE PUBLIC, OTHERWISE THE MACROS DO NOT WORK!
-    pub fn new(cb: Box<dyn FnOnce()>) -> Self {
-        Self(Some(cb))
-    }
-}
+mod assert;
+#[cfg(feature = "std")]
+mod blackboard;
+#[cfg(all(target_arch = "wasm32", feature = "wasm-bindgen"))]
+pub mod browser;
+#[cfg(feature = "async")]
+mod cancel;
+#[cfg(feature = "testing")]
+mod chaos;
+#[cfg(feature = "std")]
+mod child;
+#[cfg(all(unix, feature = "child-processes"))]
+mod children;
+#[cfg(feature = "cli")]
+mod cli;
+#[cfg(feature = "std")]
+mod command;
+#[cfg(feature = "async")]
+mod completion;
+#[cfg(feature = "std")]
+mod config_cell;
+#[cfg(feature = "std")]
+mod context;
+#[cfg(all(unix, feature = "unsafe-crash-handlers"))]
+mod crash;
+#[cfg(any(feature = "sqlx", feature = "deadpool", feature = "r2d2"))]
+mod db;
+#[cfg(all(target_has_atomic = "ptr", not(feature = "minimal")))]
+mod diagnostics;
+#[cfg(feature = "distributed")]
+mod distributed;
+#[cfg(feature = "std")]
+mod drain;
+#[cfg(feature = "embassy")]
+mod embassy;
+#[cfg(feature = "std")]
+mod env;
+#[cfg(feature = "std")]
+mod escalation;
+#[cfg(feature = "std")]
+mod events;
+#[cfg(feature = "async")]
+mod executor;
+#[cfg(feature = "std")]
+mod exit_code;
+#[cfg(feature = "std")]
+mod fsync;
+#[cfg(feature = "std")]
+mod global;
+#[cfg(feature = "std")]
+mod guard_slot;
+#[cfg(feature = "std")]
+mod hook;
+#[cfg(feature = "std")]
+mod install;
+#[cfg(all(windows, feature = "child-processes"))]
+mod job;
+#[cfg(feature = "mobile")]
+pub mod lifecycle;
+#[cfg(feature = "std")]
+mod observer;
+#[cfg(feature = "std")]
+mod plugin;
+#[cfg(feature = "std")]
+mod prealloc;
+#[cfg(feature = "std")]
+mod prepared;
+#[cfg(feature = "std")]
+mod priority;
+#[cfg(feature = "std")]
+mod process_state;
+#[cfg(feature = "std")]
+mod quarantine;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "std")]
+mod report;
+#[cfg(feature = "tokio")]
+mod runtime;
+#[cfg(feature = "tokio")]
+mod scope;
+#[cfg(feature = "scopeguard-compat")]
+mod scopeguard_compat;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+mod shuffle;
+#[cfg(feature = "std")]
+mod shutdownable;
+#[cfg(all(unix, feature = "signals"))]
+mod signals;
+#[cfg(feature = "std")]
+mod static_hook;
+#[cfg(feature = "std")]
+mod step;
+#[cfg(feature = "std")]
+mod sync;
+#[cfg(feature = "std")]
+mod system;
+#[cfg(feature = "std")]
+mod tags;
+#[cfg(all(unix, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "std")]
+mod threads;
+#[cfg(all(unix, feature = "std"))]
+mod upgrade;
+#[cfg(all(target_os = "wasi", feature = "wasi"))]
+pub mod wasi;
+#[cfg(all(unix, feature = "child-processes"))]
+mod workers;
 
-impl Drop for OnShutdownCallback {
-    /// Executes the specified callback.
-    fn drop(&mut self) {
-        // take(): because I use a FnOnce here, I need to own the value
-        // in order for it to get executed.
-        (self.0.take().unwrap())();
-    }
+pub use assert::GuardCallback;
+#[cfg(feature = "std")]
+pub use assert::{ContextCallback, EventCallback, RegistryCallback};
+#[cfg(feature = "std")]
+pub use blackboard::{BlackboardKey, ShutdownBlackboard};
+#[cfg(feature = "async")]
+pub use cancel::{CancellationToken, Cancelled};
+#[cfg(feature = "testing")]
+pub use chaos::{Chaos, Fault};
+#[cfg(feature = "std")]
+pub use child::ChildRegistry;
+#[cfg(all(unix, feature = "child-processes"))]
+pub use children::{terminate_children_on_shutdown, ChildProcesses, DEFAULT_CHILD_GRACE};
+#[cfg(feature = "cli")]
+pub use cli::show_progress;
+#[cfg(feature = "std")]
+pub use command::{command_on_shutdown, shell_command, CommandError};
+#[cfg(feature = "async")]
+pub use completion::{shutdown_complete, Completion};
+#[cfg(feature = "std")]
+pub use config_cell::ConfigCell;
+#[cfg(feature = "std")]
+pub use context::{ShutdownContext, ShutdownReason};
+#[cfg(all(unix, feature = "unsafe-crash-handlers"))]
+pub use crash::{install_crash_handlers, CRASH_SIGNALS};
+#[cfg(feature = "std")]
+pub use diagnostics::stderr_sink;
+#[cfg(all(target_has_atomic = "ptr", not(feature = "minimal")))]
+pub use diagnostics::{set_diagnostics_sink, DiagnosticsSink, Level};
+#[cfg(feature = "distributed")]
+pub use distributed::{DistributedHook, DISTRIBUTED_HOOKS};
+#[cfg(feature = "std")]
+pub use drain::{DrainGuard, TrackedStream};
+#[cfg(feature = "embassy")]
+pub use embassy::EmbassyShutdown;
+#[cfg(feature = "std")]
+pub use env::{timeout_env_var, EnvSnapshot, TIMEOUT_ENV_PREFIX};
+#[cfg(feature = "std")]
+pub use escalation::{EscalationPolicy, EscalationStage};
+#[cfg(feature = "std")]
+pub use events::Event;
+#[cfg(feature = "std")]
+pub use exit_code::{exit_code_for, set_exit_code_fn, PANIC_EXIT_CODE};
+#[cfg(feature = "std")]
+pub use fsync::FsyncPaths;
+#[cfg(feature = "std")]
+pub use global::{exit, exit_decision, force_exit, global, run_global_hooks, ExitDecision};
+#[cfg(feature = "std")]
+pub use guard_slot::{SlotCallback, StaticGuardSlot};
+#[cfg(feature = "std")]
+pub use hook::{HookConfig, HookInfo, Importance, DEFAULT_CANCEL_GRACE};
+#[cfg(feature = "std")]
+pub use install::{install, install_atexit_bridge, install_panic_hook};
+#[cfg(all(windows, feature = "child-processes"))]
+pub use job::{kill_children_on_shutdown, JobObject};
+#[cfg(feature = "std")]
+pub use observer::{HookObserver, HookUpdate};
+#[cfg(feature = "std")]
+pub use plugin::{AbiMismatch, PluginHook, PluginRegistrar, PLUGIN_ABI_VERSION};
+#[cfg(feature = "std")]
+pub use prealloc::{PreallocatedRegistry, PreallocatedResult, RegistryFull};
+#[cfg(feature = "std")]
+pub use process_state::ProcessState;
+#[cfg(feature = "std")]
+pub use quarantine::{Quarantine, QuarantineAction};
+#[cfg(feature = "std")]
+pub use registry::{
+    DuplicateRegistrationPolicy, ExecutionOrder, ExternalHooks, LateRegistrationPolicy,
+    RegistrationBarrier, Registry, CAN_ISOLATE_PANICS,
+};
+#[cfg(feature = "std")]
+pub use report::{HookError, HookOutcome, HookResult, ShutdownError, ShutdownReport};
+#[cfg(feature = "tokio")]
+pub use scope::ShutdownScope;
+#[cfg(feature = "std")]
+pub use session::SessionLog;
+#[cfg(feature = "std")]
+pub use shuffle::SHUFFLE_SEED_ENV;
+#[cfg(feature = "std")]
+pub use shutdownable::{register_shutdownable, Shutdownable};
+#[cfg(all(unix, feature = "signals"))]
+pub use signals::{
+    install_diagnostic_handlers, install_reload_handler, install_signal_handlers, route_signal,
+    set_repeated_signal_action, RepeatedSignalAction, SHUTDOWN_SIGNALS,
+};
+#[cfg(all(target_has_atomic = "ptr", not(feature = "minimal")))]
+pub use simple_on_shutdown_core::{
+    panic_shutdown, register_signal_safe, run_signal_safe_hooks, CapacityExceeded, ShutdownTrigger,
+    SignalSafeBuckets, SignalSafeHook, SyncShutdownCallback, WatchdogFeeder, SIGNAL_SAFE_CAPACITY,
+};
+pub use simple_on_shutdown_core::{
+    AnchorHandle, OnShutdownCallback, SharedShutdownCallback, ShutdownAnchor,
+};
+#[cfg(feature = "std")]
+pub use static_hook::StaticShutdownHook;
+#[cfg(feature = "std")]
+pub use step::StepHook;
+#[cfg(all(windows, feature = "std"))]
+pub use system::install_system_shutdown_handler;
+#[cfg(feature = "std")]
+pub use system::is_system_shutting_down;
+#[cfg(feature = "std")]
+pub use tags::{TagFilter, EXCLUDE_ORIGINS_ENV, EXCLUDE_TAGS_ENV, INCLUDE_TAGS_ENV};
+#[cfg(feature = "std")]
+pub use threads::DEFAULT_JOIN_TIMEOUT;
+#[cfg(all(unix, feature = "std"))]
+pub use upgrade::{exec_upgrade, UpgradeError, PRE_EXEC_TAG};
+#[cfg(all(unix, feature = "child-processes"))]
+pub use workers::{WorkerChannel, WorkerCoordinator, WorkerExit};
+
+/// PRIVATE! Re-exports used by the macros of this crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::assert::{assert_capture_size, assert_guard};
+    #[cfg(feature = "std")]
+    pub use crate::assert::{assert_context, assert_event, assert_registry};
+    #[cfg(feature = "async")]
+    pub use crate::executor::block_on_guard;
+    #[cfg(not(test))]
+    pub use alloc::boxed::Box;
+    #[cfg(feature = "distributed")]
+    pub use linkme;
+    #[cfg(test)]
+    pub use std::boxed::Box;
 }
 
 /// This crate consists of a convenient macro to specify on shutdown callbacks called [`on_shutdown`].
 /// It takes code that should be executed when your program exits (gracefully).
 ///
 /// Internally it creates a `FnOnce`-closure that gets executed when the context gets dropped.
-/// This macro can be called multiple times (at least with stable Rust 1.48.0) without problems.
+/// This macro can be called multiple times without problems.
 ///
 /// In theory this macro can be used everywhere where the context gets dropped. But it has a nice
 /// expressive name so that one exactly knows what it should achieve in code. A good example
 /// is the `main()` function in an `actix-web`-Server or a `tokio` runtime. For example you want
 /// to log to a file when the server was shut down.
 ///
+/// ## Order
+/// Callbacks of multiple invocations in one scope run in reverse order of the
+/// invocations, like destructors: the last registered callback runs first. This is
+/// part of the API contract, as is the order of [`ShutdownAnchor`] and of
+/// [`Registry::execution_order`].
+///
 /// There is no guarantee that this gets executed during "non-regular" shutdown scenarios,
 /// like when receiving `CTRL+C / SIGINT / SIGTERM`. This depends on whether your application
 /// properly handles signals and if the operating system gives the application time before it gets
@@ -114,25 +466,131 @@ macro_rules! on_shutdown {
         // multiple times. Because two values may have the same identifier in rustlang
         // but internally they are two different values (you can see this in debugger).
         let _on_shutdown_callback_1337deadbeeffoobaraffecoffee =
-            $crate::OnShutdownCallback::new(Box::new($closure));
+            $crate::OnShutdownCallback::new($crate::__private::Box::new($closure));
+    };
+    // async blocks would be dropped without being polled
+    (async $($fut:tt)*) => {
+        $crate::__on_shutdown_async!(async $($fut)*);
     };
     // move closure expression
     (move || $cb:expr) => {
         let closure = move || $cb;
-        on_shutdown!(closure);
+        $crate::on_shutdown!(closure);
     };
     // closure expression
     (|| $cb:expr) => {
         let closure = || $cb;
-        on_shutdown!(closure);
+        $crate::on_shutdown!(closure);
     };
     ($cb:expr) => {
         let closure = || $cb;
-        on_shutdown!(closure);
+        $crate::on_shutdown!(closure);
     };
     ($cb:block) => {
         let closure = || $cb;
-        on_shutdown!(closure);
+        $crate::on_shutdown!(closure);
+    };
+}
+
+/// Like [`on_shutdown`] but usable in expression position. It returns the guard
+/// instead of binding it to a hidden variable in the current block. This way,
+/// the callback runs when the object that owns the guard gets dropped.
+///
+/// Expressions and blocks become `move` closures, because the guard usually
+/// outlives the current block.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown::{on_shutdown_expr, OnShutdownCallback};
+///
+/// struct Server {
+///     port: u16,
+///     _shutdown: OnShutdownCallback,
+/// }
+///
+/// fn start(port: u16) -> Server {
+///     Server {
+///         port,
+///         _shutdown: on_shutdown_expr!(println!("server on port {} shut down", port)),
+///     }
+/// }
+///
+/// let server = start(8080);
+/// drop(server); // prints the message
+/// ```
+#[macro_export]
+macro_rules! on_shutdown_expr {
+    // a identifier that must point to a valid closure
+    ($closure:ident) => {
+        $crate::OnShutdownCallback::new($crate::__private::Box::new($closure))
+    };
+    // move closure expression
+    (move || $cb:expr) => {
+        $crate::OnShutdownCallback::new($crate::__private::Box::new(move || $cb))
+    };
+    // closure expression
+    (|| $cb:expr) => {
+        $crate::OnShutdownCallback::new($crate::__private::Box::new(|| $cb))
+    };
+    ($cb:expr) => {
+        $crate::OnShutdownCallback::new($crate::__private::Box::new(move || $cb))
+    };
+    ($cb:block) => {
+        $crate::OnShutdownCallback::new($crate::__private::Box::new(move || $cb))
+    };
+}
+
+/// Like [`on_shutdown`] but takes a future, e.g. an `async` block. It is driven to
+/// completion on the thread that drops the guard, no async runtime is required.
+/// [`on_shutdown`] forwards `async` blocks to this macro.
+///
+/// Requires the `async` feature.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown::on_shutdown_async;
+///
+/// async fn flush() {
+///     println!("flushed");
+/// }
+///
+/// fn main() {
+///     on_shutdown_async!(async {
+///         flush().await;
+///     });
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[macro_export]
+macro_rules! on_shutdown_async {
+    ($fut:expr) => {
+        let _on_shutdown_callback_1337deadbeeffoobaraffecoffee =
+            $crate::OnShutdownCallback::new($crate::__private::Box::new(move || {
+                $crate::__private::block_on_guard($fut)
+            }));
+    };
+}
+
+/// PRIVATE! Handles `async` blocks passed to [`on_shutdown`].
+#[cfg(feature = "async")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __on_shutdown_async {
+    ($fut:expr) => {
+        $crate::on_shutdown_async!($fut);
+    };
+}
+
+/// PRIVATE! Handles `async` blocks passed to [`on_shutdown`].
+#[cfg(not(feature = "async"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __on_shutdown_async {
+    ($($fut:tt)*) => {
+        ::core::compile_error!(
+            "on_shutdown! can't run an async block, it would be dropped without being polled. \
+             Enable the `async` feature of simple_on_shutdown and use on_shutdown_async! instead."
+        );
     };
 }
 
@@ -191,4 +649,33 @@ mod tests {
             println!("foobar={}", foobar_c.load(Ordering::Relaxed));
         });
     }
+
+    #[test]
+    fn test_expr_in_struct() {
+        struct Server {
+            _shutdown: super::OnShutdownCallback,
+        }
+
+        let shut_down = Arc::new(AtomicBool::new(false));
+        let shut_down_c = shut_down.clone();
+        let server = Server {
+            _shutdown: on_shutdown_expr!(shut_down_c.store(true, Ordering::Relaxed)),
+        };
+        assert!(!shut_down.load(Ordering::Relaxed));
+        drop(server);
+        assert!(shut_down.load(Ordering::Relaxed));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_block() {
+        let flushed = Arc::new(AtomicBool::new(false));
+        {
+            let flushed = flushed.clone();
+            on_shutdown!(async move {
+                flushed.store(true, Ordering::Relaxed);
+            });
+        }
+        assert!(flushed.load(Ordering::Relaxed));
+    }
 }
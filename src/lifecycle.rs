@@ -0,0 +1,106 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Bridge for the lifecycle callbacks of mobile platforms.
+//!
+//! A Rust core that is shared between Android, iOS and other platforms keeps one
+//! cleanup path: the platform glue (JNI, Swift) forwards the callbacks to these
+//! functions, which act on the [`crate::global`] registry.
+//!
+//! | Android                                | iOS                                          | Function                 |
+//! |----------------------------------------|----------------------------------------------|--------------------------|
+//! | `onDestroy()` with `isFinishing()`     | `applicationWillTerminate(_:)`               | [`will_terminate`]       |
+//! | `onStop()`                             | `applicationDidEnterBackground(_:)`          | [`entered_background`]   |
+//! | `onTrimMemory(level)`, `onLowMemory()` | `applicationDidReceiveMemoryWarning(_:)`     | [`on_trim_memory`], [`memory_warning`] |
+//!
+//! Both platforms may kill a backgrounded app without calling any of these, so
+//! persist important state on [`Event::Background`].
+//!
+//! ## Example
+//! ```ignore
+//! // JNI glue of `class App : Application`
+//! #[no_mangle]
+//! pub extern "system" fn Java_com_example_App_onTrimMemory(_env: JNIEnv, _this: JObject, level: jint) {
+//!     simple_on_shutdown::lifecycle::on_trim_memory(level);
+//! }
+//! ```
+
+use crate::{Event, ShutdownReason, ShutdownReport};
+
+/// Lowest level of Android's `onTrimMemory()` at which the app's UI is hidden
+/// (`TRIM_MEMORY_UI_HIDDEN`).
+const TRIM_MEMORY_UI_HIDDEN: i32 = 20;
+
+/// The app is about to terminate: runs the hooks of the global registry with
+/// [`ShutdownReason::Requested`]. Runs the hooks at most once, like every
+/// trigger of a registry.
+pub fn will_terminate() -> ShutdownReport {
+    crate::global().run(ShutdownReason::Requested, None)
+}
+
+/// The app moved to the background: emits [`Event::Background`] on the global
+/// registry.
+pub fn entered_background() -> ShutdownReport {
+    crate::global().emit(Event::Background)
+}
+
+/// The system is low on memory: emits [`Event::LowMemory`] on the global
+/// registry.
+pub fn memory_warning() -> ShutdownReport {
+    crate::global().emit(Event::LowMemory)
+}
+
+/// Maps Android's `onTrimMemory(level)`. `TRIM_MEMORY_UI_HIDDEN` means the app
+/// moved to the background; all other levels are memory warnings.
+pub fn on_trim_memory(level: i32) -> ShutdownReport {
+    if level == TRIM_MEMORY_UI_HIDDEN {
+        entered_background()
+    } else {
+        memory_warning()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HookConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_trim_memory_emits_events() {
+        static BACKGROUND: AtomicUsize = AtomicUsize::new(0);
+        static LOW_MEMORY: AtomicUsize = AtomicUsize::new(0);
+        crate::global().register_event(Event::Background, HookConfig::new("persist"), || {
+            BACKGROUND.fetch_add(1, Ordering::SeqCst);
+        });
+        crate::global().register_event(Event::LowMemory, HookConfig::new("drop caches"), || {
+            LOW_MEMORY.fetch_add(1, Ordering::SeqCst);
+        });
+
+        on_trim_memory(TRIM_MEMORY_UI_HIDDEN);
+        on_trim_memory(15);
+        memory_warning();
+        assert_eq!(BACKGROUND.load(Ordering::SeqCst), 1);
+        assert_eq!(LOW_MEMORY.load(Ordering::SeqCst), 2);
+    }
+}
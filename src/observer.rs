@@ -0,0 +1,181 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Observers that wrap every hook execution.
+
+use crate::sync::Mutex;
+use crate::{EscalationStage, HookInfo, HookOutcome, Registry, ShutdownReport};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+/// Wrapped around every hook execution of a [`crate::Registry`], see
+/// [`crate::Registry::add_observer`]. Allows custom telemetry, audit logging or
+/// chaos-testing delays without forking the crate.
+///
+/// All methods do nothing by default.
+pub trait HookObserver: Send + Sync {
+    /// Invoked right before the hook runs.
+    fn before(&self, _info: &HookInfo) {}
+
+    /// Invoked when the hook reports progress, see
+    /// [`crate::ShutdownContext::report_progress`].
+    fn progress(&self, _info: &HookInfo, _progress: u64) {}
+
+    /// Invoked when the hook exceeds its timeout and reaches the next stage of
+    /// its [`crate::EscalationPolicy`].
+    fn escalated(&self, _info: &HookInfo, _stage: EscalationStage) {}
+
+    /// Invoked right after the hook finished.
+    fn after(&self, _info: &HookInfo, _outcome: &HookOutcome) {}
+
+    /// Invoked once the shutdown sequence finished, see [`Registry::run`]. If
+    /// it ran because the process ends, e.g. through [`crate::exit`] or a
+    /// signal, [`ShutdownReport::exit`] tells how. This is the only way to see
+    /// that report, as the process ends right afterwards.
+    fn finished(&self, _report: &ShutdownReport) {}
+}
+
+/// Live update about a hook execution, see [`Registry::subscribe`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HookUpdate {
+    /// The hook started.
+    Started(HookInfo),
+    /// The hook reported progress, see [`crate::ShutdownContext::report_progress`].
+    Progress(HookInfo, u64),
+    /// The hook finished. Errors of [`HookOutcome::Failed`] only keep their
+    /// message and causes.
+    Finished(HookInfo, HookOutcome),
+}
+
+/// Forwards all notifications into a channel.
+struct ChannelObserver(Mutex<Sender<HookUpdate>>);
+
+impl ChannelObserver {
+    fn send(&self, update: HookUpdate) {
+        // the receiver may be gone, nobody is interested anymore then
+        let _ = self.0.lock().send(update);
+    }
+}
+
+impl HookObserver for ChannelObserver {
+    fn before(&self, info: &HookInfo) {
+        self.send(HookUpdate::Started(info.clone()));
+    }
+
+    fn progress(&self, info: &HookInfo, progress: u64) {
+        self.send(HookUpdate::Progress(info.clone(), progress));
+    }
+
+    fn after(&self, info: &HookInfo, outcome: &HookOutcome) {
+        self.send(HookUpdate::Finished(info.clone(), outcome.to_detached()));
+    }
+}
+
+impl Registry {
+    /// Returns a channel that receives a [`HookUpdate`] whenever a hook of the
+    /// registry starts, reports progress or finishes, while the shutdown
+    /// sequence runs. A supervising thread or a TUI can display the progress
+    /// live instead of waiting for the [`crate::ShutdownReport`].
+    ///
+    /// The channel is unbounded, so hooks never wait for the receiver.
+    ///
+    /// ```rust
+    /// use simple_on_shutdown::{HookConfig, HookUpdate, Registry, ShutdownReason};
+    ///
+    /// let registry = Registry::new();
+    /// registry.register(HookConfig::new("flush"), || {});
+    /// let updates = registry.subscribe();
+    /// std::thread::spawn(move || {
+    ///     for update in updates {
+    ///         if let HookUpdate::Finished(info, outcome) = update {
+    ///             println!("{}: {}", info.name, outcome);
+    ///         }
+    ///     }
+    /// });
+    /// registry.run(ShutdownReason::Exit, None);
+    /// ```
+    pub fn subscribe(&self) -> Receiver<HookUpdate> {
+        let (sender, receiver) = mpsc::channel();
+        self.add_observer(Arc::new(ChannelObserver(Mutex::new(sender))));
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExitDecision, HookConfig, ShutdownReason};
+
+    #[test]
+    fn test_subscribe() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("a"), || {});
+        registry.register_fallible(HookConfig::new("b"), |ctx| {
+            ctx.report_progress(50);
+            Err("nope")
+        });
+        let updates = registry.subscribe();
+        registry.run(ShutdownReason::Exit, None);
+
+        let updates = updates
+            .try_iter()
+            .map(|update| match update {
+                HookUpdate::Started(info) => format!("started {}", info.name),
+                HookUpdate::Progress(info, progress) => format!("{} at {}", info.name, progress),
+                HookUpdate::Finished(info, outcome) => format!("{}: {}", info.name, outcome),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            updates,
+            [
+                "started b",
+                "b at 50",
+                "b: failed: nope",
+                "started a",
+                "a: completed"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_finished_report_has_exit_decision() {
+        struct LastReport(Mutex<Option<(usize, Option<ExitDecision>)>>);
+        impl HookObserver for LastReport {
+            fn finished(&self, report: &ShutdownReport) {
+                *self.0.lock() = Some((report.results.len(), report.exit));
+            }
+        }
+
+        let registry = Registry::new();
+        registry.register(HookConfig::new("a"), || {});
+        let observer = Arc::new(LastReport(Mutex::new(None)));
+        registry.add_observer(observer.clone());
+        let decision = ExitDecision {
+            reason: ShutdownReason::Exit,
+            code: Some(3),
+        };
+        registry.run_exit(decision);
+        assert_eq!(observer.0.lock().take(), Some((1, Some(decision))));
+    }
+}
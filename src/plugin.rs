@@ -0,0 +1,220 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Stable ABI that lets dynamically loaded plugins register hooks in the
+//! registry of the host.
+//!
+//! Host and plugin may be built with different compiler versions, so no Rust
+//! types cross the boundary. The host hands a [`PluginRegistrar`] (a versioned,
+//! `#[repr(C)]` vtable) to the entry point of the plugin. The plugin turns its
+//! closures into [`PluginHook`]s, whose code and destructor are compiled into
+//! the plugin itself.
+//!
+//! The host must keep the plugin library loaded until the hooks ran, i.e. until
+//! the shutdown sequence finished. Unloading it earlier leaves the registry with
+//! dangling function pointers.
+
+use crate::{HookConfig, HookOutcome, Registry};
+use core::ffi::c_void;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Version of the plugin ABI. It is increased with every incompatible change of
+/// [`PluginRegistrar`] or [`PluginHook`].
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+
+/// A type-erased hook whose code lives in the plugin.
+#[repr(C)]
+pub struct PluginHook {
+    data: *mut c_void,
+    /// Returns `false` if the hook panicked.
+    call: extern "C" fn(*mut c_void) -> bool,
+    drop: extern "C" fn(*mut c_void),
+}
+
+// SAFETY: constructed from `FnOnce() + Send` only
+unsafe impl Send for PluginHook {}
+
+impl PluginHook {
+    /// Wraps `f`. Panics of `f` are caught, they must not cross the ABI boundary.
+    /// The host reports them as [`HookOutcome::Failed`].
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        extern "C" fn call<F: FnOnce()>(data: *mut c_void) -> bool {
+            // SAFETY: `data` was created from `Box<F>` and is consumed exactly once
+            let f = unsafe { Box::from_raw(data as *mut F) };
+            panic::catch_unwind(AssertUnwindSafe(f)).is_ok()
+        }
+        extern "C" fn drop<F>(data: *mut c_void) {
+            // SAFETY: `data` was created from `Box<F>` and is consumed exactly once
+            let _ = panic::catch_unwind(|| unsafe { Box::from_raw(data as *mut F) });
+        }
+        Self {
+            data: Box::into_raw(Box::new(f)) as *mut c_void,
+            call: call::<F>,
+            drop: drop::<F>,
+        }
+    }
+
+    /// Executes the hook.
+    fn call(self) -> HookOutcome {
+        let this = core::mem::ManuallyDrop::new(self);
+        if (this.call)(this.data) {
+            HookOutcome::Completed
+        } else {
+            HookOutcome::Failed("the plugin hook panicked".into())
+        }
+    }
+}
+
+impl Drop for PluginHook {
+    /// Frees the hook without executing it.
+    fn drop(&mut self) {
+        (self.drop)(self.data);
+    }
+}
+
+/// Error of [`PluginRegistrar::register`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiMismatch {
+    /// ABI version of the host.
+    pub host: u32,
+    /// ABI version the plugin was built with.
+    pub plugin: u32,
+}
+
+impl fmt::Display for AbiMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "plugin ABI version {} is incompatible with host version {}",
+            self.plugin, self.host
+        )
+    }
+}
+
+impl std::error::Error for AbiMismatch {}
+
+/// Versioned vtable that the host passes to plugins. The `version` field always
+/// comes first, so it can be checked before anything else is touched.
+#[repr(C)]
+pub struct PluginRegistrar {
+    version: u32,
+    registry: *const c_void,
+    register: extern "C" fn(*const c_void, *const u8, usize, PluginHook),
+}
+
+impl PluginRegistrar {
+    /// Creates the registrar of the host for `registry`.
+    pub fn new(registry: &'static Registry) -> Self {
+        extern "C" fn register(
+            registry: *const c_void,
+            name: *const u8,
+            len: usize,
+            hook: PluginHook,
+        ) {
+            // SAFETY: `registry` was created from `&'static Registry`; `name` and
+            // `len` from a `&str` that is valid for the duration of this call
+            let (registry, name) = unsafe {
+                let name = core::slice::from_raw_parts(name, len);
+                (
+                    &*(registry as *const Registry),
+                    String::from_utf8_lossy(name),
+                )
+            };
+            // panics of the host must not unwind into the plugin
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                registry.register_outcome(HookConfig::new(name), move |_ctx| hook.call())
+            }));
+        }
+        Self {
+            version: PLUGIN_ABI_VERSION,
+            registry: registry as *const Registry as *const c_void,
+            register,
+        }
+    }
+
+    /// Used by the plugin: registers `hook` under `name` in the registry of the
+    /// host. Fails if the host uses a different ABI version. The plugin library
+    /// must stay loaded until the shutdown sequence finished.
+    pub fn register(&self, name: &str, hook: PluginHook) -> Result<(), AbiMismatch> {
+        if self.version != PLUGIN_ABI_VERSION {
+            return Err(AbiMismatch {
+                host: self.version,
+                plugin: PLUGIN_ABI_VERSION,
+            });
+        }
+        (self.register)(self.registry, name.as_ptr(), name.len(), hook);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Entry point of a "plugin".
+    extern "C" fn plugin_init(registrar: &PluginRegistrar, ran: *const AtomicBool) {
+        // SAFETY: the test keeps the flag alive
+        let ran = unsafe { &*ran };
+        let hook = PluginHook::new(move || ran.store(true, Ordering::SeqCst));
+        registrar.register("plugin hook", hook).unwrap();
+        // panics stay inside the plugin
+        registrar
+            .register("panicking plugin hook", PluginHook::new(|| panic!("oops")))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_plugin_registers_hooks() {
+        static REGISTRY: Registry = Registry::new();
+        let ran = Arc::new(AtomicBool::new(false));
+        let registrar = PluginRegistrar::new(&REGISTRY);
+        plugin_init(&registrar, &*ran);
+
+        let report = REGISTRY.run(ShutdownReason::Exit, None);
+        assert!(ran.load(Ordering::SeqCst));
+        assert_eq!(report.results[1].name, "plugin hook");
+        assert!(report.results[1].outcome.is_success());
+        assert_eq!(report.results[0].name, "panicking plugin hook");
+        assert_eq!(
+            report.results[0].outcome.to_string(),
+            "failed: the plugin hook panicked"
+        );
+    }
+
+    #[test]
+    fn test_abi_mismatch() {
+        static REGISTRY: Registry = Registry::new();
+        let mut registrar = PluginRegistrar::new(&REGISTRY);
+        registrar.version += 1;
+        let err = registrar.register("x", PluginHook::new(|| {})).unwrap_err();
+        assert_eq!(err.plugin, PLUGIN_ABI_VERSION);
+        assert!(REGISTRY.is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Registry whose trigger path doesn't touch the heap, for audio/realtime
+//! processes where allocating during teardown is prohibited.
+
+use crate::sync::Mutex;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A hook that can be called through `&mut` without freeing its box.
+type Slot = Box<dyn FnMut() + Send>;
+
+/// Result of a single hook of a [`PreallocatedRegistry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreallocatedResult {
+    /// Name the hook was registered with.
+    pub name: &'static str,
+    /// How long the hook took.
+    pub duration: Duration,
+}
+
+/// Error of [`PreallocatedRegistry::register`]: the capacity is exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegistryFull {
+    /// Capacity of the registry.
+    pub capacity: usize,
+}
+
+impl fmt::Display for RegistryFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "all {} hook slots are taken", self.capacity)
+    }
+}
+
+impl std::error::Error for RegistryFull {}
+
+struct State {
+    slots: Vec<(&'static str, Slot)>,
+    results: Vec<PreallocatedResult>,
+    triggered: bool,
+}
+
+/// Registry with a fixed capacity. All memory (hook slots and the result buffer)
+/// is allocated at construction and registration time. [`Self::run`] performs no
+/// heap allocation or deallocation; only the hooks themselves might.
+///
+/// Hooks run in reverse order of their registration. Unlike [`crate::Registry`],
+/// there are no timeouts, observers or policies.
+pub struct PreallocatedRegistry {
+    state: Mutex<State>,
+    /// As requested; the vectors may have reserved more.
+    capacity: usize,
+}
+
+impl PreallocatedRegistry {
+    /// Constructor. Allocates room for `capacity` hooks and their results.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(State {
+                slots: Vec::with_capacity(capacity),
+                results: Vec::with_capacity(capacity),
+                triggered: false,
+            }),
+            capacity,
+        }
+    }
+
+    /// Registers a hook. The hook is boxed now, so the trigger path doesn't have
+    /// to.
+    pub fn register<F>(&self, name: &'static str, f: F) -> Result<(), RegistryFull>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut state = self.state.lock();
+        if state.slots.len() == self.capacity {
+            return Err(RegistryFull {
+                capacity: self.capacity,
+            });
+        }
+        let mut f = Some(f);
+        state.slots.push((
+            name,
+            Box::new(move || {
+                if let Some(f) = f.take() {
+                    f()
+                }
+            }),
+        ));
+        Ok(())
+    }
+
+    /// Executes all hooks in reverse order of their registration and records
+    /// their results in the preallocated buffer. Runs at most once; subsequent
+    /// calls do nothing. Doesn't allocate.
+    pub fn run(&self) {
+        let mut state = self.state.lock();
+        if state.triggered {
+            return;
+        }
+        state.triggered = true;
+        let State { slots, results, .. } = &mut *state;
+        for (name, slot) in slots.iter_mut().rev() {
+            let begin = Instant::now();
+            slot();
+            // within the capacity, no allocation
+            results.push(PreallocatedResult {
+                name,
+                duration: begin.elapsed(),
+            });
+        }
+    }
+
+    /// Gives `f` access to the results of [`Self::run`] in execution order.
+    pub fn with_results<R>(&self, f: impl FnOnce(&[PreallocatedResult]) -> R) -> R {
+        f(&self.state.lock().results)
+    }
+}
+
+impl fmt::Debug for PreallocatedRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("PreallocatedRegistry")
+            .field("len", &state.slots.len())
+            .field("capacity", &self.capacity)
+            .field("triggered", &state.triggered)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity() {
+        let registry = PreallocatedRegistry::with_capacity(1);
+        registry.register("a", || {}).unwrap();
+        assert_eq!(
+            registry.register("b", || {}),
+            Err(RegistryFull { capacity: 1 })
+        );
+        registry.run();
+        registry.with_results(|results| {
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].name, "a");
+        });
+    }
+}
@@ -0,0 +1,154 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Hooks whose expensive preparation runs at registration instead of at
+//! shutdown, see [`Registry::register_with`].
+
+use crate::registry::HAS_THREADS;
+use crate::sync::Mutex;
+use crate::{HookConfig, HookOutcome, Registry};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+
+impl Registry {
+    /// Registers a hook whose preparation runs right away on a background
+    /// thread. At shutdown, `cleanup` gets the result of `setup`. Lookups like
+    /// resolving the address of a service or fetching a token then don't eat
+    /// into the grace period.
+    ///
+    /// If `setup` didn't finish yet, the hook waits for it, at most until its
+    /// deadline. If `setup` panicked, the hook fails and `cleanup` isn't called.
+    /// Without threads (e.g. `wasm32-wasip1`), `setup` runs at shutdown.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+    ///
+    /// let registry = Registry::new();
+    /// registry.register_with(
+    ///     HookConfig::new("deregister"),
+    ///     || "10.0.0.7:8500".to_string(), // e.g. a DNS lookup
+    ///     |addr| println!("deregistering at {}", addr),
+    /// );
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// ```
+    #[track_caller]
+    pub fn register_with<S, P, F>(&self, config: HookConfig, setup: S, cleanup: F)
+    where
+        S: FnOnce() -> P + Send + 'static,
+        P: Send + 'static,
+        F: FnOnce(P) + Send + 'static,
+    {
+        // taken by whoever gets there first, the background thread or the hook
+        let setup = Arc::new(Mutex::new(Some(setup)));
+        let (tx, rx) = mpsc::channel();
+        if HAS_THREADS {
+            let setup = setup.clone();
+            // if the thread can't be spawned, the hook runs the setup
+            let _ = thread::Builder::new()
+                .name("shutdown-setup".to_string())
+                .spawn(move || {
+                    let setup = setup.lock().take();
+                    if let Some(setup) = setup {
+                        let _ = tx.send(setup());
+                    }
+                });
+        }
+        self.register_outcome(config, move |ctx| {
+            let setup = setup.lock().take();
+            let payload = match setup {
+                Some(setup) => setup(),
+                None => {
+                    let received = match ctx.remaining() {
+                        Some(remaining) => rx.recv_timeout(remaining),
+                        None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    match received {
+                        Ok(payload) => payload,
+                        Err(RecvTimeoutError::Timeout) => return HookOutcome::TimedOut,
+                        Err(RecvTimeoutError::Disconnected) => {
+                            return HookOutcome::Failed("the setup of the hook panicked".into())
+                        }
+                    }
+                }
+            };
+            cleanup(payload);
+            HookOutcome::Completed
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::time::Duration;
+
+    #[test]
+    fn test_setup_result_is_passed() {
+        let registry = Registry::new();
+        let (tx, rx) = mpsc::channel();
+        registry.register_with(
+            HookConfig::new("deregister"),
+            || {
+                thread::sleep(Duration::from_millis(50));
+                42
+            },
+            move |payload| tx.send(payload).unwrap(),
+        );
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        assert_eq!(rx.try_recv(), Ok(42));
+    }
+
+    #[test]
+    fn test_setup_panic_fails_hook() {
+        let registry = Registry::new();
+        registry.register_with(
+            HookConfig::new("deregister"),
+            || -> u32 { panic!("no network") },
+            |_| unreachable!(),
+        );
+        // the setup panics on its own thread
+        thread::sleep(Duration::from_millis(50));
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(
+            report.results[0].outcome.to_string(),
+            "failed: the setup of the hook panicked"
+        );
+    }
+
+    #[test]
+    fn test_slow_setup_times_out() {
+        let registry = Registry::new();
+        registry.register_with(
+            HookConfig::new("deregister"),
+            || thread::sleep(Duration::from_secs(1)),
+            |_| {},
+        );
+        // the setup runs on its own thread by now
+        thread::sleep(Duration::from_millis(50));
+        let report = registry.run(ShutdownReason::Exit, Some(Duration::from_millis(50)));
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+    }
+}
@@ -0,0 +1,60 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Runs hooks on a dedicated thread with a configured niceness, so a heavyweight
+//! flush doesn't starve a latency-critical hook during the grace period.
+
+/// Runs `f` on a dedicated thread whose niceness is set to `niceness` and
+/// waits for it. Panics of `f` are propagated.
+///
+/// Only Linux supports a niceness per thread. Elsewhere, `f` runs on the
+/// current thread with unchanged priority.
+pub(crate) fn run_with_niceness<R, F>(niceness: i32, f: F) -> R
+where
+    F: FnOnce() -> R + Send,
+    R: Send,
+{
+    #[cfg(all(target_os = "linux", feature = "thread-priority"))]
+    {
+        std::thread::scope(|s| {
+            let handle = std::thread::Builder::new()
+                .name("shutdown-hook".to_string())
+                .spawn_scoped(s, move || {
+                    // SAFETY: plain syscalls without pointers; `who = 0` is the calling thread
+                    unsafe {
+                        libc::setpriority(libc::PRIO_PROCESS, 0, niceness);
+                    }
+                    f()
+                })
+                .expect("should spawn hook thread");
+            handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+    #[cfg(not(all(target_os = "linux", feature = "thread-priority")))]
+    {
+        let _ = niceness;
+        f()
+    }
+}
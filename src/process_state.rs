@@ -0,0 +1,146 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Working directory and umask of the process at shutdown.
+//!
+//! Hooks often run long after the process changed its working directory,
+//! e.g. a cleanup that deletes `tmp/` relative to where the tool was started.
+//! The helpers here make the environment of such hooks predictable.
+
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use std::io;
+use std::path::PathBuf;
+
+#[cfg(unix)]
+extern "C" {
+    // `mode_t` is narrower than `u32` on some platforms, the result is masked
+    fn umask(mask: u32) -> u32;
+}
+
+/// Working directory and umask (UNIX) of the process, captured with
+/// [`Self::capture`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessState {
+    /// `None` if the working directory was deleted or is not accessible.
+    cwd: Option<PathBuf>,
+    #[cfg(unix)]
+    umask: u32,
+}
+
+impl ProcessState {
+    /// Captures the current working directory and umask.
+    pub fn capture() -> Self {
+        Self {
+            cwd: std::env::current_dir().ok(),
+            #[cfg(unix)]
+            umask: current_umask(),
+        }
+    }
+
+    /// The captured working directory.
+    pub fn cwd(&self) -> Option<&std::path::Path> {
+        self.cwd.as_deref()
+    }
+
+    /// Restores the working directory and umask. The umask is also restored if
+    /// the working directory can't be entered anymore.
+    pub fn restore(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        // SAFETY: umask() only swaps a value of the process
+        unsafe {
+            umask(self.umask);
+        }
+        match &self.cwd {
+            Some(cwd) => std::env::set_current_dir(cwd),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reads the umask, which is only possible by setting it.
+#[cfg(unix)]
+fn current_umask() -> u32 {
+    // SAFETY: umask() only swaps a value of the process; restored right away
+    unsafe {
+        let mask = umask(0o022) & 0o777;
+        umask(mask);
+        mask
+    }
+}
+
+impl Registry {
+    /// Captures the working directory and umask (UNIX) now and registers a hook
+    /// that restores them at shutdown. Register it first, so it runs before
+    /// the hooks that were registered later, see [`crate::ExecutionOrder`].
+    /// Hooks that need another working directory set
+    /// [`HookConfig::working_dir`].
+    ///
+    /// ## Example
+    /// ```rust
+    /// use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+    ///
+    /// let registry = Registry::new();
+    /// let start = std::env::current_dir().unwrap();
+    /// registry.register(HookConfig::new("remove tmp"), || {
+    ///     // relative to where the tool was started
+    ///     let _ = std::fs::remove_dir_all("tmp-of-the-example");
+    /// });
+    /// registry.register_process_state_restore(HookConfig::new("restore cwd"));
+    ///
+    /// std::env::set_current_dir(std::env::temp_dir()).unwrap();
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// assert_eq!(std::env::current_dir().unwrap(), start);
+    /// ```
+    #[track_caller]
+    pub fn register_process_state_restore(&self, config: HookConfig) {
+        let state = ProcessState::capture();
+        self.register_fallible(config, move |_ctx| state.restore());
+    }
+}
+
+/// Runs `f` inside `dir` and enters the previous working directory afterwards,
+/// see [`HookConfig::working_dir`].
+pub(crate) fn in_working_dir<F>(dir: PathBuf, f: F) -> impl FnOnce(&ShutdownContext) -> HookOutcome
+where
+    F: FnOnce(&ShutdownContext) -> HookOutcome,
+{
+    /// Enters the previous directory, even if the hook panics.
+    struct Leave(Option<PathBuf>);
+
+    impl Drop for Leave {
+        fn drop(&mut self) {
+            if let Some(previous) = &self.0 {
+                let _ = std::env::set_current_dir(previous);
+            }
+        }
+    }
+
+    move |ctx| {
+        let _leave = Leave(std::env::current_dir().ok());
+        if let Err(err) = std::env::set_current_dir(&dir) {
+            let err = format!("can't enter {}: {}", dir.display(), err);
+            return HookOutcome::Failed(err.into());
+        }
+        f(ctx)
+    }
+}
@@ -0,0 +1,232 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Quarantine for hooks that keep failing across runs of the application.
+
+use crate::diagnostics::{self, Level};
+use crate::{HookConfig, HookOutcome, HookResult, Importance};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Consecutive failures per hook name.
+pub(crate) type Failures = HashMap<String, u32>;
+
+/// What happens with a hook that is quarantined, see [`Quarantine`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuarantineAction {
+    /// The hook runs as [`Importance::BestEffort`], so it is skipped when time
+    /// gets short. A successful run releases it. This is the default.
+    #[default]
+    Demote,
+    /// The hook doesn't run and is reported as [`HookOutcome::Skipped`], with an
+    /// error message of the crate. It stays quarantined until
+    /// [`Quarantine::release`] is called.
+    Skip,
+}
+
+/// Remembers which hooks failed or timed out in the previous runs of the
+/// application, in a small file. Hooks that did so in `after` consecutive runs
+/// are quarantined, so a consistently broken hook doesn't eat the grace period
+/// on every restart. See [`crate::Registry::set_quarantine`].
+///
+/// The file holds one line per hook that failed recently: the number of
+/// consecutive failures and the name of the hook, e.g. `3 upload metrics`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarantine {
+    path: PathBuf,
+    after: u32,
+    action: QuarantineAction,
+}
+
+impl Quarantine {
+    /// Constructor. Hooks are quarantined after failing in `after` consecutive
+    /// runs; `0` is treated as `1`.
+    ///
+    /// ## Parameters
+    /// * `path` file that survives restarts, e.g. in `/var/lib/<app>/`
+    pub fn new(path: impl Into<PathBuf>, after: u32) -> Self {
+        Self {
+            path: path.into(),
+            after: after.max(1),
+            action: QuarantineAction::default(),
+        }
+    }
+
+    /// Sets what happens with quarantined hooks.
+    pub fn action(mut self, action: QuarantineAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Names of the hooks that are quarantined in the next run.
+    pub fn quarantined(&self) -> io::Result<Vec<String>> {
+        let mut names: Vec<_> = self
+            .read()?
+            .into_iter()
+            .filter(|&(_, failures)| failures >= self.after)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Forgets the failures of the hook `name`, e.g. after it was fixed.
+    pub fn release(&self, name: &str) -> io::Result<()> {
+        let mut failures = self.read()?;
+        if failures.remove(name).is_some() {
+            self.write(&failures)?;
+        }
+        Ok(())
+    }
+
+    /// The failures of the previous runs. Problems are reported and treated as
+    /// no failures, the shutdown must go on.
+    pub(crate) fn load(&self) -> Failures {
+        self.read().unwrap_or_else(|err| {
+            diagnostics::emit(
+                Level::Warn,
+                format_args!("can't read the quarantine {}: {}", self.path.display(), err),
+            );
+            Failures::new()
+        })
+    }
+
+    /// Applies the quarantine to a hook. `None` if it must be skipped.
+    pub(crate) fn apply(&self, failures: &Failures, config: HookConfig) -> Option<HookConfig> {
+        let count = failures.get(config.name()).copied().unwrap_or(0);
+        if count < self.after {
+            return Some(config);
+        }
+        match self.action {
+            QuarantineAction::Demote => Some(config.importance(Importance::BestEffort)),
+            QuarantineAction::Skip => {
+                diagnostics::emit(
+                    Level::Error,
+                    format_args!(
+                        "hook '{}' failed in the last {} runs and is quarantined, skipped",
+                        config.name(),
+                        count
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    /// Updates the failures with the results of this run and stores them.
+    pub(crate) fn record(&self, mut failures: Failures, results: &[HookResult]) {
+        for result in results {
+            match result.outcome {
+                HookOutcome::Completed => {
+                    failures.remove(&result.name);
+                }
+                HookOutcome::Failed(_) | HookOutcome::TimedOut => {
+                    *failures.entry(result.name.clone()).or_insert(0) += 1;
+                }
+                HookOutcome::Skipped => {}
+            }
+        }
+        if let Err(err) = self.write(&failures) {
+            diagnostics::emit(
+                Level::Warn,
+                format_args!(
+                    "can't write the quarantine {}: {}",
+                    self.path.display(),
+                    err
+                ),
+            );
+        }
+    }
+
+    fn read(&self) -> io::Result<Failures> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Failures::new()),
+            Err(err) => return Err(err),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter_map(|(count, name)| Some((name.to_string(), count.parse().ok()?)))
+            .collect())
+    }
+
+    fn write(&self, failures: &Failures) -> io::Result<()> {
+        let mut lines: Vec<_> = failures
+            .iter()
+            .map(|(name, count)| format!("{} {}\n", count, name))
+            .collect();
+        lines.sort();
+        fs::write(&self.path, lines.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Registry, ShutdownReason};
+    use std::time::Duration;
+
+    /// Runs a registry with a failing and a flaky hook.
+    fn run(quarantine: &Quarantine, flaky_fails: bool) -> crate::ShutdownReport {
+        let registry = Registry::new();
+        registry.set_quarantine(quarantine.clone());
+        registry.set_best_effort_threshold(Duration::from_secs(60));
+        registry.register_fallible(HookConfig::new("broken upload"), |_| Err("offline"));
+        registry.register_fallible(HookConfig::new("flaky"), move |_| {
+            if flaky_fails {
+                Err("flaky")
+            } else {
+                Ok(())
+            }
+        });
+        registry.run(ShutdownReason::Exit, Some(Duration::from_secs(30)))
+    }
+
+    #[test]
+    fn test_hooks_are_quarantined_after_repeated_failures() {
+        let path = std::env::temp_dir().join(format!("quarantine-test-{}", std::process::id()));
+        let quarantine = Quarantine::new(&path, 2);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(run(&quarantine, true).failures().count(), 2);
+        assert!(quarantine.quarantined().unwrap().is_empty());
+        assert_eq!(run(&quarantine, false).failures().count(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2 broken upload\n");
+        assert_eq!(quarantine.quarantined().unwrap(), ["broken upload"]);
+
+        // demoted to best effort, skipped as time is short
+        let report = run(&quarantine, false);
+        let skipped: Vec<_> = report.skipped().map(|r| r.name.as_str()).collect();
+        assert_eq!(skipped, ["broken upload"]);
+
+        let quarantine = quarantine.action(QuarantineAction::Skip);
+        run(&quarantine, false);
+        assert_eq!(quarantine.quarantined().unwrap(), ["broken upload"]);
+        quarantine.release("broken upload").unwrap();
+        assert!(quarantine.quarantined().unwrap().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,2108 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Registry that collects named hooks and runs them at shutdown.
+
+use crate::diagnostics::{self, Level};
+use crate::events::EventHook;
+use crate::sync::{Condvar, Mutex};
+use crate::{
+    EscalationPolicy, ExitDecision, HookConfig, HookError, HookInfo, HookObserver, HookOutcome,
+    HookResult, Importance, Quarantine, ShutdownBlackboard, ShutdownContext, ShutdownReason,
+    ShutdownReport, TagFilter,
+};
+use core::panic::Location;
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+/// Whether the target can spawn threads. Without threads (e.g. `wasm32-wasip1`),
+/// escalation and the runner thread are not available and hooks run inline.
+pub(crate) const HAS_THREADS: bool =
+    !cfg!(all(target_family = "wasm", not(target_feature = "atomics")));
+
+/// Whether [`Registry::set_isolate_panics`] can turn panics of hooks into
+/// failures. `false` in builds with `panic = "abort"`.
+pub const CAN_ISOLATE_PANICS: bool = cfg!(panic = "unwind");
+
+/// Boxed hook that receives the [`ShutdownContext`].
+type HookFn = Box<dyn FnOnce(&ShutdownContext) -> HookOutcome + Send>;
+
+/// Dry run of a hook, see [`Registry::rehearse`].
+type RehearsalFn = Arc<dyn Fn(&ShutdownContext) -> HookOutcome + Send + Sync>;
+
+/// A registered hook together with its configuration.
+struct Hook {
+    config: HookConfig,
+    f: HookFn,
+    /// Where the hook was registered.
+    location: &'static Location<'static>,
+    /// Set for hooks that were registered with [`Registry::register_rehearsable`].
+    rehearsal: Option<RehearsalFn>,
+}
+
+/// What happens with hooks that are registered after [`Registry::run`] was
+/// called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LateRegistrationPolicy {
+    /// The hook is executed immediately on the registering thread. This is the
+    /// default.
+    #[default]
+    RunImmediately,
+    /// The hook is dropped without being executed.
+    SilentlyDrop,
+    /// The registration panics. Useful in tests to catch bugs.
+    Panic,
+}
+
+/// What happens in debug builds if a hook is registered with the same name and
+/// from the same location as a hook that is already registered. This usually
+/// means a hook is accidentally armed twice, e.g. in a loop. Release builds
+/// don't check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateRegistrationPolicy {
+    /// A warning goes to the diagnostics sink, see [`crate::set_diagnostics_sink`].
+    /// This is the default.
+    #[default]
+    Warn,
+    /// The registration panics. Useful in tests to catch bugs.
+    Panic,
+    /// Duplicates are registered without further notice.
+    Allow,
+}
+
+/// What happens with hooks that touch external systems, see
+/// [`HookConfig::external`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExternalHooks {
+    /// The hooks run like all others. This is the default.
+    #[default]
+    Run,
+    /// The hooks are replaced by recorders at registration. They don't run but
+    /// are reported as [`HookOutcome::Skipped`] and listed by
+    /// [`Registry::recorded_external_hooks`]. For unit tests of functions that
+    /// register hooks, so the tests don't hit real databases or networks.
+    Record,
+}
+
+/// How registrations that race with [`Registry::run`] are synchronized. A
+/// registration is never torn: the hook is either part of the run or handled by
+/// the [`LateRegistrationPolicy`] as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegistrationBarrier {
+    /// The run takes the hooks that are registered at the moment it starts.
+    /// Later registrations are handled by the [`LateRegistrationPolicy`] right
+    /// away, i.e. with `RunImmediately` possibly in parallel to the run. This is
+    /// the default.
+    #[default]
+    Snapshot,
+    /// Registrations block while the run is in progress and are handled by the
+    /// [`LateRegistrationPolicy`] after the run has finished. Late hooks never run
+    /// in parallel to the sequence. Hooks that register hooks while they are
+    /// executed are not blocked.
+    Block,
+}
+
+/// Order in which [`Registry::run`] executes the hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionOrder {
+    /// Reverse order of registration, like destructors. This is the default.
+    #[default]
+    Lifo,
+    /// Order of registration.
+    Fifo,
+    /// Higher [`HookConfig::priority`] first. Hooks with the same priority run in
+    /// reverse order of registration.
+    Priority,
+    /// Random order, determined by the seed. Only for tests: hooks that rely on
+    /// the order of other hooks without saying so fail sooner or later. The
+    /// seed is reported when the order is set, see [`Self::shuffled`].
+    Shuffled(u64),
+}
+
+impl ExecutionOrder {
+    /// [`Self::Shuffled`] with the seed of [`crate::SHUFFLE_SEED_ENV`], or with a
+    /// new one from the clock, so every test run checks another order.
+    pub fn shuffled() -> Self {
+        let seed = std::env::var(crate::SHUFFLE_SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64)
+            });
+        Self::Shuffled(seed)
+    }
+}
+
+/// Mutable state of a [`Registry`].
+struct State {
+    hooks: Vec<Hook>,
+    /// Set once the registry was triggered.
+    triggered: Option<(ShutdownReason, Option<Instant>)>,
+    late_policy: LateRegistrationPolicy,
+    order: ExecutionOrder,
+    observers: Vec<Arc<dyn HookObserver>>,
+    best_effort_threshold: Duration,
+    escalation: Option<EscalationPolicy>,
+    /// Stack size of the dedicated runner thread, if enabled.
+    runner_stack_size: Option<usize>,
+    /// Registries of subsystems, see [`Registry::child`].
+    children: Vec<Weak<Registry>>,
+    /// Names of the hooks of the current run that didn't finish yet, in
+    /// execution order.
+    pending: Vec<String>,
+    /// See [`Registry::set_enabled`].
+    enabled: bool,
+    barrier: RegistrationBarrier,
+    duplicate_policy: DuplicateRegistrationPolicy,
+    /// Whether [`Registry::run_filtered`] is in progress.
+    running: bool,
+    /// See [`Registry::set_pause_between_hooks`].
+    pause: Option<Duration>,
+    /// See [`Registry::set_isolate_panics`].
+    isolate_panics: bool,
+    /// Blackboard of the current or last shutdown sequence.
+    blackboard: Option<ShutdownBlackboard>,
+    /// See [`Registry::set_quarantine`].
+    quarantine: Option<Quarantine>,
+    /// See [`Registry::shutdown_complete`].
+    #[cfg(feature = "async")]
+    completion: Option<crate::Completion>,
+    /// Hooks that were recorded instead of executed, if
+    /// [`ExternalHooks::Record`] is set.
+    recorded: Option<Arc<Mutex<Vec<HookInfo>>>>,
+    #[cfg(feature = "testing")]
+    chaos: Option<crate::Chaos>,
+}
+
+/// Collects hooks and executes them during [`Registry::run`]. Unlike
+/// [`crate::on_shutdown`] the hooks are not bound to a lexical scope.
+///
+/// By default, hooks run in reverse order of their registration (like
+/// destructors). See [`ExecutionOrder`] for alternatives.
+pub struct Registry {
+    state: Mutex<State>,
+    /// Hooks for recurring events. They are kept separately because they are
+    /// never consumed.
+    events: Mutex<Vec<Arc<EventHook>>>,
+    /// Notified when a run has finished, see [`RegistrationBarrier::Block`].
+    finished: Condvar,
+}
+
+std::thread_local! {
+    /// Whether the current thread executes a hook.
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+    /// Registries whose run the current thread takes part in, innermost last:
+    /// the thread that called [`Registry::run`] and the threads of its hooks.
+    static RUNS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    /// Whether the current thread executes a hook whose panics are caught.
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+impl Registry {
+    /// Constructor. Creates an empty registry. Can be used in statics.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                hooks: Vec::new(),
+                triggered: None,
+                late_policy: LateRegistrationPolicy::RunImmediately,
+                order: ExecutionOrder::Lifo,
+                observers: Vec::new(),
+                best_effort_threshold: Duration::from_secs(0),
+                escalation: None,
+                runner_stack_size: None,
+                children: Vec::new(),
+                pending: Vec::new(),
+                enabled: true,
+                barrier: RegistrationBarrier::Snapshot,
+                duplicate_policy: DuplicateRegistrationPolicy::Warn,
+                running: false,
+                pause: None,
+                isolate_panics: false,
+                blackboard: None,
+                quarantine: None,
+                #[cfg(feature = "async")]
+                completion: None,
+                recorded: None,
+                #[cfg(feature = "testing")]
+                chaos: None,
+            }),
+            events: Mutex::new(Vec::new()),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Sets what happens with hooks that are registered after the registry was
+    /// triggered. See [`LateRegistrationPolicy`].
+    pub fn set_late_registration_policy(&self, policy: LateRegistrationPolicy) {
+        self.state.lock().late_policy = policy;
+    }
+
+    /// Sets what happens if the same hook is registered twice from the same
+    /// location. See [`DuplicateRegistrationPolicy`].
+    pub fn set_duplicate_registration_policy(&self, policy: DuplicateRegistrationPolicy) {
+        self.state.lock().duplicate_policy = policy;
+    }
+
+    /// Sets how registrations that race with [`Self::run`] are synchronized. See
+    /// [`RegistrationBarrier`].
+    pub fn set_registration_barrier(&self, barrier: RegistrationBarrier) {
+        self.state.lock().barrier = barrier;
+    }
+
+    /// Sets the order in which [`Self::run`] executes the hooks.
+    pub fn set_execution_order(&self, order: ExecutionOrder) {
+        if let ExecutionOrder::Shuffled(seed) = order {
+            diagnostics::emit(
+                Level::Info,
+                format_args!(
+                    "hooks run in shuffled order, reproduce it with {}={}",
+                    crate::SHUFFLE_SEED_ENV,
+                    seed
+                ),
+            );
+        }
+        self.state.lock().order = order;
+    }
+
+    /// [`Importance::BestEffort`] hooks are skipped and reported as
+    /// [`HookOutcome::Skipped`] once less than `threshold` of the global budget of
+    /// [`Self::run`] is left. This leaves the remaining time to more important
+    /// hooks. By default, they are only skipped when the budget is used up.
+    pub fn set_best_effort_threshold(&self, threshold: Duration) {
+        self.state.lock().best_effort_threshold = threshold;
+    }
+
+    /// Pauses between two hooks, so other threads get CPU time during a long
+    /// shutdown sequence, e.g. to answer outstanding health checks. A zero
+    /// duration only yields the current thread. The pause never exceeds the
+    /// global budget of [`Self::run`]. Disabled by default.
+    ///
+    /// Also applies between async hooks, as the built-in executor drives them one
+    /// after another on the current thread.
+    pub fn set_pause_between_hooks(&self, pause: Option<Duration>) {
+        self.state.lock().pause = pause;
+    }
+
+    /// Turns a panic of a hook into [`HookOutcome::Failed`], so the remaining
+    /// hooks still run. Disabled by default: panics are propagated.
+    ///
+    /// Builds with `panic = "abort"` can't isolate panics, see
+    /// [`CAN_ISOLATE_PANICS`]. A panic aborts the whole process there, no matter
+    /// on which thread, so running the hook on a helper thread wouldn't help
+    /// either. In that case, enabling it emits a warning and hooks run as usual.
+    pub fn set_isolate_panics(&self, isolate: bool) {
+        if isolate && !CAN_ISOLATE_PANICS {
+            diagnostics::emit(
+                Level::Warn,
+                format_args!(
+                    "panics of hooks can't be isolated with panic = \"abort\", a panicking hook aborts the process"
+                ),
+            );
+        }
+        self.state.lock().isolate_panics = isolate;
+    }
+
+    /// Quarantines hooks that failed or timed out in the previous runs of the
+    /// application, see [`Quarantine`]. Each run of [`Self::run`] updates the
+    /// file of the quarantine with its results.
+    pub fn set_quarantine(&self, quarantine: Quarantine) {
+        self.state.lock().quarantine = Some(quarantine);
+    }
+
+    /// Sets what happens with hooks that are marked with
+    /// [`HookConfig::external`] and registered afterwards. Tests switch to
+    /// [`ExternalHooks::Record`], e.g. for the [`crate::global`] registry. Code
+    /// that creates its own registry can pass
+    /// `if cfg!(test) { ExternalHooks::Record } else { ExternalHooks::Run }`.
+    ///
+    /// ```rust
+    /// use simple_on_shutdown::{ExternalHooks, HookConfig, Registry, ShutdownReason};
+    ///
+    /// fn connect(registry: &Registry) {
+    ///     registry.register(HookConfig::new("close_db").external(), || {
+    ///         unreachable!("closes the real connection")
+    ///     });
+    /// }
+    ///
+    /// // in a unit test
+    /// let registry = Registry::new();
+    /// registry.set_external_hooks(ExternalHooks::Record);
+    /// connect(&registry);
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// assert_eq!(registry.recorded_external_hooks()[0].name, "close_db");
+    /// ```
+    pub fn set_external_hooks(&self, mode: ExternalHooks) {
+        let mut state = self.state.lock();
+        state.recorded = match mode {
+            ExternalHooks::Run => None,
+            ExternalHooks::Record => Some(
+                state
+                    .recorded
+                    .take()
+                    .unwrap_or_else(|| Arc::new(Mutex::new(Vec::new()))),
+            ),
+        };
+    }
+
+    /// The hooks that were recorded instead of executed, in the order they
+    /// would have run, see [`ExternalHooks::Record`].
+    pub fn recorded_external_hooks(&self) -> Vec<HookInfo> {
+        match &self.state.lock().recorded {
+            Some(recorded) => recorded.lock().clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Sets what happens with hooks that exceed their timeout or the global
+    /// budget. Hooks can override it with [`HookConfig::escalation`]. By default,
+    /// hooks are not escalated and may run as long as they want. Ignored on
+    /// targets without threads, e.g. WASI.
+    pub fn set_escalation_policy(&self, policy: EscalationPolicy) {
+        self.state.lock().escalation = Some(policy);
+    }
+
+    /// Executes the whole hook sequence of [`Self::run`] on a dedicated
+    /// `shutdown-runner` thread with the given stack size. The triggering thread
+    /// only waits for it. This way, stack-heavy or TLS-dependent cleanups don't
+    /// depend on the context that triggered the shutdown, e.g. a thread that is
+    /// already tearing down its thread-locals. By default, hooks run on the
+    /// triggering thread. Ignored on targets without threads, e.g. WASI.
+    pub fn set_runner_thread(&self, stack_size: usize) {
+        self.state.lock().runner_stack_size = Some(stack_size);
+    }
+
+    /// Injects faults into the hooks of the following runs, see [`crate::Chaos`].
+    /// Only for tests.
+    #[cfg(feature = "testing")]
+    pub fn set_chaos(&self, chaos: crate::Chaos) {
+        self.state.lock().chaos = Some(chaos);
+    }
+
+    /// Adds an observer that is notified before and after every hook execution.
+    pub fn add_observer(&self, observer: Arc<dyn HookObserver>) {
+        self.state.lock().observers.push(observer);
+    }
+
+    /// Observers added with [`Self::add_observer`].
+    pub(crate) fn observers(&self) -> Vec<Arc<dyn HookObserver>> {
+        self.state.lock().observers.clone()
+    }
+
+    /// Hooks for recurring events, see [`Self::emit`].
+    pub(crate) fn event_hooks(&self) -> &Mutex<Vec<Arc<EventHook>>> {
+        &self.events
+    }
+
+    /// Disarms (`false`) or re-arms (`true`) the registry. While disarmed,
+    /// [`Self::run`] does nothing and returns an empty report; the hooks stay
+    /// registered and the registry is not triggered. E.g., during an in-place
+    /// upgrade where the new process takes over the resources:
+    /// `global().set_enabled(false)`. Registries are armed by default.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.lock().enabled = enabled;
+    }
+
+    /// Whether the registry is armed, see [`Self::set_enabled`].
+    pub fn is_enabled(&self) -> bool {
+        self.state.lock().enabled
+    }
+
+    /// Identifies the registry in [`RUNS`]. Stable while it is borrowed for a
+    /// run.
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Registries whose run the current thread takes part in, with this one.
+    fn runs(&self) -> Vec<usize> {
+        RUNS.with(|r| r.borrow().iter().copied().chain([self.id()]).collect())
+    }
+
+    /// Adds a child registry whose hooks run before the own ones.
+    pub(crate) fn add_child(&self, child: Weak<Registry>) {
+        let mut state = self.state.lock();
+        // forget children that were dropped in the meantime
+        state.children.retain(|c| c.strong_count() > 0);
+        state.children.push(child);
+    }
+
+    /// Names of the hooks that [`Self::run`] is about to execute or currently
+    /// executes, in execution order. Tells which cleanup steps are lost if the
+    /// process is force-exited, see [`crate::force_exit`].
+    pub fn pending_hooks(&self) -> Vec<String> {
+        self.state.lock().pending.clone()
+    }
+
+    /// Whether [`Self::run`] was already called.
+    pub fn is_triggered(&self) -> bool {
+        self.state.lock().triggered.is_some()
+    }
+
+    /// Registers a hook that doesn't need any information about the shutdown.
+    #[track_caller]
+    pub fn register<F>(&self, config: HookConfig, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.register_with_context(config, move |_ctx| f());
+    }
+
+    /// Registers a context-aware hook. It receives a [`ShutdownContext`] when it
+    /// gets executed.
+    ///
+    /// If the registry was already triggered, the [`LateRegistrationPolicy`]
+    /// decides what happens with the hook.
+    #[track_caller]
+    pub fn register_with_context<F>(&self, config: HookConfig, f: F)
+    where
+        F: FnOnce(&ShutdownContext) + Send + 'static,
+    {
+        self.register_outcome(config, move |ctx| {
+            f(ctx);
+            HookOutcome::Completed
+        });
+    }
+
+    /// Registers a context-aware hook that can fail. The error ends up in the
+    /// [`ShutdownReport`].
+    #[track_caller]
+    pub fn register_fallible<F, E>(&self, config: HookConfig, f: F)
+    where
+        F: FnOnce(&ShutdownContext) -> Result<(), E> + Send + 'static,
+        E: Into<HookError>,
+    {
+        self.register_outcome(config, move |ctx| match f(ctx) {
+            Ok(()) => HookOutcome::Completed,
+            Err(err) => HookOutcome::Failed(err.into()),
+        });
+    }
+
+    /// Registers a hook that supports rehearsals, see [`Self::rehearse`]. It is
+    /// called once more at shutdown, with [`ShutdownContext::is_rehearsal`]
+    /// being `false`.
+    ///
+    /// ```rust
+    /// use simple_on_shutdown::{HookConfig, Registry};
+    ///
+    /// let registry = Registry::new();
+    /// registry.register_rehearsable(HookConfig::new("flush"), |ctx| {
+    ///     let dir = std::env::temp_dir();
+    ///     if ctx.is_rehearsal() {
+    ///         // only check that the flush would work
+    ///         return match dir.metadata() {
+    ///             Ok(meta) if !meta.permissions().readonly() => Ok(()),
+    ///             _ => Err("spool directory is not writable"),
+    ///         };
+    ///     }
+    ///     // flush into `dir`
+    ///     Ok(())
+    /// });
+    /// assert!(registry.rehearse(None).is_success());
+    /// ```
+    #[track_caller]
+    pub fn register_rehearsable<F, E>(&self, config: HookConfig, f: F)
+    where
+        F: Fn(&ShutdownContext) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<HookError>,
+    {
+        let rehearsal: RehearsalFn = Arc::new(move |ctx| match f(ctx) {
+            Ok(()) => HookOutcome::Completed,
+            Err(err) => HookOutcome::Failed(err.into()),
+        });
+        let real = rehearsal.clone();
+        self.insert(
+            config,
+            Box::new(move |ctx| real(ctx)),
+            Location::caller(),
+            Some(rehearsal),
+        );
+    }
+
+    /// Registers a hook that determines its [`HookOutcome`] by itself.
+    #[track_caller]
+    pub(crate) fn register_outcome<F>(&self, config: HookConfig, f: F)
+    where
+        F: FnOnce(&ShutdownContext) -> HookOutcome + Send + 'static,
+    {
+        self.insert(config, Box::new(f), Location::caller(), None);
+    }
+
+    /// Adds a hook, or handles it according to the [`LateRegistrationPolicy`].
+    fn insert(
+        &self,
+        mut config: HookConfig,
+        f: HookFn,
+        location: &'static Location<'static>,
+        rehearsal: Option<RehearsalFn>,
+    ) {
+        config.snapshot_env();
+        if config.get_origin().is_none() {
+            config = config.origin(location.file());
+        }
+        let mut hook = Hook {
+            config,
+            f,
+            location,
+            rehearsal,
+        };
+        if let Some(dir) = hook.config.get_working_dir() {
+            let f = hook.f;
+            hook.f = Box::new(crate::process_state::in_working_dir(dir.to_path_buf(), f));
+        }
+        let mut state = self.state.lock();
+        if let (Some(recorded), true) = (&state.recorded, hook.config.is_external()) {
+            let recorded = recorded.clone();
+            let info = HookInfo::from(&hook.config);
+            hook.f = Box::new(move |_| {
+                recorded.lock().push(info);
+                HookOutcome::Skipped
+            });
+        }
+        if state.barrier == RegistrationBarrier::Block && !IN_HOOK.with(Cell::get) {
+            state = self.finished.wait_while(state, |s| s.running);
+        }
+        match state.triggered {
+            None => {
+                let duplicate = cfg!(debug_assertions)
+                    && state.hooks.iter().any(|h| {
+                        h.location == hook.location && h.config.name() == hook.config.name()
+                    });
+                if duplicate {
+                    match state.duplicate_policy {
+                        DuplicateRegistrationPolicy::Warn => diagnostics::emit(
+                            Level::Warn,
+                            format_args!(
+                                "hook '{}' was registered twice at {}",
+                                hook.config.name(),
+                                hook.location
+                            ),
+                        ),
+                        DuplicateRegistrationPolicy::Panic => {
+                            drop(state);
+                            panic!(
+                                "hook '{}' was registered twice at {}",
+                                hook.config.name(),
+                                hook.location
+                            );
+                        }
+                        DuplicateRegistrationPolicy::Allow => {}
+                    }
+                }
+                state.hooks.push(hook)
+            }
+            Some((reason, deadline)) => match state.late_policy {
+                LateRegistrationPolicy::RunImmediately if !hook.config.is_enabled() => {}
+                LateRegistrationPolicy::RunImmediately => {
+                    let observers = state.observers.clone();
+                    let escalation = state.escalation;
+                    let blackboard = state.blackboard.clone().unwrap_or_default();
+                    let hook = if state.isolate_panics {
+                        isolate_panics(hook)
+                    } else {
+                        hook
+                    };
+                    drop(state);
+                    let runs = self.runs();
+                    execute(
+                        hook,
+                        reason,
+                        deadline,
+                        &observers,
+                        escalation,
+                        &blackboard,
+                        &runs,
+                    );
+                }
+                LateRegistrationPolicy::SilentlyDrop => {
+                    // the captures of the hook may panic when they are dropped
+                    drop(state);
+                    drop(hook);
+                }
+                LateRegistrationPolicy::Panic => {
+                    drop(state);
+                    panic!(
+                        "hook '{}' was registered after the shutdown started",
+                        hook.config.name()
+                    );
+                }
+            },
+        }
+    }
+
+    /// Registers an async hook. It gets a [`crate::CancellationToken`] that is
+    /// cancelled when the timeout of the hook or the global deadline expires. The
+    /// future then has [`HookConfig::cancel_grace`] more time before it is dropped
+    /// and reported as [`HookOutcome::TimedOut`].
+    ///
+    /// The future is driven on the thread that runs the hooks, no async runtime is
+    /// required.
+    #[cfg(feature = "async")]
+    #[track_caller]
+    pub fn register_async<F, Fut>(&self, config: HookConfig, f: F)
+    where
+        F: FnOnce(crate::CancellationToken) -> Fut + Send + 'static,
+        Fut: core::future::Future<Output = ()>,
+    {
+        self.register_outcome(config, move |ctx| {
+            let token = crate::CancellationToken::new();
+            let fut = f(token.clone());
+            let grace = ctx.config().get_cancel_grace();
+            if crate::executor::block_on(fut, ctx.deadline(), grace, &token) {
+                HookOutcome::Completed
+            } else {
+                HookOutcome::TimedOut
+            }
+        });
+    }
+
+    /// Snapshot of the hooks that are currently registered, in registration
+    /// order. With the `serde` feature, it can be exposed as JSON, e.g. on an admin
+    /// endpoint.
+    pub fn hooks(&self) -> Vec<HookInfo> {
+        let state = self.state.lock();
+        state
+            .hooks
+            .iter()
+            .map(|h| HookInfo::from(&h.config))
+            .collect()
+    }
+
+    /// Names of the hooks in the order [`Self::run`] would execute them right
+    /// now: the hooks of the children (see [`Self::child`]) in reverse order of
+    /// creation, then the own hooks according to the [`ExecutionOrder`]. Hooks
+    /// that the tag filters of the environment exclude are left out.
+    ///
+    /// The order is part of the API contract and doesn't change between
+    /// releases without a major version bump.
+    pub fn execution_order(&self) -> Vec<String> {
+        let env_filter = TagFilter::from_env();
+        let (mut hooks, order, children) = {
+            let state = self.state.lock();
+            let hooks = state
+                .hooks
+                .iter()
+                .filter(|h| env_filter.matches(&h.config))
+                .map(|h| (h.config.name().to_string(), h.config.get_priority()))
+                .collect::<Vec<_>>();
+            (hooks, state.order, state.children.clone())
+        };
+        sort(&mut hooks, order, |&(_, priority)| priority);
+        children
+            .iter()
+            .rev()
+            .filter_map(Weak::upgrade)
+            .flat_map(|child| child.execution_order())
+            .chain(hooks.into_iter().map(|(name, _)| name))
+            .collect()
+    }
+
+    /// Rehearses the shutdown of a live service: runs the dry run of every hook
+    /// that was registered with [`Self::register_rehearsable`], with
+    /// [`ShutdownReason::Rehearsal`], in the order [`Self::run`] would use. The
+    /// other hooks are reported as [`HookOutcome::Skipped`], they can't run
+    /// without side effects.
+    ///
+    /// Nothing is removed and the registry is not triggered, so this can be
+    /// called any number of times, e.g. from a readiness check. Failed dry runs
+    /// show which hooks wouldn't manage the real shutdown.
+    ///
+    /// ## Parameters
+    /// * `budget` total time all dry runs together should take; `None` for unlimited
+    pub fn rehearse(&self, budget: Option<Duration>) -> ShutdownReport {
+        let deadline = budget.map(|b| Instant::now() + b);
+        let blackboard = ShutdownBlackboard::new();
+        let (mut hooks, order, observers, escalation, children) = {
+            let state = self.state.lock();
+            let hooks = state
+                .hooks
+                .iter()
+                .map(|h| (h.config.clone(), h.rehearsal.clone(), h.location))
+                .collect::<Vec<_>>();
+            (
+                hooks,
+                state.order,
+                state.observers.clone(),
+                state.escalation,
+                state.children.clone(),
+            )
+        };
+        sort(&mut hooks, order, |(config, ..)| config.get_priority());
+        let runs = self.runs();
+        let results = children
+            .iter()
+            .rev()
+            .filter_map(Weak::upgrade)
+            .flat_map(|child| {
+                let budget = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                child.rehearse(budget).results
+            })
+            .chain(hooks.into_iter().map(|(config, rehearsal, location)| {
+                match rehearsal.filter(|_| config.is_enabled()) {
+                    Some(rehearsal) => {
+                        let hook = Hook {
+                            config,
+                            f: Box::new(move |ctx| rehearsal(ctx)),
+                            location,
+                            rehearsal: None,
+                        };
+                        let reason = ShutdownReason::Rehearsal;
+                        execute(
+                            hook,
+                            reason,
+                            deadline,
+                            &observers,
+                            escalation,
+                            &blackboard,
+                            &runs,
+                        )
+                    }
+                    None => skip(&config),
+                }
+            }))
+            .collect();
+        ShutdownReport {
+            results,
+            exit: None,
+        }
+    }
+
+    /// Completes when the shutdown sequence of this registry finished, see
+    /// [`Self::run`]. Completes right away if it already finished. Runs that
+    /// don't trigger the registry, like [`Self::run_partial`], don't count.
+    #[cfg(feature = "async")]
+    pub fn shutdown_complete(&self) -> crate::Completion {
+        let mut state = self.state.lock();
+        if state.triggered.is_some() && !state.running {
+            return crate::Completion::completed();
+        }
+        state
+            .completion
+            .get_or_insert_with(crate::Completion::new)
+            .clone()
+    }
+
+    /// Number of hooks that are currently registered.
+    pub fn len(&self) -> usize {
+        self.state.lock().hooks.len()
+    }
+
+    /// Whether no hooks are registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Executes all registered hooks and removes them from the registry. Hooks
+    /// that are registered afterwards are handled according to the
+    /// [`LateRegistrationPolicy`]. Returns how each hook ended.
+    ///
+    /// Hooks of child registries (see [`Self::child`]) run first, the youngest
+    /// child first.
+    ///
+    /// Timeouts can be overridden per hook with environment variables like
+    /// `SHUTDOWN_TIMEOUT__FLUSH_DB=5s`, see [`crate::timeout_env_var`]. Hooks can be
+    /// skipped by their tags with [`crate::EXCLUDE_TAGS_ENV`] and
+    /// [`crate::INCLUDE_TAGS_ENV`].
+    ///
+    /// If a hook calls this (directly or e.g. through [`crate::run_global_hooks`]
+    /// or an `atexit` handler) while the hooks of the same registry run, the
+    /// nested call does nothing: it reports an error to the diagnostics sink and
+    /// returns an empty report. The hooks of the outer call keep running. Other
+    /// calls, e.g. from other threads or from hooks of other registries, wait
+    /// until the running call has finished.
+    ///
+    /// ## Parameters
+    /// * `reason` why the shutdown sequence was started
+    /// * `budget` total time all hooks together should take; `None` for unlimited
+    pub fn run(&self, reason: ShutdownReason, budget: Option<Duration>) -> ShutdownReport {
+        self.run_filtered(reason, budget, &TagFilter::new())
+    }
+
+    /// Executes only the hooks that match `filter` and removes them from the
+    /// registry. Unlike [`Self::run_filtered`], the registry is not triggered
+    /// and all other hooks stay registered. Hooks of child registries don't run.
+    /// Does nothing while the registry is disarmed, see [`Self::set_enabled`].
+    pub fn run_partial(
+        &self,
+        reason: ShutdownReason,
+        budget: Option<Duration>,
+        filter: &TagFilter,
+    ) -> ShutdownReport {
+        let deadline = budget.map(|b| Instant::now() + b);
+        let blackboard = ShutdownBlackboard::new();
+        let (mut hooks, order, observers, escalation, pause) = {
+            let mut state = self.state.lock();
+            if !state.enabled {
+                return ShutdownReport::default();
+            }
+            let (matching, rest): (Vec<_>, Vec<_>) = core::mem::take(&mut state.hooks)
+                .into_iter()
+                .partition(|h| filter.matches(&h.config));
+            state.hooks = rest;
+            #[cfg(feature = "testing")]
+            let matching = inject_faults(matching, state.chaos.as_ref());
+            let observers = state.observers.clone();
+            (
+                matching,
+                state.order,
+                observers,
+                state.escalation,
+                state.pause,
+            )
+        };
+        sort(&mut hooks, order, |h| h.config.get_priority());
+        let runs = self.runs();
+        let results = hooks
+            .into_iter()
+            .enumerate()
+            .map(|(i, hook)| {
+                if i > 0 {
+                    pause_between(pause, deadline);
+                }
+                execute(
+                    hook,
+                    reason,
+                    deadline,
+                    &observers,
+                    escalation,
+                    &blackboard,
+                    &runs,
+                )
+            })
+            .collect();
+        ShutdownReport {
+            results,
+            exit: None,
+        }
+    }
+
+    /// Like [`Self::run`] but only executes the hooks that match `filter`, e.g.
+    /// only `"disk"` hooks in a fast-restart path. The filter from the environment
+    /// applies additionally. Hooks that don't match are dropped without being
+    /// executed and don't show up in the report.
+    pub fn run_filtered(
+        &self,
+        reason: ShutdownReason,
+        budget: Option<Duration>,
+        filter: &TagFilter,
+    ) -> ShutdownReport {
+        self.run_with(reason, budget, filter, None)
+    }
+
+    /// Runs the hooks because the process ends with `decision`, see
+    /// [`crate::exit`]. The decision ends up in [`ShutdownReport::exit`].
+    pub(crate) fn run_exit(&self, decision: ExitDecision) -> ShutdownReport {
+        self.run_with(decision.reason, None, &TagFilter::new(), Some(decision))
+    }
+
+    fn run_with(
+        &self,
+        reason: ShutdownReason,
+        budget: Option<Duration>,
+        filter: &TagFilter,
+        exit: Option<ExitDecision>,
+    ) -> ShutdownReport {
+        let env_filter = TagFilter::from_env();
+        let deadline = budget.map(|b| Instant::now() + b);
+        let blackboard = ShutdownBlackboard::new();
+        // don't hold the lock while hooks run, they may register new hooks
+        let (mut hooks, order, observers, threshold, escalation, runner, children, pause) = {
+            let mut state = self.state.lock();
+            if !state.enabled {
+                return ShutdownReport::default();
+            }
+            if state.running && RUNS.with(|r| r.borrow().contains(&self.id())) {
+                drop(state);
+                diagnostics::emit(
+                    Level::Error,
+                    format_args!(
+                        "a hook started the shutdown sequence of its own registry again ({:?}), ignored",
+                        reason
+                    ),
+                );
+                return ShutdownReport::default();
+            }
+            // only the run that set `running` may clear it again
+            state = self.finished.wait_while(state, |s| s.running);
+            state.triggered = Some((reason, deadline));
+            state.running = true;
+            state.blackboard = Some(blackboard.clone());
+            let hooks = core::mem::take(&mut state.hooks);
+            #[cfg(feature = "testing")]
+            let hooks = inject_faults(hooks, state.chaos.as_ref());
+            let hooks = if state.isolate_panics {
+                hooks.into_iter().map(isolate_panics).collect()
+            } else {
+                hooks
+            };
+            let observers = state.observers.clone();
+            let threshold = state.best_effort_threshold;
+            let runner = state.runner_stack_size;
+            let children = core::mem::take(&mut state.children);
+            (
+                hooks,
+                state.order,
+                observers,
+                threshold,
+                state.escalation,
+                runner,
+                children,
+                state.pause,
+            )
+        };
+        // releases blocked registrations and clears `pending`, even if a hook
+        // panics
+        let _running = Running(self);
+        let runs = self.runs();
+        let _runs = EnterRuns::new(runs.clone());
+        hooks.retain(|h| filter.matches(&h.config) && env_filter.matches(&h.config));
+        sort(&mut hooks, order, |h| h.config.get_priority());
+        let quarantine = self.state.lock().quarantine.clone();
+        let failures = quarantine
+            .as_ref()
+            .map(Quarantine::load)
+            .unwrap_or_default();
+        // conditions are resolved once and before the children run, so hooks
+        // can't toggle each other
+        let enabled: Vec<bool> = hooks
+            .iter_mut()
+            .map(|h| match &quarantine {
+                _ if !h.config.is_enabled() => false,
+                None => true,
+                Some(quarantine) => match quarantine.apply(&failures, h.config.clone()) {
+                    Some(config) => {
+                        h.config = config;
+                        true
+                    }
+                    None => false,
+                },
+            })
+            .collect();
+        // children in reverse order of creation, before the own hooks
+        let mut results: Vec<HookResult> = children
+            .iter()
+            .rev()
+            .filter_map(Weak::upgrade)
+            .flat_map(|child| {
+                let budget = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+                child.run_filtered(reason, budget, filter).results
+            })
+            .collect();
+        self.state.lock().pending = hooks.iter().map(|h| h.config.name().to_string()).collect();
+        let execute_all = || {
+            hooks
+                .into_iter()
+                .zip(enabled)
+                .enumerate()
+                .map(|(i, (hook, enabled))| {
+                    if i > 0 {
+                        pause_between(pause, deadline);
+                    }
+                    let result = if !enabled || is_time_short(&hook, deadline, threshold) {
+                        skip(&hook.config)
+                    } else {
+                        execute(
+                            hook,
+                            reason,
+                            deadline,
+                            &observers,
+                            escalation,
+                            &blackboard,
+                            &runs,
+                        )
+                    };
+                    self.state.lock().pending.remove(0);
+                    result
+                })
+                .collect()
+        };
+        let own_results: Vec<HookResult> = match runner.filter(|_| HAS_THREADS) {
+            Some(stack_size) => std::thread::scope(|s| {
+                std::thread::Builder::new()
+                    .name("shutdown-runner".to_string())
+                    .stack_size(stack_size)
+                    .spawn_scoped(s, execute_all)
+                    .expect("should spawn runner thread")
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            }),
+            None => execute_all(),
+        };
+        if let Some(quarantine) = quarantine {
+            quarantine.record(failures, &own_results);
+        }
+        results.extend(own_results);
+        let report = ShutdownReport { results, exit };
+        for observer in &observers {
+            observer.finished(&report);
+        }
+        report
+    }
+}
+
+/// Marks the end of a run when dropped.
+struct Running<'a>(&'a Registry);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock();
+        state.running = false;
+        // left over if a hook panicked
+        state.pending.clear();
+        #[cfg(feature = "async")]
+        if let Some(completion) = state.completion.take() {
+            completion.complete();
+        }
+        self.0.finished.notify_all();
+    }
+}
+
+/// Pauses between two hooks, see [`Registry::set_pause_between_hooks`].
+fn pause_between(pause: Option<Duration>, deadline: Option<Instant>) {
+    match pause {
+        None => {}
+        Some(pause) if pause.is_zero() => std::thread::yield_now(),
+        Some(pause) => {
+            let left = deadline.map_or(pause, |d| d.saturating_duration_since(Instant::now()));
+            std::thread::sleep(pause.min(left));
+        }
+    }
+}
+
+/// Brings hooks that are in registration order into execution order.
+fn sort<T>(hooks: &mut [T], order: ExecutionOrder, priority: impl Fn(&T) -> i32) {
+    match order {
+        ExecutionOrder::Lifo => hooks.reverse(),
+        ExecutionOrder::Fifo => {}
+        ExecutionOrder::Priority => {
+            hooks.reverse();
+            // stable: hooks with the same priority stay in LIFO order
+            hooks.sort_by_key(|h| core::cmp::Reverse(priority(h)));
+        }
+        ExecutionOrder::Shuffled(seed) => crate::shuffle::shuffle(hooks, seed),
+    }
+}
+
+/// Replaces the hooks with misbehaving versions according to `chaos`.
+#[cfg(feature = "testing")]
+fn inject_faults(hooks: Vec<Hook>, chaos: Option<&crate::Chaos>) -> Vec<Hook> {
+    use crate::Fault;
+    let chaos = match chaos {
+        Some(chaos) => chaos,
+        None => return hooks,
+    };
+    hooks
+        .into_iter()
+        .map(
+            |Hook {
+                 config,
+                 f,
+                 location,
+                 rehearsal,
+             }| {
+                let f: HookFn = match chaos.fault_for(config.name()) {
+                    None => f,
+                    Some(Fault::Delay(delay)) => Box::new(move |ctx| {
+                        std::thread::sleep(delay);
+                        f(ctx)
+                    }),
+                    Some(Fault::Panic) => Box::new(|ctx| {
+                        panic!("chaos: injected panic in hook '{}'", ctx.config().name())
+                    }),
+                    Some(Fault::Hang) => Box::new(|ctx| {
+                        while !ctx.is_cancelled() && ctx.remaining() != Some(Duration::from_secs(0))
+                        {
+                            std::thread::sleep(Duration::from_millis(10));
+                        }
+                        HookOutcome::TimedOut
+                    }),
+                };
+                Hook {
+                    config,
+                    f,
+                    location,
+                    rehearsal,
+                }
+            },
+        )
+        .collect()
+}
+
+/// Replaces the hook with a version whose panics become failures, see
+/// [`Registry::set_isolate_panics`].
+fn isolate_panics(mut hook: Hook) -> Hook {
+    let f = hook.f;
+    hook.f = Box::new(move |ctx| catch_panic(f, ctx));
+    hook
+}
+
+/// Calls the hook and turns a panic into [`HookOutcome::Failed`].
+#[cfg(panic = "unwind")]
+fn catch_panic(f: HookFn, ctx: &ShutdownContext) -> HookOutcome {
+    let outer = CATCHING.with(|c| c.replace(true));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(ctx)));
+    CATCHING.with(|c| c.set(outer));
+    let panic = match result {
+        Ok(outcome) => return outcome,
+        Err(panic) => panic,
+    };
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    HookOutcome::Failed(format!("the hook panicked: {}", message).into())
+}
+
+/// Calls the hook. Panics abort the process, they can't be caught.
+#[cfg(not(panic = "unwind"))]
+fn catch_panic(f: HookFn, ctx: &ShutdownContext) -> HookOutcome {
+    f(ctx)
+}
+
+/// Whether the hook should be skipped because less than `threshold` is left until
+/// the `deadline`.
+fn is_time_short(hook: &Hook, deadline: Option<Instant>, threshold: Duration) -> bool {
+    hook.config.get_importance() == Importance::BestEffort
+        && deadline.is_some_and(|d| {
+            let remaining = d.saturating_duration_since(Instant::now());
+            remaining.is_zero() || remaining < threshold
+        })
+}
+
+/// Result of a hook that is not executed.
+fn skip(config: &HookConfig) -> HookResult {
+    HookResult {
+        name: config.name().to_string(),
+        outcome: HookOutcome::Skipped,
+        duration: Duration::from_secs(0),
+        env: config.env_snapshot().cloned(),
+    }
+}
+
+/// Executes a single hook and notifies the observers.
+///
+/// ## Parameters
+/// * `escalation` policy of the registry, used if the hook has none
+/// * `runs` registries whose run the hook is part of, see [`Registry::runs`]
+fn execute(
+    hook: Hook,
+    reason: ShutdownReason,
+    deadline: Option<Instant>,
+    observers: &[Arc<dyn HookObserver>],
+    escalation: Option<EscalationPolicy>,
+    blackboard: &ShutdownBlackboard,
+    runs: &[usize],
+) -> HookResult {
+    let Hook { mut config, f, .. } = hook;
+    if let Some(timeout) = crate::env::timeout_override(config.name()) {
+        config = config.timeout(timeout);
+    }
+    let info = HookInfo::from(&config);
+    for observer in observers {
+        observer.before(&info);
+    }
+    let begin = Instant::now();
+    let escalation = config
+        .get_escalation()
+        .or(escalation)
+        .filter(|_| HAS_THREADS);
+    let remaining = deadline.map(|d| d.saturating_duration_since(begin));
+    let timeout = match (config.get_timeout(), remaining) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    let outcome = match (escalation, timeout) {
+        (Some(policy), Some(timeout)) => {
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let worker_config = config.clone();
+            let worker_info = info.clone();
+            let worker_observers = observers.to_vec();
+            let worker_cancelled = cancelled.clone();
+            let worker_blackboard = blackboard.clone();
+            let worker_runs = runs.to_vec();
+            crate::escalation::run_with_escalation(
+                policy,
+                timeout,
+                &info,
+                observers,
+                &cancelled,
+                move || {
+                    let ctx = ShutdownContext::new(
+                        reason,
+                        deadline,
+                        &worker_config,
+                        &worker_info,
+                        &worker_observers,
+                        &worker_cancelled,
+                        &worker_blackboard,
+                    );
+                    call(f, &ctx, &worker_runs)
+                },
+            )
+        }
+        _ => {
+            let cancelled = AtomicBool::new(false);
+            let ctx = ShutdownContext::new(
+                reason, deadline, &config, &info, observers, &cancelled, blackboard,
+            );
+            call(f, &ctx, runs)
+        }
+    };
+    let duration = begin.elapsed();
+    for observer in observers {
+        observer.after(&info, &outcome);
+    }
+    HookResult {
+        name: config.name().to_string(),
+        outcome,
+        duration,
+        env: config.env_snapshot().cloned(),
+    }
+}
+
+/// Calls the hook, on a dedicated thread if it has a niceness.
+fn call(f: HookFn, ctx: &ShutdownContext, runs: &[usize]) -> HookOutcome {
+    match ctx.config().get_niceness() {
+        Some(niceness) => crate::priority::run_with_niceness(niceness, || in_hook(f, ctx, runs)),
+        None => in_hook(f, ctx, runs),
+    }
+}
+
+/// Whether the current thread executes a hook.
+pub(crate) fn is_in_hook() -> bool {
+    IN_HOOK.with(Cell::get)
+}
+
+/// Whether a panic on the current thread is caught by
+/// [`Registry::set_isolate_panics`], so it doesn't end the process.
+pub(crate) fn is_catching_panics() -> bool {
+    CATCHING.with(Cell::get)
+}
+
+/// Calls the hook and marks the current thread as executing a hook of `runs`
+/// meanwhile.
+fn in_hook(f: HookFn, ctx: &ShutdownContext, runs: &[usize]) -> HookOutcome {
+    /// Restores the outer value, also if the hook panics.
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            IN_HOOK.with(|h| h.set(self.0));
+        }
+    }
+
+    let _restore = Restore(IN_HOOK.with(|h| h.replace(true)));
+    let _runs = EnterRuns::new(runs.to_vec());
+    f(ctx)
+}
+
+/// Sets [`RUNS`] of the current thread and restores the outer value when
+/// dropped, also if a hook panics.
+struct EnterRuns(Vec<usize>);
+
+impl EnterRuns {
+    fn new(runs: Vec<usize>) -> Self {
+        Self(RUNS.with(|r| r.replace(runs)))
+    }
+}
+
+impl Drop for EnterRuns {
+    fn drop(&mut self) {
+        RUNS.with(|r| *r.borrow_mut() = core::mem::take(&mut self.0));
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_context_is_passed() {
+        let registry = Registry::new();
+        let seen = Arc::new(Mutex::new(None));
+        let seen_c = seen.clone();
+        registry.register_with_context(
+            HookConfig::new("flush_db").timeout(Duration::from_secs(5)),
+            move |ctx| {
+                *seen_c.lock().unwrap() = Some((
+                    ctx.reason(),
+                    ctx.config().name().to_string(),
+                    ctx.remaining().unwrap(),
+                ));
+            },
+        );
+        registry.run(ShutdownReason::Requested, Some(Duration::from_secs(1)));
+
+        let (reason, name, remaining) = seen.lock().unwrap().take().unwrap();
+        assert_eq!(reason, ShutdownReason::Requested);
+        assert_eq!(name, "flush_db");
+        // global budget is shorter than the hook's timeout
+        assert!(remaining <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_reentrant_run_is_ignored() {
+        let registry = Arc::new(Registry::new());
+        let inner_report = Arc::new(Mutex::new(None));
+        registry.register(HookConfig::new("first"), || {});
+        let registry_c = registry.clone();
+        let inner_report_c = inner_report.clone();
+        registry.register(HookConfig::new("exits"), move || {
+            let report = registry_c.run(ShutdownReason::Exit, None);
+            *inner_report_c.lock().unwrap() = Some(report);
+        });
+
+        let report = registry.run(ShutdownReason::Requested, None);
+        let names = report.results.iter().map(|r| &r.name).collect::<Vec<_>>();
+        assert_eq!(names, ["exits", "first"]);
+        assert!(report.is_success());
+        let inner_report = inner_report.lock().unwrap().take().unwrap();
+        assert!(inner_report.results.is_empty());
+        // the registry is usable afterwards
+        assert!(!registry.state.lock().running);
+    }
+
+    #[test]
+    fn test_hook_waits_for_run_of_other_registry() {
+        let other = Arc::new(Registry::new());
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let done = Arc::new(AtomicBool::new(false));
+        let done_c = done.clone();
+        other.register(HookConfig::new("slow"), move || {
+            started_tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            done_c.store(true, Ordering::SeqCst);
+        });
+        let other_c = other.clone();
+        let first = std::thread::spawn(move || other_c.run(ShutdownReason::Exit, None));
+        started_rx.recv().unwrap();
+
+        // not a nested run of `other`, although a hook calls it
+        let registry = Registry::new();
+        let other_c = other.clone();
+        let done_c = done.clone();
+        registry.register(HookConfig::new("runs other"), move || {
+            other_c.run(ShutdownReason::Requested, None);
+            assert!(done_c.load(Ordering::SeqCst));
+        });
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        assert_eq!(first.join().unwrap().results.len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_run_waits_for_first_run() {
+        let registry = Arc::new(Registry::new());
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let done = Arc::new(AtomicBool::new(false));
+        let done_c = done.clone();
+        registry.register(HookConfig::new("slow"), move || {
+            started_tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            done_c.store(true, Ordering::SeqCst);
+        });
+
+        let registry_c = registry.clone();
+        let first = std::thread::spawn(move || registry_c.run(ShutdownReason::Exit, None));
+        started_rx.recv().unwrap();
+        let second = registry.run(ShutdownReason::Requested, None);
+        assert!(done.load(Ordering::SeqCst));
+        assert!(second.results.is_empty());
+        assert_eq!(first.join().unwrap().results.len(), 1);
+        assert!(!registry.state.lock().running);
+    }
+
+    #[test]
+    fn test_origin_defaults_to_call_site() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("flush"), || {});
+        registry.register(crate::hook_config!("close"), || {});
+        let origins = registry
+            .hooks()
+            .into_iter()
+            .map(|h| h.origin.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(origins, [file!(), module_path!()]);
+    }
+
+    #[test]
+    fn test_external_hooks_are_recorded() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("before"), || {});
+        registry.set_external_hooks(ExternalHooks::Record);
+        registry.register(HookConfig::new("close_db").external(), || {
+            panic!("touches the database")
+        });
+        registry.register(HookConfig::new("local"), || {});
+
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(report.is_success());
+        let skipped = report.skipped().map(|r| &r.name).collect::<Vec<_>>();
+        assert_eq!(skipped, ["close_db"]);
+        let recorded = registry.recorded_external_hooks();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].name, "close_db");
+    }
+
+    #[test]
+    fn test_rehearse() {
+        let registry = Registry::new();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls_c = calls.clone();
+        registry.register_rehearsable(HookConfig::new("flush"), move |ctx| {
+            calls_c.lock().unwrap().push(ctx.is_rehearsal());
+            Ok::<_, HookError>(())
+        });
+        registry.register_rehearsable(HookConfig::new("upload"), |ctx| {
+            if ctx.is_rehearsal() {
+                Err("no network")
+            } else {
+                Ok(())
+            }
+        });
+        registry.register(HookConfig::new("close"), || {});
+
+        for _ in 0..2 {
+            let report = registry.rehearse(None);
+            let names = report.results.iter().map(|r| &r.name).collect::<Vec<_>>();
+            assert_eq!(names, ["close", "upload", "flush"]);
+            assert!(matches!(report.results[0].outcome, HookOutcome::Skipped));
+            assert_eq!(report.failures().count(), 1);
+        }
+        assert_eq!(registry.len(), 3);
+        assert_eq!(*calls.lock().unwrap(), [true, true]);
+
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        assert_eq!(*calls.lock().unwrap(), [true, true, false]);
+    }
+
+    #[test]
+    fn test_env_is_captured_at_registration() {
+        crate::env::tests::set_var("SHUTDOWN_TEST_UPLOAD_URL", "https://old");
+        let registry = Registry::new();
+        registry.register_fallible(
+            HookConfig::new("upload").capture_env("SHUTDOWN_TEST_UPLOAD_URL"),
+            |_ctx| Err("unreachable"),
+        );
+        crate::env::tests::set_var("SHUTDOWN_TEST_UPLOAD_URL", "https://new");
+        let err = registry
+            .run(ShutdownReason::Exit, None)
+            .into_result()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("SHUTDOWN_TEST_UPLOAD_URL=https://old"));
+    }
+
+    #[test]
+    fn test_enabled_if() {
+        use std::sync::atomic::Ordering;
+        static TELEMETRY: AtomicBool = AtomicBool::new(true);
+        let registry = Registry::new();
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let ran_c = ran.clone();
+        registry.register(
+            HookConfig::new("telemetry").enabled_if(|| TELEMETRY.load(Ordering::SeqCst)),
+            move || ran_c.lock().unwrap().push("telemetry"),
+        );
+        // resolved before the first hook runs
+        registry.register(HookConfig::new("disable telemetry"), || {
+            TELEMETRY.store(false, Ordering::SeqCst)
+        });
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(*ran.lock().unwrap(), ["telemetry"]);
+        assert_eq!(report.skipped().count(), 0);
+
+        let ran_c = ran.clone();
+        let registry = Registry::new();
+        registry.register(
+            HookConfig::new("telemetry").enabled_if(|| TELEMETRY.load(Ordering::SeqCst)),
+            move || ran_c.lock().unwrap().push("again"),
+        );
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(report.skipped().count(), 1);
+        assert_eq!(ran.lock().unwrap().len(), 1);
+
+        // also hooks of children run after the conditions were resolved
+        let ran_c = ran.clone();
+        let registry = Registry::new();
+        registry.register(
+            HookConfig::new("telemetry").enabled_if(|| !TELEMETRY.load(Ordering::SeqCst)),
+            move || ran_c.lock().unwrap().push("child toggled"),
+        );
+        let child = registry.child();
+        child.register(HookConfig::new("enable telemetry"), || {
+            TELEMETRY.store(true, Ordering::SeqCst)
+        });
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(report.skipped().count(), 0);
+        assert_eq!(*ran.lock().unwrap(), ["telemetry", "child toggled"]);
+    }
+
+    #[test]
+    fn test_isolate_panics() {
+        let registry = Registry::new();
+        registry.set_isolate_panics(true);
+        registry.register(HookConfig::new("after"), || {});
+        registry.register(HookConfig::new("panics"), || panic!("disk on fire"));
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(
+            report.results[0].outcome.to_string(),
+            "failed: the hook panicked: disk on fire"
+        );
+        assert!(report.results[1].outcome.is_success());
+        assert!(!is_catching_panics());
+    }
+
+    #[test]
+    fn test_pause_between_hooks() {
+        let registry = Registry::new();
+        registry.set_pause_between_hooks(Some(Duration::from_millis(50)));
+        for name in ["a", "b", "c"].iter().copied() {
+            registry.register(HookConfig::new(name), || {});
+        }
+        let begin = Instant::now();
+        registry.run(ShutdownReason::Exit, None);
+        assert!(begin.elapsed() >= Duration::from_millis(100));
+
+        // the pause doesn't exceed the budget
+        let registry = Registry::new();
+        registry.set_pause_between_hooks(Some(Duration::from_secs(10)));
+        registry.register(HookConfig::new("a"), || {});
+        registry.register(HookConfig::new("b"), || {});
+        let begin = Instant::now();
+        registry.run(ShutdownReason::Exit, Some(Duration::from_millis(50)));
+        assert!(begin.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_reverse_order() {
+        let registry = Registry::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..3 {
+            let order = order.clone();
+            registry.register(HookConfig::new("push"), move || {
+                order.lock().unwrap().push(i)
+            });
+        }
+        registry.run(ShutdownReason::Exit, None);
+        assert_eq!(*order.lock().unwrap(), vec![2, 1, 0]);
+        assert!(registry.is_empty());
+    }
+
+    /// Registers hooks with the given priorities and returns the priorities in
+    /// execution order.
+    fn run_with_order(order: ExecutionOrder, priorities: &[i32]) -> Vec<String> {
+        let registry = Registry::new();
+        registry.set_execution_order(order);
+        for (i, &prio) in priorities.iter().enumerate() {
+            registry.register(
+                HookConfig::new(format!("{}:{}", i, prio)).priority(prio),
+                || {},
+            );
+        }
+        let report = registry.run(ShutdownReason::Exit, None);
+        report.results.into_iter().map(|r| r.name).collect()
+    }
+
+    #[test]
+    fn test_execution_order() {
+        assert_eq!(
+            run_with_order(ExecutionOrder::Lifo, &[0, 0, 0]),
+            ["2:0", "1:0", "0:0"]
+        );
+        assert_eq!(
+            run_with_order(ExecutionOrder::Fifo, &[0, 0, 0]),
+            ["0:0", "1:0", "2:0"]
+        );
+        assert_eq!(
+            run_with_order(ExecutionOrder::Priority, &[1, 5, 1, -3]),
+            ["1:5", "2:1", "0:1", "3:-3"]
+        );
+    }
+
+    #[test]
+    fn test_observer() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl HookObserver for Recorder {
+            fn before(&self, info: &HookInfo) {
+                self.0.lock().unwrap().push(format!("before {}", info.name));
+            }
+
+            fn after(&self, info: &HookInfo, outcome: &HookOutcome) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("after {}: {}", info.name, outcome));
+            }
+        }
+
+        let registry = Registry::new();
+        let recorder = Arc::new(Recorder::default());
+        registry.add_observer(recorder.clone());
+        registry.register(HookConfig::new("a"), || {});
+        registry.register_fallible(HookConfig::new("b"), |_ctx| Err("nope"));
+        registry.run(ShutdownReason::Exit, None);
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "before b",
+                "after b: failed: nope",
+                "before a",
+                "after a: completed"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_filtered() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("fsync").tag("disk"), || {});
+        registry.register(HookConfig::new("metrics").tag("telemetry"), || {
+            panic!("must not run")
+        });
+        let report = registry.run_filtered(
+            ShutdownReason::Exit,
+            None,
+            &TagFilter::new().exclude("telemetry"),
+        );
+        let names: Vec<_> = report.results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["fsync"]);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_best_effort_hooks_are_skipped() {
+        let registry = Registry::new();
+        registry.set_best_effort_threshold(Duration::from_millis(500));
+        registry.register(
+            HookConfig::new("fsync").importance(Importance::Critical),
+            || {},
+        );
+        registry.register(
+            HookConfig::new("telemetry").importance(Importance::BestEffort),
+            || panic!("must not run"),
+        );
+        registry.register(HookConfig::new("slow"), || {
+            std::thread::sleep(Duration::from_millis(600))
+        });
+        let report = registry.run(ShutdownReason::Exit, Some(Duration::from_secs(1)));
+
+        assert!(report.is_success());
+        let skipped: Vec<_> = report.skipped().map(|r| r.name.as_str()).collect();
+        assert_eq!(skipped, ["telemetry"]);
+        assert!(report.results[2].outcome.is_success());
+    }
+
+    #[test]
+    fn test_escalation() {
+        use crate::EscalationStage::{self, *};
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<EscalationStage>>);
+
+        impl HookObserver for Recorder {
+            fn escalated(&self, _info: &HookInfo, stage: EscalationStage) {
+                self.0.lock().unwrap().push(stage);
+            }
+        }
+
+        let registry = Registry::new();
+        let recorder = Arc::new(Recorder::default());
+        registry.add_observer(recorder.clone());
+        registry.set_escalation_policy(EscalationPolicy::new());
+        // stops once it is asked to
+        registry.register_with_context(
+            HookConfig::new("cooperative").timeout(Duration::from_millis(20)),
+            |ctx| {
+                while !ctx.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            },
+        );
+        // ignores the cancellation
+        registry.register(
+            HookConfig::new("stubborn")
+                .timeout(Duration::from_millis(20))
+                .escalation(EscalationPolicy::new().cancel_at(100).detach_at(150)),
+            || std::thread::sleep(Duration::from_secs(60)),
+        );
+        let begin = Instant::now();
+        let report = registry.run(ShutdownReason::Exit, None);
+
+        assert!(begin.elapsed() < Duration::from_secs(1));
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+        assert!(report.results[1].outcome.is_success());
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [Warn, Cancel, Detach, Warn, Cancel]
+        );
+    }
+
+    #[test]
+    fn test_runner_thread() {
+        let registry = Registry::new();
+        registry.set_runner_thread(16 * 1024 * 1024);
+        let thread_name = Arc::new(Mutex::new(None));
+        let thread_name_c = thread_name.clone();
+        registry.register(HookConfig::new("stack heavy"), move || {
+            // would overflow the default stack of test threads
+            let buf = [1_u8; 8 * 1024 * 1024];
+            assert_eq!(std::hint::black_box(&buf)[0], 1);
+            *thread_name_c.lock().unwrap() = std::thread::current().name().map(String::from);
+        });
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        assert_eq!(
+            thread_name.lock().unwrap().as_deref(),
+            Some("shutdown-runner")
+        );
+    }
+
+    #[test]
+    fn test_pending_hooks() {
+        let registry = Arc::new(Registry::new());
+        let pending = Arc::new(Mutex::new(Vec::new()));
+        for name in ["a", "b", "c"] {
+            let registry_c = registry.clone();
+            let pending = pending.clone();
+            registry.register(HookConfig::new(name), move || {
+                if name == "b" {
+                    *pending.lock().unwrap() = registry_c.pending_hooks();
+                }
+            });
+        }
+        assert!(registry.pending_hooks().is_empty());
+        registry.run(ShutdownReason::Exit, None);
+        assert_eq!(*pending.lock().unwrap(), ["b", "a"]);
+        assert!(registry.pending_hooks().is_empty());
+
+        // also if a hook panics out of the run
+        let registry = Registry::new();
+        registry.register(HookConfig::new("after"), || {});
+        registry.register(HookConfig::new("panics"), || panic!("disk on fire"));
+        let run = std::panic::catch_unwind(|| registry.run(ShutdownReason::Exit, None));
+        assert!(run.is_err());
+        assert!(registry.pending_hooks().is_empty());
+    }
+
+    #[test]
+    fn test_disarmed() {
+        let registry = Registry::new();
+        let ran = Arc::new(Mutex::new(false));
+        let ran_c = ran.clone();
+        registry.register(HookConfig::new("release lock"), move || {
+            *ran_c.lock().unwrap() = true
+        });
+        registry.set_enabled(false);
+        assert!(registry.run(ShutdownReason::Exit, None).results.is_empty());
+        assert!(!*ran.lock().unwrap());
+        assert!(!registry.is_triggered());
+
+        registry.set_enabled(true);
+        assert_eq!(registry.run(ShutdownReason::Exit, None).results.len(), 1);
+        assert!(*ran.lock().unwrap());
+    }
+
+    #[test]
+    fn test_run_partial() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("close listener").tag("pre-exec"), || {});
+        registry.register(HookConfig::new("flush db"), || {});
+        let report = registry.run_partial(
+            ShutdownReason::Requested,
+            None,
+            &TagFilter::new().include("pre-exec"),
+        );
+        assert_eq!(report.results[0].name, "close listener");
+        assert!(!registry.is_triggered());
+        assert_eq!(registry.hooks()[0].name, "flush db");
+    }
+
+    #[test]
+    fn test_niceness() {
+        let registry = Registry::new();
+        let thread_name = Arc::new(Mutex::new(None));
+        let thread_name_c = thread_name.clone();
+        registry.register(HookConfig::new("nice").niceness(10), move || {
+            *thread_name_c.lock().unwrap() = std::thread::current().name().map(String::from);
+        });
+        assert!(registry.run(ShutdownReason::Exit, None).is_success());
+        if cfg!(all(target_os = "linux", feature = "thread-priority")) {
+            assert_eq!(
+                thread_name.lock().unwrap().as_deref(),
+                Some("shutdown-hook")
+            );
+        }
+    }
+
+    #[test]
+    fn test_env_timeout_override() {
+        crate::env::tests::set_var(&crate::timeout_env_var("env_override_test"), "42s");
+        let registry = Registry::new();
+        let timeout = Arc::new(Mutex::new(None));
+        let timeout_c = timeout.clone();
+        registry.register_with_context(
+            HookConfig::new("env_override_test").timeout(Duration::from_secs(1)),
+            move |ctx| *timeout_c.lock().unwrap() = ctx.config().get_timeout(),
+        );
+        registry.run(ShutdownReason::Exit, None);
+        assert_eq!(*timeout.lock().unwrap(), Some(Duration::from_secs(42)));
+    }
+
+    #[test]
+    fn test_report() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("ok"), || {});
+        registry.register_fallible(HookConfig::new("broken"), |_ctx| Err("disk full"));
+        let report = registry.run(ShutdownReason::Exit, None);
+
+        assert!(!report.is_success());
+        let failures: Vec<_> = report.failures().map(|r| r.name.as_str()).collect();
+        assert_eq!(failures, vec!["broken"]);
+        assert_eq!(report.results[0].outcome.to_string(), "failed: disk full");
+        assert!(report.results[1].outcome.is_success());
+    }
+
+    #[test]
+    fn test_late_registration_runs_immediately() {
+        let registry = Registry::new();
+        registry.run(ShutdownReason::Exit, None);
+        let ran = Arc::new(Mutex::new(false));
+        let ran_c = ran.clone();
+        registry.register(HookConfig::new("late"), move || {
+            *ran_c.lock().unwrap() = true
+        });
+        assert!(*ran.lock().unwrap());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_late_registration_silently_dropped() {
+        let registry = Registry::new();
+        registry.set_late_registration_policy(LateRegistrationPolicy::SilentlyDrop);
+        registry.run(ShutdownReason::Exit, None);
+        registry.register(HookConfig::new("late"), || panic!("must not run"));
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_poisoned_lock_is_recovered() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("before"), || {});
+        let _ = std::panic::catch_unwind(|| {
+            let _state = registry.state.lock();
+            panic!("registering thread panics while holding the lock");
+        });
+        registry.register(HookConfig::new("after"), || {});
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(report.results.len(), 2);
+        assert!(report.is_success());
+    }
+
+    #[test]
+    fn test_panicking_drop_of_late_hook_doesnt_poison() {
+        struct PanicOnDrop;
+        impl Drop for PanicOnDrop {
+            fn drop(&mut self) {
+                panic!("drop panics")
+            }
+        }
+
+        let registry = Registry::new();
+        registry.set_late_registration_policy(LateRegistrationPolicy::SilentlyDrop);
+        registry.run(ShutdownReason::Exit, None);
+        let captured = PanicOnDrop;
+        let _ = std::panic::catch_unwind(|| {
+            registry.register(HookConfig::new("late"), move || drop(captured));
+        });
+        assert!(registry.is_triggered());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "registered after the shutdown started")]
+    fn test_late_registration_panics_in_strict_mode() {
+        let registry = Registry::new();
+        registry.set_late_registration_policy(LateRegistrationPolicy::Panic);
+        registry.run(ShutdownReason::Exit, None);
+        registry.register(HookConfig::new("late"), || {});
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_duplicate_registration_panics_in_strict_mode() {
+        let registry = Registry::new();
+        registry.set_duplicate_registration_policy(DuplicateRegistrationPolicy::Panic);
+        // same name, but different locations
+        registry.register(HookConfig::new("flush"), || {});
+        registry.register(HookConfig::new("flush"), || {});
+        let armed_twice = std::panic::catch_unwind(|| {
+            for _ in 0..2 {
+                registry.register(HookConfig::new("flush"), || {});
+            }
+        });
+        let panic = armed_twice.unwrap_err();
+        assert!(panic
+            .downcast_ref::<String>()
+            .unwrap()
+            .contains(&format!("'flush' was registered twice at {}", file!())));
+        assert_eq!(registry.len(), 3);
+    }
+
+    #[test]
+    fn test_barrier_blocks_registrations_during_run() {
+        let registry = Arc::new(Registry::new());
+        registry.set_registration_barrier(RegistrationBarrier::Block);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (log_c, registry_c) = (log.clone(), registry.clone());
+        registry.register(HookConfig::new("slow"), move || {
+            started_tx.send(()).unwrap();
+            // hooks themselves are not blocked
+            let log_n = log_c.clone();
+            registry_c.register(HookConfig::new("nested"), move || {
+                log_n.lock().unwrap().push("nested")
+            });
+            std::thread::sleep(Duration::from_millis(100));
+            log_c.lock().unwrap().push("slow");
+        });
+
+        let (log_c, registry_c) = (log.clone(), registry.clone());
+        let registering = std::thread::spawn(move || {
+            started_rx.recv().unwrap();
+            registry_c.register(HookConfig::new("late"), move || {
+                log_c.lock().unwrap().push("late")
+            });
+        });
+        registry.run(ShutdownReason::Exit, None);
+        registering.join().unwrap();
+        assert_eq!(*log.lock().unwrap(), ["nested", "slow", "late"]);
+    }
+
+    #[test]
+    fn test_shuffled_order() {
+        let order_of = |seed| {
+            let registry = Registry::new();
+            registry.set_execution_order(ExecutionOrder::Shuffled(seed));
+            let order = Arc::new(Mutex::new(Vec::new()));
+            for i in 0..8 {
+                let order = order.clone();
+                registry.register(HookConfig::new(i.to_string()), move || {
+                    order.lock().unwrap().push(i)
+                });
+            }
+            registry.run(ShutdownReason::Exit, None);
+            let order = order.lock().unwrap().clone();
+            order
+        };
+        let order = order_of(7);
+        assert_eq!(order, order_of(7));
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+        assert!((0..10).any(|seed| order_of(seed) != order));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_chaos_injects_faults() {
+        use crate::{Chaos, Fault};
+        let chaos = Chaos::new(1).probability(100).panics(false);
+        let name = |fault: fn(Fault) -> bool| {
+            (0..)
+                .map(|i| format!("hook-{}", i))
+                .find(|n| chaos.fault_for(n).is_some_and(fault))
+                .unwrap()
+        };
+        let hanging = name(|f| f == Fault::Hang);
+        let delayed = name(|f| matches!(f, Fault::Delay(_)));
+        let registry = Registry::new();
+        registry.set_chaos(chaos);
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        for name in [&hanging, &delayed] {
+            let ran_c = ran.clone();
+            let hook_name = name.clone();
+            registry.register(
+                HookConfig::new(name).timeout(Duration::from_millis(50)),
+                move || ran_c.lock().unwrap().push(hook_name),
+            );
+        }
+
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(matches!(report.results[0].outcome, HookOutcome::Completed));
+        assert!(matches!(report.results[1].outcome, HookOutcome::TimedOut));
+        assert!(report.results[1].duration >= Duration::from_millis(50));
+        assert_eq!(*ran.lock().unwrap(), [delayed]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_hook_is_cancelled_at_timeout() {
+        let registry = Registry::new();
+        let finished_gracefully = Arc::new(Mutex::new(false));
+        let finished_gracefully_c = finished_gracefully.clone();
+        registry.register_async(
+            HookConfig::new("slow").timeout(Duration::from_millis(50)),
+            move |token| async move {
+                // would never complete without the cancellation
+                token.cancelled().await;
+                *finished_gracefully_c.lock().unwrap() = true;
+            },
+        );
+        registry.run(ShutdownReason::Exit, None);
+        assert!(*finished_gracefully.lock().unwrap());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_hook_is_dropped_after_grace() {
+        let registry = Registry::new();
+        registry.register_async(
+            HookConfig::new("stubborn")
+                .timeout(Duration::from_millis(10))
+                .cancel_grace(Duration::from_millis(10)),
+            |_token| core::future::pending::<()>(),
+        );
+        let begin = Instant::now();
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(begin.elapsed() < Duration::from_secs(1));
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+    }
+}
@@ -0,0 +1,276 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Outcome of a shutdown sequence.
+
+use crate::{EnvSnapshot, ExitDecision};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Error of a fallible hook. Errors of `anyhow` and `eyre` convert into it
+/// directly and keep their chain of causes.
+pub type HookError = Box<dyn Error + Send + Sync>;
+
+/// How the execution of a single hook ended.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HookOutcome {
+    /// The hook finished successfully.
+    Completed,
+    /// The hook returned an error.
+    Failed(HookError),
+    /// The hook didn't finish in time and was abandoned.
+    TimedOut,
+    /// The hook was not executed because time was short and it is only
+    /// [`crate::Importance::BestEffort`], because its condition didn't hold,
+    /// see [`crate::HookConfig::enabled_if`], or because it was only recorded,
+    /// see [`crate::ExternalHooks::Record`].
+    Skipped,
+}
+
+impl HookOutcome {
+    /// Whether the hook finished successfully. Skipped hooks count as success, as
+    /// they were skipped on purpose.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Completed | Self::Skipped)
+    }
+
+    /// The error of a [`Self::Failed`] hook.
+    pub fn error(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
+        match self {
+            Self::Failed(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Copy of the outcome. Errors are replaced by their message and causes, as
+    /// they can't be cloned.
+    pub(crate) fn to_detached(&self) -> Self {
+        match self {
+            Self::Completed => Self::Completed,
+            Self::Failed(err) => Self::Failed(ErrorChain(err.as_ref()).to_string().into()),
+            Self::TimedOut => Self::TimedOut,
+            Self::Skipped => Self::Skipped,
+        }
+    }
+}
+
+/// Displays an error together with its chain of causes, e.g.
+/// `flushing cache: disk full`.
+struct ErrorChain<'a>(&'a (dyn Error + 'static));
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(err) = source {
+            write!(f, ": {}", err)?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+/// Errors are serialized as their `Display` representation, followed by their
+/// causes.
+#[cfg(feature = "serde")]
+impl serde::Serialize for HookOutcome {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Completed => serializer.serialize_unit_variant("HookOutcome", 0, "Completed"),
+            Self::Failed(err) => {
+                let chain = ErrorChain(err.as_ref()).to_string();
+                serializer.serialize_newtype_variant("HookOutcome", 1, "Failed", &chain)
+            }
+            Self::TimedOut => serializer.serialize_unit_variant("HookOutcome", 2, "TimedOut"),
+            Self::Skipped => serializer.serialize_unit_variant("HookOutcome", 3, "Skipped"),
+        }
+    }
+}
+
+impl fmt::Display for HookOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Completed => write!(f, "completed"),
+            Self::Failed(err) => write!(f, "failed: {}", ErrorChain(err.as_ref())),
+            Self::TimedOut => write!(f, "timed out"),
+            Self::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Result of a single hook inside a [`ShutdownReport`].
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HookResult {
+    /// Name of the hook, see [`crate::HookConfig::name`].
+    pub name: String,
+    /// How the execution ended.
+    pub outcome: HookOutcome,
+    /// How long the execution took.
+    pub duration: Duration,
+    /// Environment at the registration, if configured with
+    /// [`crate::HookConfig::capture_env`].
+    pub env: Option<EnvSnapshot>,
+}
+
+/// Returned by [`crate::Registry::run`]. Contains one [`HookResult`] per executed
+/// hook in execution order.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ShutdownReport {
+    /// Results in execution order.
+    pub results: Vec<HookResult>,
+    /// How the process ends, if the hooks ran because the process exits, see
+    /// [`crate::exit`]. Observers get it with [`crate::HookObserver::finished`].
+    pub exit: Option<ExitDecision>,
+}
+
+impl ShutdownReport {
+    /// `Ok` if all hooks finished successfully, otherwise a [`ShutdownError`]
+    /// with all results.
+    pub fn into_result(self) -> Result<Self, ShutdownError> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(ShutdownError {
+                results: self.results,
+            })
+        }
+    }
+
+    /// Whether all hooks finished successfully.
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| r.outcome.is_success())
+    }
+
+    /// Results of the hooks that didn't finish successfully.
+    pub fn failures(&self) -> impl Iterator<Item = &HookResult> {
+        self.results.iter().filter(|r| !r.outcome.is_success())
+    }
+
+    /// Results of the hooks that were skipped, see [`HookOutcome::Skipped`].
+    pub fn skipped(&self) -> impl Iterator<Item = &HookResult> {
+        self.results
+            .iter()
+            .filter(|r| matches!(r.outcome, HookOutcome::Skipped))
+    }
+}
+
+/// Returned by [`crate::run_global_hooks`] if at least one hook didn't finish
+/// successfully. Contains the results of all hooks in execution order, not only
+/// the failed ones.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ShutdownError {
+    /// Results in execution order.
+    pub results: Vec<HookResult>,
+}
+
+impl ShutdownError {
+    /// Results of the hooks that didn't finish successfully.
+    pub fn failures(&self) -> impl Iterator<Item = &HookResult> {
+        self.results.iter().filter(|r| !r.outcome.is_success())
+    }
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} shutdown hooks didn't finish successfully",
+            self.failures().count(),
+            self.results.len()
+        )?;
+        for result in self.failures() {
+            write!(
+                f,
+                "\n- {} ({:?}): {}",
+                result.name, result.duration, result.outcome
+            )?;
+            if let Some(env) = &result.env {
+                write!(f, "\n  {}", env)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for ShutdownError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Context(&'static str, std::io::Error);
+
+    impl fmt::Display for Context {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Error for Context {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.1)
+        }
+    }
+
+    #[test]
+    fn test_failure_shows_causes() {
+        let err = Context("flushing cache", std::io::Error::other("disk full"));
+        let outcome = HookOutcome::Failed(err.into());
+        assert_eq!(outcome.to_string(), "failed: flushing cache: disk full");
+        assert!(outcome.error().unwrap().is::<Context>());
+        assert!(HookOutcome::Completed.error().is_none());
+    }
+
+    #[test]
+    fn test_shutdown_error() {
+        let result = |name: &str, outcome| HookResult {
+            name: name.to_string(),
+            outcome,
+            duration: Duration::from_millis(3),
+            env: None,
+        };
+        let report = ShutdownReport {
+            results: vec![
+                result("ok", HookOutcome::Completed),
+                result("flush", HookOutcome::Failed("disk full".into())),
+                result("upload", HookOutcome::TimedOut),
+            ],
+            exit: None,
+        };
+        let err = report.into_result().unwrap_err();
+        assert_eq!(err.results.len(), 3);
+        assert_eq!(
+            err.to_string(),
+            "2 of 3 shutdown hooks didn't finish successfully\n\
+             - flush (3ms): failed: disk full\n\
+             - upload (3ms): timed out"
+        );
+        assert!(ShutdownReport::default().into_result().is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Runs hooks while a Tokio runtime is still alive.
+//!
+//! Async cleanup in a `Drop` at the end of `#[tokio::main]` happens after the
+//! runtime is gone: spawning tasks panics and timers and sockets don't work
+//! anymore. Build the runtime yourself instead and hand it over to
+//! [`Registry::shutdown_runtime`] when `main` is done.
+
+use crate::{Registry, ShutdownReason, ShutdownReport};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+impl Registry {
+    /// Executes the hooks of the registry inside the context of `runtime` and
+    /// shuts the runtime down with [`Runtime::shutdown_timeout`] afterwards.
+    /// Both share `timeout`: the runtime gets what is left after the hooks.
+    ///
+    /// Async hooks are still driven by the built-in executor, but
+    /// `tokio::spawn()` and `Handle::current()` work inside of them. Timers and
+    /// I/O need a multi-thread runtime, because the drivers of a current-thread
+    /// runtime only make progress inside `Runtime::block_on`.
+    ///
+    /// ```rust,no_run
+    /// use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+    /// use std::time::Duration;
+    ///
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    /// let registry = Registry::new();
+    /// registry.register_async(HookConfig::new("flush"), |_token| async {
+    ///     tokio::time::sleep(Duration::from_millis(10)).await;
+    /// });
+    /// runtime.block_on(async { /* the application */ });
+    /// registry.shutdown_runtime(runtime, ShutdownReason::Exit, Duration::from_secs(5));
+    /// ```
+    pub fn shutdown_runtime(
+        &self,
+        runtime: Runtime,
+        reason: ShutdownReason,
+        timeout: Duration,
+    ) -> ShutdownReport {
+        let begin = Instant::now();
+        let report = {
+            let _enter = runtime.enter();
+            self.run(reason, Some(timeout))
+        };
+        runtime.shutdown_timeout(timeout.saturating_sub(begin.elapsed()));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HookConfig;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_async_hooks_use_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let registry = Registry::new();
+        let flushed = Arc::new(AtomicBool::new(false));
+        let flushed_c = flushed.clone();
+        registry.register_async(HookConfig::new("flush"), move |_token| async move {
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                flushed_c.store(true, Ordering::SeqCst);
+            })
+            .await
+            .unwrap();
+        });
+
+        let report =
+            registry.shutdown_runtime(runtime, ShutdownReason::Exit, Duration::from_secs(5));
+        assert!(report.is_success());
+        assert!(flushed.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,164 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Tokio tasks that are stopped and awaited at shutdown.
+
+use crate::sync::Mutex;
+use crate::{CancellationToken, HookConfig, HookOutcome, Registry, ShutdownContext};
+use core::future::Future;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// Spawns Tokio tasks that are told to stop and awaited when the shutdown
+/// starts. Combines spawning with teardown tracking, so no task is forgotten.
+///
+/// The scope registers one hook. When it runs, the [`CancellationToken`] of all
+/// tasks is cancelled and the hook waits until they ended. Tasks that are still
+/// running at the deadline of the hook are aborted and the hook is reported as
+/// [`HookOutcome::TimedOut`]; panicked tasks make it [`HookOutcome::Failed`].
+/// The runtime must still be alive then, see [`Registry::shutdown_runtime`].
+///
+/// ## Example
+/// ```rust,no_run
+/// use simple_on_shutdown::{global, HookConfig, ShutdownScope};
+/// use std::time::Duration;
+///
+/// # async fn run() {
+/// let scope = ShutdownScope::new(global(), HookConfig::new("workers").timeout(Duration::from_secs(5)));
+/// scope.spawn(|token| async move {
+///     while !token.is_cancelled() {
+///         // process the next job
+/// #       tokio::task::yield_now().await;
+///     }
+/// });
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ShutdownScope {
+    token: CancellationToken,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl ShutdownScope {
+    /// Constructor. Registers the hook that stops the tasks with `config` in
+    /// `registry`.
+    #[track_caller]
+    pub fn new(registry: &Registry, config: HookConfig) -> Arc<Self> {
+        let scope = Arc::new(Self {
+            token: CancellationToken::new(),
+            tasks: Mutex::new(Vec::new()),
+        });
+        let scope_c = scope.clone();
+        registry.register_outcome(config, move |ctx| scope_c.shut_down(ctx));
+        scope
+    }
+
+    /// Spawns a task on the current Tokio runtime. `f` gets the token that is
+    /// cancelled when the shutdown starts. Tasks spawned after that are neither
+    /// awaited nor aborted.
+    ///
+    /// ## Panics
+    /// If called outside of a Tokio runtime, like `tokio::spawn`.
+    pub fn spawn<F, Fut>(&self, f: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = tokio::spawn(f(self.token.clone()));
+        let mut tasks = self.tasks.lock();
+        // don't accumulate handles of short-lived tasks
+        tasks.retain(|t| !t.is_finished());
+        tasks.push(task);
+    }
+
+    /// Token of the tasks. It is cancelled when the shutdown starts.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Body of the hook: cancels the token and awaits the tasks.
+    fn shut_down(&self, ctx: &ShutdownContext) -> HookOutcome {
+        self.token.cancel();
+        let tasks = core::mem::take(&mut *self.tasks.lock());
+        let aborts: Vec<_> = tasks.iter().map(JoinHandle::abort_handle).collect();
+        let mut panicked = 0;
+        let join_all = async {
+            for task in tasks {
+                if task.await.is_err_and(|err| err.is_panic()) {
+                    panicked += 1;
+                }
+            }
+        };
+        let grace = ctx.config().get_cancel_grace();
+        let completed =
+            crate::executor::block_on(join_all, ctx.deadline(), grace, &CancellationToken::new());
+        if !completed {
+            aborts.iter().for_each(|task| task.abort());
+            HookOutcome::TimedOut
+        } else if panicked > 0 {
+            HookOutcome::Failed(format!("{} tasks panicked", panicked).into())
+        } else {
+            HookOutcome::Completed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_tasks_are_stopped_and_awaited() {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let registry = Registry::new();
+        let cooperative = ShutdownScope::new(&registry, HookConfig::new("cooperative"));
+        let stubborn = ShutdownScope::new(
+            &registry,
+            HookConfig::new("stubborn")
+                .timeout(Duration::from_millis(100))
+                .cancel_grace(Duration::from_millis(0)),
+        );
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_c = stopped.clone();
+        runtime.block_on(async {
+            cooperative.spawn(|token| async move {
+                token.cancelled().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                stopped_c.store(true, Ordering::SeqCst);
+            });
+            stubborn.spawn(|_token| tokio::time::sleep(Duration::from_secs(60)));
+        });
+
+        let report =
+            registry.shutdown_runtime(runtime, ShutdownReason::Exit, Duration::from_secs(5));
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+        assert!(matches!(report.results[1].outcome, HookOutcome::Completed));
+        assert!(stopped.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,79 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Migration of [`scopeguard::ScopeGuard`]s.
+//!
+//! Codebases that already use `scopeguard` (or `defer!` macros built on it) can
+//! hand their guards over one by one instead of rewriting them: the guard is
+//! moved into a callback or hook and dropped there. Its strategy still applies,
+//! e.g. a guard with `OnUnwind` does nothing when it is dropped in a regular
+//! shutdown.
+//!
+//! The conversion into an [`OnShutdownCallback`](crate::OnShutdownCallback) is
+//! part of `simple-on-shutdown-core`.
+
+use crate::{HookConfig, Registry};
+use scopeguard::{ScopeGuard, Strategy};
+
+impl Registry {
+    /// Registers a hook that drops `guard`, so it runs at shutdown instead of
+    /// at the end of its scope.
+    #[track_caller]
+    pub fn register_scopeguard<T, F, S>(&self, config: HookConfig, guard: ScopeGuard<T, F, S>)
+    where
+        T: Send + 'static,
+        F: FnOnce(T) + Send + 'static,
+        S: Strategy + 'static,
+    {
+        self.register(config, move || drop(guard));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ShutdownAnchor, ShutdownReason};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_guards_are_migrated() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let registry = Registry::new();
+        let anchor = ShutdownAnchor::new();
+        {
+            let count = |dropped: Arc<AtomicUsize>| {
+                scopeguard::guard(dropped, |d| {
+                    d.fetch_add(1, Ordering::SeqCst);
+                })
+            };
+            registry.register_scopeguard(HookConfig::new("guard"), count(dropped.clone()));
+            anchor.adopt(count(dropped.clone()).into());
+        }
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        drop(anchor);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        registry.run(ShutdownReason::Exit, None);
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+    }
+}
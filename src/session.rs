@@ -0,0 +1,174 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! End-of-run summaries for CLI tools.
+
+use crate::sync::Mutex;
+use crate::{exit_code_for, HookConfig, Registry, ShutdownContext};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Counters of a run, in the order they were first used.
+type Counters = Vec<(String, u64)>;
+
+/// Handle to the summary that [`Registry::register_session_log`] writes at
+/// the end of the run. The tool counts what it did, e.g. processed files or
+/// errors. Cloned handles share the counters.
+#[derive(Clone)]
+pub struct SessionLog {
+    started: Instant,
+    started_at: SystemTime,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl SessionLog {
+    /// Adds `n` to the counter `name`. Counters start at `0`.
+    pub fn add(&self, name: &str, n: u64) {
+        let mut counters = self.counters.lock();
+        match counters.iter_mut().find(|(c, _)| c == name) {
+            Some((_, value)) => *value += n,
+            None => counters.push((name.to_string(), n)),
+        }
+    }
+
+    /// Adds `1` to the counter `name`.
+    pub fn increment(&self, name: &str) {
+        self.add(name, 1);
+    }
+
+    /// The current value of the counter `name`.
+    pub fn get(&self, name: &str) -> u64 {
+        let counters = self.counters.lock();
+        counters
+            .iter()
+            .find(|(c, _)| c == name)
+            .map_or(0, |&(_, value)| value)
+    }
+
+    /// The summary line, e.g.
+    /// `started=1700000000.123 duration=2.501s reason=Exit exit_code=0 files=3 errors=0`.
+    fn summary(&self, ctx: &ShutdownContext) -> String {
+        let reason = ctx.reason();
+        let code = crate::exit_decision()
+            .and_then(|d| d.code)
+            .unwrap_or_else(|| exit_code_for(reason));
+        let started = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut line = format!(
+            "started={}.{:03} duration={:.3}s reason={:?} exit_code={}",
+            started.as_secs(),
+            started.subsec_millis(),
+            self.started.elapsed().as_secs_f64(),
+            reason,
+            code
+        );
+        for (name, value) in self.counters.lock().iter() {
+            line.push_str(&format!(" {}={}", name, value));
+        }
+        line
+    }
+}
+
+impl Registry {
+    /// Registers a hook that appends a summary of the run to the log file at
+    /// `path`: when it started, how long it took, why it ended, the exit code
+    /// and the counters of the returned [`SessionLog`]. One line per run, e.g.
+    ///
+    /// ```text
+    /// started=1700000000.123 duration=2.501s reason=Exit exit_code=0 files=3 errors=0
+    /// ```
+    ///
+    /// The run starts with this call, so register it early in `main()`.
+    ///
+    /// ## Example
+    /// ```rust
+    /// use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+    ///
+    /// let registry = Registry::new();
+    /// let path = std::env::temp_dir().join("session-example.log");
+    /// let session = registry.register_session_log(HookConfig::new("session log"), &path);
+    /// for _file in ["a.txt", "b.txt"].iter() {
+    ///     session.increment("files");
+    /// }
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// assert!(std::fs::read_to_string(&path).unwrap().contains(" files=2"));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn register_session_log(&self, config: HookConfig, path: impl Into<PathBuf>) -> SessionLog {
+        let path = path.into();
+        let session = SessionLog {
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+            counters: Arc::new(Mutex::new(Vec::new())),
+        };
+        let handle = session.clone();
+        self.register_fallible(config, move |ctx| -> io::Result<()> {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            writeln!(file, "{}", session.summary(ctx))
+        });
+        handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+
+    #[test]
+    fn test_summary_is_appended() {
+        let path = std::env::temp_dir().join(format!("session-test-{}.log", std::process::id()));
+        for run in 0..2 {
+            let registry = Registry::new();
+            let session = registry.register_session_log(HookConfig::new("session log"), &path);
+            session.add("files", 3);
+            session.increment("errors");
+            session.increment("files");
+            assert_eq!(session.get("files"), 4);
+            assert_eq!(session.get("skipped"), 0);
+            let reason = [ShutdownReason::Exit, ShutdownReason::Signal(15)][run];
+            assert!(registry.run(reason, None).is_success());
+        }
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("started="), "{}", lines[0]);
+        assert!(
+            lines[0].ends_with("reason=Exit exit_code=0 files=4 errors=1"),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].ends_with("reason=Signal(15) exit_code=143 files=4 errors=1"),
+            "{}",
+            lines[1]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Seeded shuffle of the execution order, see [`crate::ExecutionOrder::Shuffled`].
+
+/// Environment variable with the seed of [`crate::ExecutionOrder::shuffled`],
+/// e.g. to reproduce the order of a failed CI run.
+pub const SHUFFLE_SEED_ENV: &str = "SHUTDOWN_SHUFFLE_SEED";
+
+/// Fisher-Yates shuffle. The same seed and length give the same permutation.
+pub(crate) fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut random = seed;
+    for i in (1..items.len()).rev() {
+        random = splitmix64(random);
+        items.swap(i, (random % (i as u64 + 1)) as usize);
+    }
+}
+
+/// Finalizer of the SplitMix64 generator.
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
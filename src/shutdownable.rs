@@ -0,0 +1,147 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Components that know how to shut themselves down.
+
+use crate::{HookConfig, Registry};
+use std::fs::File;
+use std::io::{BufWriter, LineWriter, Write};
+use std::net::{Shutdown, TcpStream};
+use std::process::Child;
+use std::thread::JoinHandle;
+
+/// A component that can be registered generically with
+/// [`Registry::register_shutdownable`] instead of writing a closure per
+/// resource.
+pub trait Shutdownable {
+    /// Shuts the component down. Errors can't be handled at this point anymore
+    /// and are ignored by the implementations of this crate.
+    fn shutdown(self);
+}
+
+/// Shuts down both halves of the connection.
+impl Shutdownable for TcpStream {
+    fn shutdown(self) {
+        let _ = TcpStream::shutdown(&self, Shutdown::Both);
+    }
+}
+
+/// Flushes all data and metadata to disk.
+impl Shutdownable for File {
+    fn shutdown(self) {
+        let _ = self.sync_all();
+    }
+}
+
+/// Flushes the buffer.
+impl<W: Write> Shutdownable for BufWriter<W> {
+    fn shutdown(mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Flushes the buffer.
+impl<W: Write> Shutdownable for LineWriter<W> {
+    fn shutdown(mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Kills the child and waits for it, so no zombie is left behind.
+impl Shutdownable for Child {
+    fn shutdown(mut self) {
+        let _ = self.kill();
+        let _ = self.wait();
+    }
+}
+
+/// Waits for the thread to finish.
+impl<T> Shutdownable for JoinHandle<T> {
+    fn shutdown(self) {
+        let _ = self.join();
+    }
+}
+
+/// Closes the channel, so the receiving side knows that no more values arrive.
+impl<T> Shutdownable for std::sync::mpsc::Sender<T> {
+    fn shutdown(self) {
+        drop(self);
+    }
+}
+
+impl Registry {
+    /// Registers a hook that calls [`Shutdownable::shutdown`] on `component`. The
+    /// hook is named after the type of the component.
+    #[track_caller]
+    pub fn register_shutdownable<S>(&self, component: S)
+    where
+        S: Shutdownable + Send + 'static,
+    {
+        let config = HookConfig::new(core::any::type_name::<S>());
+        self.register(config, move || component.shutdown());
+    }
+}
+
+/// Registers `component` in the [`crate::global`] registry. See
+/// [`Registry::register_shutdownable`].
+#[track_caller]
+pub fn register_shutdownable<S>(component: S)
+where
+    S: Shutdownable + Send + 'static,
+{
+    crate::global().register_shutdownable(component);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    struct Component(Arc<AtomicBool>);
+
+    impl Shutdownable for Component {
+        fn shutdown(self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_shutdownable() {
+        let registry = Registry::new();
+        let down = Arc::new(AtomicBool::new(false));
+        registry.register_shutdownable(Component(down.clone()));
+
+        let (sender, receiver) = mpsc::channel::<()>();
+        let worker = std::thread::spawn(move || while receiver.recv().is_ok() {});
+        // runs first: closing the channel lets the worker finish
+        registry.register_shutdownable(worker);
+        registry.register_shutdownable(sender);
+
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(down.load(Ordering::SeqCst));
+        assert!(report.results[0].name.contains("Sender"));
+    }
+}
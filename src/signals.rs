@@ -0,0 +1,303 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Graceful shutdown on `SIGINT` and `SIGTERM` and events on `SIGHUP`. UNIX
+//! only.
+//!
+//! The signal handler only writes the signal number into a pipe. A dedicated
+//! thread reads it, runs the hooks of the [`crate::global`] registry and exits the
+//! process afterwards with [`crate::exit_code_for`] (`128 + signal` by default).
+//! If the machine shuts down (see [`crate::is_system_shutting_down`]), the hooks
+//! get [`ShutdownReason::SystemShutdown`] instead of the signal.
+//! Signals that are routed to an [`Event`] emit that event instead and the
+//! process keeps running. This way, regular hooks never run inside a signal
+//! handler.
+//!
+//! A second shutdown signal while the hooks still run ends the process
+//! immediately with [`crate::force_exit`], see [`RepeatedSignalAction`].
+
+use crate::diagnostics::{self, Level};
+use crate::sync::Mutex;
+use crate::{Event, ExitDecision, ShutdownReason};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Signals that start the shutdown sequence.
+pub const SHUTDOWN_SIGNALS: [i32; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// Write end of the pipe, `-1` until installed.
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+/// Signals that emit an event instead of starting the shutdown sequence.
+static EVENT_SIGNALS: Mutex<Vec<(i32, Event)>> = Mutex::new(Vec::new());
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of deliveries per signal that the signal thread didn't pick up yet.
+/// The handler only writes to the pipe if the count was zero, so a flood of
+/// signals can't fill up the pipe.
+static DELIVERIES: [AtomicUsize; 65] = [ZERO; 65];
+
+/// Whether shutdown signals that arrive while the hooks run are only logged.
+static LOG_REPEATED: AtomicBool = AtomicBool::new(false);
+
+/// Minimum time between two log messages about repeated signals.
+const REPEATED_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What happens with shutdown signals that arrive while the hooks still run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatedSignalAction {
+    /// The process ends immediately with [`crate::force_exit`], like pressing
+    /// `CTRL+C` twice. This is the default.
+    #[default]
+    ForceExit,
+    /// The signals are counted and logged to the diagnostics sink (stderr by
+    /// default, see [`crate::set_diagnostics_sink`]) at most once per second,
+    /// e.g. `received 13 additional SIGTERM`. The hooks keep running. For
+    /// supervisors that send the shutdown signal repeatedly.
+    Log,
+}
+
+/// Sets what happens with shutdown signals that arrive while the hooks still
+/// run. See [`RepeatedSignalAction`].
+pub fn set_repeated_signal_action(action: RepeatedSignalAction) {
+    LOG_REPEATED.store(action == RepeatedSignalAction::Log, Ordering::SeqCst);
+}
+
+/// Installs handlers for all [`SHUTDOWN_SIGNALS`]. When one of them arrives, the
+/// hooks of the [`crate::global`] registry run with
+/// [`ShutdownReason::Signal`] on a dedicated thread and the process exits
+/// afterwards with [`crate::exit_code_for`]. Calling this more than once has no
+/// effect; every call returns the result of the first installation.
+pub fn install_signal_handlers() -> io::Result<()> {
+    static INSTALL: OnceLock<io::Result<()>> = OnceLock::new();
+    crate::global::init_once(&INSTALL, || {
+        SHUTDOWN_SIGNALS
+            .iter()
+            .try_for_each(|&sig| set_handler(sig))
+    })
+}
+
+/// Installs a handler for `SIGHUP` that emits [`Event::Reload`] on the
+/// [`crate::global`] registry, see [`crate::Registry::register_event`]. Unlike
+/// the default action of `SIGHUP`, the process keeps running.
+pub fn install_reload_handler() -> io::Result<()> {
+    route_signal(libc::SIGHUP, Event::Reload)
+}
+
+/// Installs handlers for `SIGUSR1` and `SIGUSR2` that emit [`Event::User1`] and
+/// [`Event::User2`] on the [`crate::global`] registry. Useful for diagnostics
+/// like dumping the internal state of a running process.
+pub fn install_diagnostic_handlers() -> io::Result<()> {
+    route_signal(libc::SIGUSR1, Event::User1)?;
+    route_signal(libc::SIGUSR2, Event::User2)
+}
+
+/// Emits `event` on the [`crate::global`] registry every time `sig` arrives,
+/// instead of starting the shutdown sequence. The hooks of the event run on the
+/// same thread as the shutdown hooks and are reported to the same observers.
+pub fn route_signal(sig: i32, event: Event) -> io::Result<()> {
+    {
+        let mut routes = EVENT_SIGNALS.lock();
+        routes.retain(|&(s, _)| s != sig);
+        routes.push((sig, event));
+    }
+    set_handler(sig)
+}
+
+/// Installs [`handle_signal`] for `sig`.
+fn set_handler(sig: i32) -> io::Result<()> {
+    start_signal_thread()?;
+    // SAFETY: the action is fully initialized and the handler is async-signal-safe
+    unsafe {
+        let mut action: libc::sigaction = core::mem::zeroed();
+        action.sa_sigaction = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(sig, &action, core::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Creates the pipe and the thread that reads from it, unless that already
+/// happened.
+fn start_signal_thread() -> io::Result<()> {
+    static START: OnceLock<io::Result<()>> = OnceLock::new();
+    crate::global::init_once(&START, || {
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for both ends
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let [read_fd, write_fd] = fds;
+        PIPE_WRITE.store(write_fd, Ordering::SeqCst);
+
+        thread::Builder::new()
+            .name("shutdown-signals".to_string())
+            .spawn(move || wait_for_signals(read_fd))?;
+        Ok(())
+    })
+}
+
+/// Signal handler for all [`SHUTDOWN_SIGNALS`] and routed signals.
+extern "C" fn handle_signal(sig: libc::c_int) {
+    // atomics are async-signal-safe
+    if let Some(count) = DELIVERIES.get(sig as usize) {
+        if count.fetch_add(1, Ordering::SeqCst) > 0 {
+            // the signal thread wasn't woken up yet
+            return;
+        }
+    }
+    let byte = sig as u8;
+    // SAFETY: write() is async-signal-safe; errors can't be handled here
+    unsafe {
+        libc::write(
+            PIPE_WRITE.load(Ordering::SeqCst),
+            &byte as *const u8 as *const libc::c_void,
+            1,
+        );
+    }
+}
+
+/// Body of the signal thread.
+fn wait_for_signals(read_fd: libc::c_int) {
+    let mut byte = 0_u8;
+    loop {
+        // SAFETY: `byte` is valid for one byte
+        let n = unsafe { libc::read(read_fd, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if n < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+        }
+        if n != 1 {
+            // the pipe is broken, nothing to wait for
+            return;
+        }
+        let sig = i32::from(byte);
+        // signals of the same kind that arrived meanwhile are coalesced
+        let deliveries = take_deliveries(sig);
+        let event = EVENT_SIGNALS
+            .lock()
+            .iter()
+            .find(|&&(s, _)| s == sig)
+            .map(|&(_, event)| event);
+        match event {
+            Some(event) => {
+                crate::global().emit(event);
+            }
+            None => shut_down(ShutdownReason::Signal(sig), deliveries),
+        }
+    }
+}
+
+/// Runs the hooks of the [`crate::global`] registry on a dedicated thread and
+/// exits afterwards. The signal thread stays responsive for further signals,
+/// see [`RepeatedSignalAction`].
+///
+/// ## Parameters
+/// * `deliveries` how often the signal arrived since the last call
+fn shut_down(reason: ShutdownReason, deliveries: usize) {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    let repeated = if STARTED.swap(true, Ordering::SeqCst) {
+        deliveries
+    } else {
+        deliveries.saturating_sub(1)
+    };
+    let code = crate::exit_code_for(reason);
+    if repeated > 0 {
+        if !LOG_REPEATED.load(Ordering::SeqCst) {
+            // the first decision wins, e.g. the code of an earlier crate::exit()
+            let claimed = crate::exit_decision().and_then(|decision| decision.code);
+            crate::force_exit(claimed.unwrap_or(code));
+        }
+        if let ShutdownReason::Signal(sig) = reason {
+            log_repeated(sig, repeated);
+        }
+    }
+    if deliveries == repeated {
+        // the hooks already run
+        return;
+    }
+    // keeps the exit code of the signal, init systems expect it
+    let decision = ExitDecision {
+        reason: crate::system::refine(reason),
+        code: Some(code),
+    };
+    let decision = match crate::global::claim_exit(decision) {
+        Ok(decision) => decision,
+        // e.g. crate::exit() already runs the hooks and ends the process
+        Err(_) => return,
+    };
+    let run = move || {
+        // the exit code reflects the reason, not the outcome of the hooks
+        let _ = crate::global::run_exit_hooks(decision);
+        std::process::exit(code);
+    };
+    if thread::Builder::new()
+        .name("shutdown-hooks".to_string())
+        .spawn(run)
+        .is_err()
+    {
+        run();
+    }
+}
+
+/// Logs `count` repeated deliveries of `sig`, at most once per
+/// [`REPEATED_LOG_INTERVAL`]. Waits for the interval to pass first; signals that
+/// arrive meanwhile are added to the message.
+fn log_repeated(sig: i32, mut count: usize) {
+    static LAST_LOG: Mutex<Option<Instant>> = Mutex::new(None);
+    let mut last_log = LAST_LOG.lock();
+    if let Some(last) = *last_log {
+        thread::sleep((last + REPEATED_LOG_INTERVAL).saturating_duration_since(Instant::now()));
+        count += take_deliveries(sig);
+    }
+    *last_log = Some(Instant::now());
+    diagnostics::emit(
+        Level::Info,
+        format_args!("received {} additional {}", count, signal_name(sig)),
+    );
+}
+
+/// Number of deliveries of `sig` since the last call.
+fn take_deliveries(sig: i32) -> usize {
+    DELIVERIES
+        .get(sig as usize)
+        .map_or(1, |count| count.swap(0, Ordering::SeqCst))
+}
+
+/// Name of a signal for log messages.
+fn signal_name(sig: i32) -> String {
+    match sig {
+        libc::SIGINT => "SIGINT".to_string(),
+        libc::SIGTERM => "SIGTERM".to_string(),
+        libc::SIGHUP => "SIGHUP".to_string(),
+        libc::SIGQUIT => "SIGQUIT".to_string(),
+        _ => format!("signal {}", sig),
+    }
+}
@@ -0,0 +1,117 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Hooks that are declared in statics and armed lazily.
+
+use crate::{HookConfig, Registry};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A hook that can be constructed in a `const` context and lives in a `static`.
+/// Fixed infrastructure hooks don't need to be re-registered on every call
+/// path that needs them: each path calls [`Self::arm`], which registers the hook
+/// in the [`crate::global`] registry only the first time.
+///
+/// ## Example
+/// ```
+/// use simple_on_shutdown::StaticShutdownHook;
+///
+/// fn flush_logs() {
+///     println!("flushing logs");
+/// }
+///
+/// static FLUSH_LOGS: StaticShutdownHook = StaticShutdownHook::new(flush_logs).named("flush logs");
+///
+/// FLUSH_LOGS.arm();
+/// FLUSH_LOGS.arm(); // no effect
+/// assert!(FLUSH_LOGS.is_armed());
+/// ```
+#[derive(Debug)]
+pub struct StaticShutdownHook {
+    name: &'static str,
+    f: fn(),
+    armed: AtomicBool,
+}
+
+impl StaticShutdownHook {
+    /// Constructor. The hook is named `"static hook"` unless [`Self::named`] is
+    /// used.
+    pub const fn new(f: fn()) -> Self {
+        Self {
+            name: "static hook",
+            f,
+            armed: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the name of the hook, see [`HookConfig::name`].
+    pub const fn named(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Registers the hook in the [`crate::global`] registry, unless it was
+    /// already armed.
+    pub fn arm(&self) {
+        self.arm_in(crate::global());
+    }
+
+    /// Registers the hook in `registry`, unless it was already armed. A hook is
+    /// armed at most once, even across different registries.
+    pub fn arm_in(&self, registry: &Registry) {
+        if !self.armed.swap(true, Ordering::SeqCst) {
+            registry.register(HookConfig::new(self.name), self.f);
+        }
+    }
+
+    /// Whether [`Self::arm`] or [`Self::arm_in`] was called.
+    pub fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+    use std::sync::atomic::AtomicUsize;
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count() {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    static HOOK: StaticShutdownHook = StaticShutdownHook::new(count).named("count");
+
+    #[test]
+    fn test_arm_once() {
+        let registry = Registry::new();
+        assert!(!HOOK.is_armed());
+        HOOK.arm_in(&registry);
+        HOOK.arm_in(&registry);
+        assert_eq!(registry.hooks()[0].name, "count");
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,122 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Hooks that are polled repeatedly with a tiny time slice.
+
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use core::task::Poll;
+use std::time::Instant;
+
+/// A hook that performs its cleanup in small steps. The runner checks the
+/// deadline and reports progress between the steps, instead of being at the
+/// mercy of one long blocking call. See [`Registry::register_stepped`].
+///
+/// Implemented for closures, too.
+pub trait StepHook: Send + 'static {
+    /// Performs the next small piece of work. Returns [`Poll::Ready`] once the
+    /// cleanup is done.
+    fn step(&mut self) -> Poll<()>;
+}
+
+impl<F> StepHook for F
+where
+    F: FnMut() -> Poll<()> + Send + 'static,
+{
+    fn step(&mut self) -> Poll<()> {
+        self()
+    }
+}
+
+impl Registry {
+    /// Registers a hook that is polled until it is ready. After every pending
+    /// step, the number of completed steps is reported to the
+    /// [`crate::HookObserver`]s. If the deadline of the hook expires before it is
+    /// ready, it is abandoned and reported as [`HookOutcome::TimedOut`].
+    #[track_caller]
+    pub fn register_stepped<S: StepHook>(&self, config: HookConfig, mut hook: S) {
+        self.register_outcome(config, move |ctx| run_steps(&mut hook, ctx));
+    }
+}
+
+/// Polls `hook` until it is ready or the deadline of the context expires.
+fn run_steps<S: StepHook>(hook: &mut S, ctx: &ShutdownContext) -> HookOutcome {
+    let mut steps = 0;
+    loop {
+        if hook.step().is_ready() {
+            return HookOutcome::Completed;
+        }
+        steps += 1;
+        ctx.report_progress(steps);
+        if ctx.deadline().is_some_and(|d| Instant::now() >= d) {
+            return HookOutcome::TimedOut;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HookInfo, HookObserver, ShutdownReason};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Progress(AtomicU64);
+
+    impl HookObserver for Progress {
+        fn progress(&self, _info: &HookInfo, progress: u64) {
+            self.0.store(progress, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_stepped_hook() {
+        let registry = Registry::new();
+        let progress = Arc::new(Progress::default());
+        registry.add_observer(progress.clone());
+        let mut remaining = 10;
+        registry.register_stepped(HookConfig::new("cleanup"), move || {
+            remaining -= 1;
+            if remaining == 0 {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        });
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(report.is_success());
+        assert_eq!(progress.0.load(Ordering::SeqCst), 9);
+    }
+
+    #[test]
+    fn test_stepped_hook_times_out() {
+        let registry = Registry::new();
+        registry.register_stepped(
+            HookConfig::new("endless").timeout(Duration::from_millis(20)),
+            || Poll::Pending,
+        );
+        let report = registry.run(ShutdownReason::Exit, None);
+        assert!(matches!(report.results[0].outcome, HookOutcome::TimedOut));
+    }
+}
@@ -0,0 +1,161 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Locks of the [`crate::Registry`] and everything else that runs during the
+//! shutdown sequence: the ones of std by default, the smaller ones of
+//! `parking_lot` with the `parking_lot` feature.
+
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) use std::sync::MutexGuard;
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::PoisonError;
+
+#[cfg(feature = "parking_lot")]
+pub(crate) use parking_lot::MutexGuard;
+
+/// Mutex whose `lock()` returns the guard directly.
+pub(crate) struct Mutex<T>(
+    #[cfg(not(feature = "parking_lot"))] std::sync::Mutex<T>,
+    #[cfg(feature = "parking_lot")] parking_lot::Mutex<T>,
+);
+
+impl<T> Mutex<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        #[cfg(not(feature = "parking_lot"))]
+        return Self(std::sync::Mutex::new(value));
+        #[cfg(feature = "parking_lot")]
+        return Self(parking_lot::const_mutex(value));
+    }
+
+    /// Locks the mutex. A poisoned mutex is recovered: a thread that panicked
+    /// while holding the lock must not cost all other threads their cleanup. The
+    /// registry never leaves its state inconsistent across a panic.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        #[cfg(not(feature = "parking_lot"))]
+        return self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "parking_lot")]
+        return self.0.lock();
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Condition variable that works with [`Mutex`].
+pub(crate) struct Condvar(
+    #[cfg(not(feature = "parking_lot"))] std::sync::Condvar,
+    #[cfg(feature = "parking_lot")] parking_lot::Condvar,
+);
+
+impl Condvar {
+    pub(crate) const fn new() -> Self {
+        #[cfg(not(feature = "parking_lot"))]
+        return Self(std::sync::Condvar::new());
+        #[cfg(feature = "parking_lot")]
+        return Self(parking_lot::Condvar::new());
+    }
+
+    /// Blocks while `condition` is true.
+    pub(crate) fn wait_while<'a, T, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        #[cfg(not(feature = "parking_lot"))]
+        return self
+            .0
+            .wait_while(guard, condition)
+            .unwrap_or_else(PoisonError::into_inner);
+        #[cfg(feature = "parking_lot")]
+        {
+            let mut guard = guard;
+            self.0.wait_while(&mut guard, condition);
+            guard
+        }
+    }
+
+    /// Blocks while `condition` is true, but at most for `timeout`. The caller
+    /// checks the condition again to see whether the timeout expired.
+    pub(crate) fn wait_timeout_while<'a, T, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: core::time::Duration,
+        condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        #[cfg(not(feature = "parking_lot"))]
+        return self
+            .0
+            .wait_timeout_while(guard, timeout, condition)
+            .map_or_else(|err| err.into_inner().0, |(guard, _)| guard);
+        #[cfg(feature = "parking_lot")]
+        {
+            let mut guard = guard;
+            self.0.wait_while_for(&mut guard, condition, timeout);
+            guard
+        }
+    }
+
+    pub(crate) fn notify_all(&self) {
+        self.0.notify_all();
+    }
+}
+
+impl core::fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lock_after_panic() {
+        let mutex = Arc::new(Mutex::new(1));
+        let mutex_c = mutex.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = mutex_c.lock();
+            panic!("poisons the std mutex");
+        })
+        .join();
+        *mutex.lock() += 1;
+        assert_eq!(*mutex.lock(), 2);
+    }
+}
@@ -0,0 +1,153 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Tells a shutdown of the whole machine apart from a stop of only the
+//! application, see [`crate::ShutdownReason::SystemShutdown`].
+//!
+//! On Linux, systemd is asked whether it stops the system. On Windows, the
+//! handler of [`install_system_shutdown_handler`] receives
+//! `CTRL_SHUTDOWN_EVENT`. Other platforms don't tell, there a machine shutdown
+//! looks like any other signal.
+
+#[cfg(any(windows, all(unix, feature = "signals")))]
+use crate::ShutdownReason;
+#[cfg(windows)]
+use crate::{exit_code_for, ExitDecision};
+#[cfg(windows)]
+use std::io;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(windows)]
+use std::sync::OnceLock;
+
+/// Set by the handler of [`install_system_shutdown_handler`].
+#[cfg(windows)]
+static SYSTEM_SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether the operating system shuts the machine down or reboots it right
+/// now, as opposed to only the application being stopped, e.g. by
+/// `systemctl stop` or a deployment.
+///
+/// On Linux, this asks systemd (`systemctl is-system-running`), which takes a
+/// few milliseconds. Without systemd, and on platforms other than Linux and
+/// Windows, this is always `false`.
+#[cfg(target_os = "linux")]
+pub fn is_system_shutting_down() -> bool {
+    use std::process::{Command, Stdio};
+    if !std::path::Path::new("/run/systemd/system").exists() {
+        return false;
+    }
+    Command::new("systemctl")
+        .arg("is-system-running")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim() == "stopping")
+        .unwrap_or(false)
+}
+
+/// Whether the operating system shuts the machine down or reboots it right
+/// now, as opposed to only the application being stopped.
+///
+/// On Windows, this requires [`install_system_shutdown_handler`].
+#[cfg(windows)]
+pub fn is_system_shutting_down() -> bool {
+    SYSTEM_SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Whether the operating system shuts the machine down or reboots it right
+/// now. Not detected on this platform, always `false`.
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn is_system_shutting_down() -> bool {
+    false
+}
+
+/// [`ShutdownReason::SystemShutdown`] instead of `reason` if the machine goes
+/// down.
+#[cfg(all(unix, feature = "signals"))]
+pub(crate) fn refine(reason: ShutdownReason) -> ShutdownReason {
+    if is_system_shutting_down() {
+        ShutdownReason::SystemShutdown
+    } else {
+        reason
+    }
+}
+
+#[cfg(windows)]
+const CTRL_SHUTDOWN_EVENT: u32 = 6;
+
+#[cfg(windows)]
+extern "system" {
+    fn SetConsoleCtrlHandler(
+        handler: Option<unsafe extern "system" fn(u32) -> i32>,
+        add: i32,
+    ) -> i32;
+}
+
+/// Installs a console control handler that runs the hooks of the
+/// [`crate::global`] registry with [`ShutdownReason::SystemShutdown`] when
+/// Windows shuts down, and ends the process afterwards. Other console events
+/// like `CTRL+C` are left to the other handlers. Windows only.
+///
+/// Windows only sends `CTRL_SHUTDOWN_EVENT` to services and to console
+/// processes without windows, and ends the process after a few seconds, no
+/// matter if the hooks finished.
+///
+/// Installed by [`crate::install`]. Calling this more than once has no effect.
+#[cfg(windows)]
+pub fn install_system_shutdown_handler() -> io::Result<()> {
+    static INSTALL: OnceLock<io::Result<()>> = OnceLock::new();
+    crate::global::init_once(&INSTALL, || {
+        // SAFETY: the handler is a plain function that lives as long as the process
+        if unsafe { SetConsoleCtrlHandler(Some(handle_console_event), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    })
+}
+
+/// Handler of [`install_system_shutdown_handler`]. Windows calls it on a new
+/// thread.
+#[cfg(windows)]
+unsafe extern "system" fn handle_console_event(event: u32) -> i32 {
+    if event != CTRL_SHUTDOWN_EVENT {
+        // not handled, the next handler decides
+        return 0;
+    }
+    SYSTEM_SHUTDOWN.store(true, Ordering::SeqCst);
+    let reason = ShutdownReason::SystemShutdown;
+    let decision = ExitDecision {
+        reason,
+        code: Some(exit_code_for(reason)),
+    };
+    match crate::global::claim_exit(decision) {
+        Ok(decision) => {
+            let _ = crate::global::run_exit_hooks(decision);
+            std::process::exit(decision.code.unwrap_or(0))
+        }
+        // the hooks already run; returning would end the process right away
+        Err(_) => loop {
+            std::thread::park();
+        },
+    }
+}
@@ -0,0 +1,235 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Selective execution of hooks based on their tags.
+
+use crate::HookConfig;
+
+/// Environment variable with a comma-separated list of tags. If set, only hooks
+/// with at least one of these tags run.
+pub const INCLUDE_TAGS_ENV: &str = "SHUTDOWN_INCLUDE_TAGS";
+
+/// Environment variable with a comma-separated list of tags. Hooks with one of
+/// these tags are skipped, e.g. `SHUTDOWN_EXCLUDE_TAGS=telemetry` in air-gapped
+/// deployments.
+pub const EXCLUDE_TAGS_ENV: &str = "SHUTDOWN_EXCLUDE_TAGS";
+
+/// Environment variable with a comma-separated list of modules, e.g.
+/// `SHUTDOWN_EXCLUDE_ORIGINS=noisy_crate`. Hooks that were registered by one of
+/// these modules or their submodules are skipped, see [`HookConfig::origin`].
+pub const EXCLUDE_ORIGINS_ENV: &str = "SHUTDOWN_EXCLUDE_ORIGINS";
+
+/// Decides which hooks run based on their [`HookConfig::tag`]s and
+/// [`HookConfig::origin`]. The default filter lets all hooks pass.
+///
+/// Excludes take precedence over includes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    include_origins: Vec<String>,
+    exclude_origins: Vec<String>,
+}
+
+impl TagFilter {
+    /// Constructor. Creates a filter that lets all hooks pass.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a filter from [`INCLUDE_TAGS_ENV`], [`EXCLUDE_TAGS_ENV`] and
+    /// [`EXCLUDE_ORIGINS_ENV`].
+    pub fn from_env() -> Self {
+        let tags = |var| {
+            std::env::var(var)
+                .map(|val| split_tags(&val))
+                .unwrap_or_default()
+        };
+        Self {
+            include: tags(INCLUDE_TAGS_ENV),
+            exclude: tags(EXCLUDE_TAGS_ENV),
+            include_origins: Vec::new(),
+            exclude_origins: tags(EXCLUDE_ORIGINS_ENV),
+        }
+    }
+
+    /// Only hooks with at least one of the included tags pass. Can be called
+    /// multiple times.
+    pub fn include(mut self, tag: impl Into<String>) -> Self {
+        self.include.push(tag.into());
+        self
+    }
+
+    /// Hooks with this tag don't pass. Can be called multiple times.
+    pub fn exclude(mut self, tag: impl Into<String>) -> Self {
+        self.exclude.push(tag.into());
+        self
+    }
+
+    /// Only hooks that were registered by one of the included modules or their
+    /// submodules pass, e.g. `"my_app"` for all hooks of the crate `my_app`.
+    /// Hooks without an origin don't pass. Can be called multiple times.
+    pub fn include_origin(mut self, module: impl Into<String>) -> Self {
+        self.include_origins.push(module.into());
+        self
+    }
+
+    /// Hooks that were registered by this module or its submodules don't pass,
+    /// e.g. to disable the hooks of a misbehaving dependency. Can be called
+    /// multiple times.
+    pub fn exclude_origin(mut self, module: impl Into<String>) -> Self {
+        self.exclude_origins.push(module.into());
+        self
+    }
+
+    /// Whether the hook with the given configuration should run.
+    pub fn matches(&self, config: &HookConfig) -> bool {
+        let has = |tags: &[String]| config.get_tags().iter().any(|t| tags.contains(t));
+        let from = |modules: &[String]| {
+            config
+                .get_origin()
+                .is_some_and(|origin| modules.iter().any(|m| is_within(origin, m)))
+        };
+        (self.include.is_empty() || has(&self.include))
+            && !has(&self.exclude)
+            && (self.include_origins.is_empty() || from(&self.include_origins))
+            && !from(&self.exclude_origins)
+    }
+}
+
+/// Whether `origin` is `module` or one of its submodules. Origins that are
+/// source files are mapped to modules through the directory of their package,
+/// e.g. `.../noisy-crate-0.1.0/src/exporter.rs` is `noisy_crate::exporter`.
+/// Files of the root package have no such directory and never match.
+fn is_within(origin: &str, module: &str) -> bool {
+    if !origin.ends_with(".rs") {
+        return origin
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+    }
+    let mut segments = module.split("::");
+    let package = segments.next().unwrap_or_default();
+    let components = origin.split(['/', '\\']).collect::<Vec<_>>();
+    let src = components
+        .windows(2)
+        .rposition(|w| is_package_dir(w[0], package) && w[1] == "src");
+    let mut path = match src {
+        Some(i) => components[i + 2..]
+            .iter()
+            .map(|c| c.trim_end_matches(".rs"))
+            .filter(|c| !matches!(*c, "lib" | "main" | "mod")),
+        None => return false,
+    };
+    segments.all(|segment| path.next() == Some(segment))
+}
+
+/// Whether `dir` is the directory of the package `name`, with or without a
+/// version, e.g. `noisy-crate-0.1.0` for `noisy_crate`.
+fn is_package_dir(dir: &str, name: &str) -> bool {
+    let dir = dir.replace('-', "_");
+    dir.strip_prefix(name).is_some_and(|rest| {
+        rest.is_empty()
+            || rest
+                .strip_prefix('_')
+                .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+    })
+}
+
+/// Splits a comma-separated list of tags and ignores empty entries.
+fn split_tags(val: &str) -> Vec<String> {
+    val.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let disk = HookConfig::new("fsync").tag("disk");
+        let telemetry = HookConfig::new("metrics").tag("network").tag("telemetry");
+        let untagged = HookConfig::new("untagged");
+
+        let all = TagFilter::new();
+        assert!(all.matches(&disk) && all.matches(&telemetry) && all.matches(&untagged));
+
+        let no_telemetry = TagFilter::new().exclude("telemetry");
+        assert!(no_telemetry.matches(&disk));
+        assert!(!no_telemetry.matches(&telemetry));
+        assert!(no_telemetry.matches(&untagged));
+
+        let only_disk = TagFilter::new().include("disk");
+        assert!(only_disk.matches(&disk));
+        assert!(!only_disk.matches(&telemetry));
+        assert!(!only_disk.matches(&untagged));
+
+        let network_without_telemetry = TagFilter::new().include("network").exclude("telemetry");
+        assert!(!network_without_telemetry.matches(&telemetry));
+    }
+
+    #[test]
+    fn test_matches_origin() {
+        let db = HookConfig::new("pool").origin("my_app::db");
+        let dependency = HookConfig::new("metrics").origin("noisy_crate::exporter");
+        let unknown = HookConfig::new("unknown");
+
+        let no_dependency = TagFilter::new().exclude_origin("noisy_crate");
+        assert!(no_dependency.matches(&db));
+        assert!(!no_dependency.matches(&dependency));
+        assert!(no_dependency.matches(&unknown));
+
+        let only_app = TagFilter::new().include_origin("my_app");
+        assert!(only_app.matches(&db));
+        assert!(!only_app.matches(&dependency));
+        assert!(!only_app.matches(&unknown));
+
+        // whole path segments only
+        assert!(!TagFilter::new().include_origin("my").matches(&db));
+    }
+
+    #[test]
+    fn test_matches_origin_file() {
+        let file = "/home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f/noisy-crate-0.1.0/src/exporter/mod.rs";
+        let dependency = HookConfig::new("metrics").origin(file);
+        let root = HookConfig::new("flush").origin("src/main.rs");
+
+        for module in ["noisy_crate", "noisy_crate::exporter"] {
+            assert!(!TagFilter::new().exclude_origin(module).matches(&dependency));
+        }
+        for module in ["noisy", "noisy_crate::db", "noisy_crate::exporter::http"] {
+            assert!(TagFilter::new().exclude_origin(module).matches(&dependency));
+        }
+        assert!(TagFilter::new().exclude_origin("my_app").matches(&root));
+        assert!(is_within("crates/my_app/src/db.rs", "my_app::db"));
+    }
+
+    #[test]
+    fn test_split_tags() {
+        assert_eq!(split_tags("disk, network,,"), ["disk", "network"]);
+        assert!(split_tags("").is_empty());
+    }
+}
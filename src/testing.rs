@@ -0,0 +1,163 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Support for integration tests that verify the shutdown behavior of a binary.
+//! UNIX only.
+//!
+//! [`ShutdownProbe`] spawns the binary as a child, sends it a signal and
+//! collects its output, so "run the binary, press CTRL+C and look at the
+//! output" becomes an automated test.
+
+use std::io::{self, BufRead, BufReader};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A spawned binary whose shutdown behavior is tested.
+///
+/// ## Example
+/// ```no_run
+/// use simple_on_shutdown::testing::ShutdownProbe;
+/// use std::process::Command;
+/// use std::time::Duration;
+///
+/// let mut probe = ShutdownProbe::spawn(Command::new("target/debug/my-server")).unwrap();
+/// assert!(probe.wait_for_line("listening", Duration::from_secs(5)));
+/// probe.signal(libc::SIGTERM).unwrap();
+/// let output = probe.finish(Duration::from_secs(5)).unwrap();
+/// assert!(output.contains("flushed database"));
+/// ```
+#[derive(Debug)]
+pub struct ShutdownProbe {
+    child: Child,
+    lines: Receiver<String>,
+    seen: Vec<String>,
+}
+
+/// Everything a [`ShutdownProbe`] observed.
+#[derive(Debug)]
+pub struct ProbeOutput {
+    /// Exit status of the binary.
+    pub status: ExitStatus,
+    /// Lines the binary printed to stdout and stderr, in the order they were
+    /// received.
+    pub lines: Vec<String>,
+}
+
+impl ProbeOutput {
+    /// Whether any line contains `pattern`.
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.lines.iter().any(|l| l.contains(pattern))
+    }
+}
+
+impl ShutdownProbe {
+    /// Spawns `cmd` with piped stdout and stderr.
+    pub fn spawn(mut cmd: Command) -> io::Result<Self> {
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let (sender, lines) = mpsc::channel();
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let stderr_sender = sender.clone();
+        thread::spawn(move || forward_lines(stdout, sender));
+        thread::spawn(move || forward_lines(stderr, stderr_sender));
+        Ok(Self {
+            child,
+            lines,
+            seen: Vec::new(),
+        })
+    }
+
+    /// Waits until the binary printed a line that contains `pattern`. Returns
+    /// `false` if that doesn't happen within `timeout`.
+    pub fn wait_for_line(&mut self, pattern: &str, timeout: Duration) -> bool {
+        if self.seen.iter().any(|l| l.contains(pattern)) {
+            return true;
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    let found = line.contains(pattern);
+                    self.seen.push(line);
+                    if found {
+                        return true;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+
+    /// Sends `sig` (e.g. `libc::SIGTERM`) to the binary.
+    pub fn signal(&self, sig: i32) -> io::Result<()> {
+        // SAFETY: plain syscall without pointers
+        if unsafe { libc::kill(self.child.id() as libc::pid_t, sig) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Waits until the binary exited and returns everything it printed. Kills it
+    /// if it is still running after `timeout` and returns an error.
+    pub fn finish(mut self, timeout: Duration) -> io::Result<ProbeOutput> {
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            if let Some(status) = self.child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                self.child.kill()?;
+                self.child.wait()?;
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the binary didn't exit in time",
+                ));
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        // the forwarding threads end with the pipes
+        self.seen.extend(self.lines.iter());
+        Ok(ProbeOutput {
+            status,
+            lines: self.seen,
+        })
+    }
+}
+
+/// Sends every line of `input` to `sender`.
+fn forward_lines(input: impl io::Read, sender: mpsc::Sender<String>) {
+    for line in BufReader::new(input).lines() {
+        let sent = line.map(|line| sender.send(line).is_ok());
+        if !matches!(sent, Ok(true)) {
+            return;
+        }
+    }
+}
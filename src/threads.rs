@@ -0,0 +1,141 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Stops registered threads at shutdown and joins them with a timeout.
+
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Time a thread gets to finish after its stop flag was set, if the hook has no
+/// deadline.
+pub const DEFAULT_JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the thread is checked for termination.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+impl Registry {
+    /// Registers a hook that stops a worker thread: it sets `stop` to `true` and
+    /// waits until the deadline of the hook (or [`DEFAULT_JOIN_TIMEOUT`]) for the
+    /// thread to finish. The thread is expected to check `stop` regularly.
+    ///
+    /// A thread that doesn't finish in time is left behind and reported as
+    /// [`HookOutcome::TimedOut`]; a thread that panicked as
+    /// [`HookOutcome::Failed`].
+    ///
+    /// ## Example
+    /// ```
+    /// use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let registry = Registry::new();
+    /// let stop = Arc::new(AtomicBool::new(false));
+    /// let stop_c = stop.clone();
+    /// let worker = std::thread::spawn(move || {
+    ///     while !stop_c.load(Ordering::SeqCst) {
+    ///         std::thread::sleep(std::time::Duration::from_millis(1));
+    ///     }
+    /// });
+    /// registry.register_thread(HookConfig::new("worker"), worker, stop);
+    /// assert!(registry.run(ShutdownReason::Exit, None).is_success());
+    /// ```
+    #[track_caller]
+    pub fn register_thread<T>(
+        &self,
+        config: HookConfig,
+        handle: JoinHandle<T>,
+        stop: Arc<AtomicBool>,
+    ) where
+        T: Send + 'static,
+    {
+        self.register_outcome(config, move |ctx| join(handle, &stop, ctx));
+    }
+}
+
+/// Body of the hook created by [`Registry::register_thread`].
+fn join<T>(handle: JoinHandle<T>, stop: &AtomicBool, ctx: &ShutdownContext) -> HookOutcome {
+    stop.store(true, Ordering::SeqCst);
+    let deadline = ctx
+        .deadline()
+        .unwrap_or_else(|| Instant::now() + DEFAULT_JOIN_TIMEOUT);
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            // the thread keeps running detached
+            return HookOutcome::TimedOut;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    match handle.join() {
+        Ok(_) => HookOutcome::Completed,
+        Err(_) => HookOutcome::Failed("thread panicked".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShutdownReason;
+
+    #[test]
+    fn test_threads_are_stopped() {
+        let registry = Registry::new();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_c = stop.clone();
+        let cooperative = thread::spawn(move || {
+            while !stop_c.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+        registry.register_thread(HookConfig::new("cooperative"), cooperative, stop);
+        let stubborn = thread::spawn(|| thread::sleep(Duration::from_secs(60)));
+        registry.register_thread(
+            HookConfig::new("stubborn").timeout(Duration::from_millis(20)),
+            stubborn,
+            Arc::new(AtomicBool::new(false)),
+        );
+        let panicking = thread::spawn(|| panic!("worker crashed"));
+        registry.register_thread(
+            HookConfig::new("panicking"),
+            panicking,
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        let report = registry.run(ShutdownReason::Exit, None);
+        let outcomes: Vec<_> = report
+            .results
+            .iter()
+            .map(|r| format!("{}: {}", r.name, r.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                "panicking: failed: thread panicked",
+                "stubborn: timed out",
+                "cooperative: completed"
+            ]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Zero-downtime self-upgrade: partial shutdown followed by `exec()` of the new
+//! binary. UNIX only.
+
+use crate::{Registry, ShutdownError, ShutdownReason, TagFilter};
+use std::fmt;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Tag of the hooks that run before the new binary is executed, see
+/// [`Registry::exec_upgrade`].
+pub const PRE_EXEC_TAG: &str = "pre-exec";
+
+/// Error of [`Registry::exec_upgrade`]. The current process keeps running.
+#[derive(Debug)]
+pub enum UpgradeError {
+    /// At least one of the [`PRE_EXEC_TAG`] hooks didn't finish successfully.
+    /// The new binary was not executed.
+    PreExecHooks(ShutdownError),
+    /// `exec()` failed.
+    Exec(io::Error),
+}
+
+impl fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PreExecHooks(err) => write!(f, "pre-exec hooks failed: {}", err),
+            Self::Exec(err) => write!(f, "exec() failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for UpgradeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::PreExecHooks(err) => Some(err),
+            Self::Exec(err) => Some(err),
+        }
+    }
+}
+
+impl Registry {
+    /// Replaces the current process with `cmd` for a zero-downtime upgrade:
+    /// 1. runs the hooks tagged with [`PRE_EXEC_TAG`], e.g. to stop accepting
+    ///    connections or to release a PID file
+    /// 2. disarms the registry (see [`Registry::set_enabled`]), as the new
+    ///    process takes over the remaining resources
+    /// 3. `exec()`s the new binary
+    ///
+    /// Resources that the new process inherits, like listening sockets, must not
+    /// have the `FD_CLOEXEC` flag. Only returns on failure; the registry is
+    /// re-armed then.
+    pub fn exec_upgrade(&self, cmd: &mut Command) -> UpgradeError {
+        let filter = TagFilter::new().include(PRE_EXEC_TAG);
+        let report = self.run_partial(ShutdownReason::Requested, None, &filter);
+        if let Err(err) = report.into_result() {
+            return UpgradeError::PreExecHooks(err);
+        }
+        self.set_enabled(false);
+        let err = cmd.exec();
+        self.set_enabled(true);
+        UpgradeError::Exec(err)
+    }
+}
+
+/// [`Registry::exec_upgrade`] for the [`crate::global`] registry.
+pub fn exec_upgrade(cmd: &mut Command) -> UpgradeError {
+    crate::global().exec_upgrade(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HookConfig;
+
+    #[test]
+    fn test_failed_exec_rearms() {
+        let registry = Registry::new();
+        registry.register(HookConfig::new("close listener").tag(PRE_EXEC_TAG), || {});
+        registry.register(HookConfig::new("flush db"), || {});
+        let err = registry.exec_upgrade(&mut Command::new("/does/not/exist"));
+        assert!(matches!(err, UpgradeError::Exec(_)), "{}", err);
+        assert!(registry.is_enabled());
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn test_failed_pre_exec_hook_prevents_exec() {
+        let registry = Registry::new();
+        registry.register_fallible(HookConfig::new("close listener").tag(PRE_EXEC_TAG), |_| {
+            Err("still in use")
+        });
+        let err = registry.exec_upgrade(&mut Command::new("/does/not/exist"));
+        assert!(matches!(err, UpgradeError::PreExecHooks(_)), "{}", err);
+    }
+}
@@ -0,0 +1,37 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Exit flow of WASI programs (`wasm32-wasip1`).
+//!
+//! WASI has no signals. A command module ends when `main()` returns or when it
+//! calls `proc_exit`. wasi-libc runs `atexit()` handlers in the first case and
+//! in `exit()`, so [`crate::install`] covers both `main()` returning and
+//! [`std::process::exit`]. Only a direct `proc_exit` skips them; use
+//! [`proc_exit`] of this module instead.
+
+/// Runs the hooks of the [`crate::global`] registry and ends the program with
+/// `code`. Replacement for `wasi::proc_exit()`, which ends the program without
+/// running any hooks.
+pub fn proc_exit(code: u32) -> ! {
+    crate::exit(code as i32)
+}
@@ -0,0 +1,262 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Coordinated shutdown of forked worker processes, e.g. of a prefork server:
+//! the workers are notified and awaited before the hooks of the parent run.
+//! UNIX only.
+
+use crate::sync::Mutex;
+use crate::{HookConfig, HookOutcome, Registry, ShutdownContext, DEFAULT_CHILD_GRACE};
+use std::os::unix::io::OwnedFd;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the workers are checked for termination.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a worker learns that it should shut down.
+#[derive(Debug)]
+pub enum WorkerChannel {
+    /// The worker receives the signal, e.g. `SIGTERM`.
+    Signal(i32),
+    /// The write end of a pipe whose read end the worker watches. It's closed,
+    /// so the worker reads EOF. Works without signal handlers in the worker.
+    Pipe(OwnedFd),
+}
+
+/// How a worker ended, see [`WorkerCoordinator::shut_down`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerExit {
+    /// The worker exited with the given status.
+    Exited(i32),
+    /// The worker was terminated by the given signal.
+    Signaled(i32),
+    /// The worker was still running at the deadline and got `SIGKILL`.
+    Killed,
+}
+
+#[derive(Debug)]
+struct Worker {
+    pid: libc::pid_t,
+    channel: WorkerChannel,
+}
+
+/// Worker processes that are shut down before the hooks of a [`Registry`], see
+/// [`Registry::coordinate_workers`]. Cheap to clone; all clones refer to the
+/// same workers.
+#[derive(Clone)]
+pub struct WorkerCoordinator {
+    workers: Arc<Mutex<Vec<Worker>>>,
+    /// Holds the hook. The parent registry only knows it weakly.
+    _registry: Arc<Registry>,
+}
+
+impl Registry {
+    /// Creates a coordinator for forked worker processes. When this registry is
+    /// triggered, a hook with `config` notifies all workers, waits until they
+    /// exited and only then the own hooks of this registry run. Workers that
+    /// are still running at the deadline of the hook (or after
+    /// [`DEFAULT_CHILD_GRACE`]) get `SIGKILL`.
+    ///
+    /// The hook fails if a worker had to be killed or didn't exit cleanly. A
+    /// worker exits cleanly with status `0` or by the signal of its
+    /// [`WorkerChannel::Signal`].
+    ///
+    /// The workers must be children of this process. The coordinator must be
+    /// kept alive, like a [`crate::ChildRegistry`].
+    pub fn coordinate_workers(&self, config: HookConfig) -> WorkerCoordinator {
+        let registry = Arc::new(Registry::new());
+        let coordinator = WorkerCoordinator {
+            workers: Arc::new(Mutex::new(Vec::new())),
+            _registry: registry.clone(),
+        };
+        let workers = coordinator.clone();
+        registry.register_outcome(config, move |ctx| shut_down(&workers, ctx));
+        self.add_child(Arc::downgrade(&registry));
+        coordinator
+    }
+}
+
+impl WorkerCoordinator {
+    /// Adds the worker with the process ID `pid`, e.g. the result of `fork()`.
+    pub fn add(&self, pid: u32, channel: WorkerChannel) {
+        let worker = Worker {
+            pid: pid as libc::pid_t,
+            channel,
+        };
+        self.workers.lock().push(worker);
+    }
+
+    /// Number of workers that were not shut down yet.
+    pub fn len(&self) -> usize {
+        self.workers.lock().len()
+    }
+
+    /// Whether there are no workers left.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Notifies all workers and waits until they exited or `deadline` passed.
+    /// Workers that are left get `SIGKILL`. Returns the process ID and the exit
+    /// of each worker, in the order they were added. Normally called by the
+    /// hook, see [`Registry::coordinate_workers`].
+    pub fn shut_down(&self, deadline: Instant) -> Vec<(u32, WorkerExit)> {
+        self.finish(deadline)
+            .into_iter()
+            .map(|(pid, exit, _)| (pid, exit))
+            .collect()
+    }
+
+    /// Like [`Self::shut_down`], additionally returns the signal that notified
+    /// each worker.
+    fn finish(&self, deadline: Instant) -> Vec<(u32, WorkerExit, Option<i32>)> {
+        let workers = core::mem::take(&mut *self.workers.lock());
+        let mut pending = Vec::with_capacity(workers.len());
+        for Worker { pid, channel } in workers {
+            let signal = match channel {
+                WorkerChannel::Signal(sig) => {
+                    // SAFETY: plain syscall without pointers
+                    unsafe { libc::kill(pid, sig) };
+                    Some(sig)
+                }
+                WorkerChannel::Pipe(fd) => {
+                    drop(fd);
+                    None
+                }
+            };
+            pending.push((pid, None, signal));
+        }
+        loop {
+            for (pid, exit, _) in pending.iter_mut().filter(|(_, exit, _)| exit.is_none()) {
+                *exit = try_wait(*pid);
+            }
+            if pending.iter().all(|(_, exit, _)| exit.is_some()) || Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        pending
+            .into_iter()
+            .map(|(pid, exit, signal)| {
+                let exit = exit.unwrap_or_else(|| {
+                    // SAFETY: plain syscalls; the zombie is reaped
+                    unsafe {
+                        libc::kill(pid, libc::SIGKILL);
+                        libc::waitpid(pid, core::ptr::null_mut(), 0);
+                    }
+                    WorkerExit::Killed
+                });
+                (pid as u32, exit, signal)
+            })
+            .collect()
+    }
+}
+
+/// Reaps the worker if it exited.
+fn try_wait(pid: libc::pid_t) -> Option<WorkerExit> {
+    let mut status = 0;
+    // SAFETY: `status` is a valid pointer
+    match unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } {
+        0 => None,
+        // not our child (anymore), nothing to wait for
+        -1 => Some(WorkerExit::Exited(-1)),
+        _ if libc::WIFSIGNALED(status) => Some(WorkerExit::Signaled(libc::WTERMSIG(status))),
+        _ => Some(WorkerExit::Exited(libc::WEXITSTATUS(status))),
+    }
+}
+
+/// Body of the hook created by [`Registry::coordinate_workers`].
+fn shut_down(coordinator: &WorkerCoordinator, ctx: &ShutdownContext) -> HookOutcome {
+    let deadline = ctx
+        .deadline()
+        .unwrap_or_else(|| Instant::now() + DEFAULT_CHILD_GRACE);
+    let problems: Vec<_> = coordinator
+        .finish(deadline)
+        .into_iter()
+        .filter_map(|(pid, exit, signal)| match exit {
+            WorkerExit::Exited(0) => None,
+            WorkerExit::Signaled(sig) if Some(sig) == signal => None,
+            WorkerExit::Exited(status) => Some(format!("worker {} exited with {}", pid, status)),
+            WorkerExit::Signaled(sig) => Some(format!("worker {} got signal {}", pid, sig)),
+            WorkerExit::Killed => Some(format!("worker {} had to be killed", pid)),
+        })
+        .collect();
+    if problems.is_empty() {
+        HookOutcome::Completed
+    } else {
+        HookOutcome::Failed(problems.join(", ").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{shell_command, ShutdownReason};
+    use std::process::Stdio;
+
+    #[test]
+    // the coordinator reaps the workers
+    #[allow(clippy::zombie_processes)]
+    fn test_workers_shut_down_before_parent() {
+        let parent = Registry::new();
+        let workers_left = Arc::new(Mutex::new(None));
+        let workers_left_c = workers_left.clone();
+        let coordinator = parent.coordinate_workers(HookConfig::new("workers"));
+        let coordinator_c = coordinator.clone();
+        parent.register(HookConfig::new("parent"), move || {
+            *workers_left_c.lock() = Some(coordinator_c.len())
+        });
+
+        let sleeping = shell_command("sleep 10").spawn().unwrap();
+        coordinator.add(sleeping.id(), WorkerChannel::Signal(libc::SIGTERM));
+        let mut reading = shell_command("read line; exit 3")
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pipe = OwnedFd::from(reading.stdin.take().unwrap());
+        coordinator.add(reading.id(), WorkerChannel::Pipe(pipe));
+        let ignoring = shell_command("trap '' TERM; sleep 10 & wait")
+            .spawn()
+            .unwrap();
+        // give the shell time to install the trap
+        thread::sleep(Duration::from_millis(100));
+        coordinator.add(ignoring.id(), WorkerChannel::Signal(libc::SIGTERM));
+
+        let begin = Instant::now();
+        let report = parent.run(ShutdownReason::Exit, Some(Duration::from_millis(500)));
+        assert!(begin.elapsed() < Duration::from_secs(5));
+        assert_eq!(*workers_left.lock(), Some(0));
+        let names: Vec<_> = report.results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["workers", "parent"]);
+        assert_eq!(
+            report.results[0].outcome.to_string(),
+            format!(
+                "failed: worker {} exited with 3, worker {} had to be killed",
+                reading.id(),
+                ignoring.id()
+            )
+        );
+    }
+}
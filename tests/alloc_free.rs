@@ -0,0 +1,69 @@
+//! Verifies that the trigger path of `PreallocatedRegistry` doesn't touch the heap.
+
+use simple_on_shutdown::PreallocatedRegistry;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts (de)allocations of the current thread.
+struct CountingAllocator;
+
+thread_local! {
+    static HEAP_OPS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        HEAP_OPS.with(|c| c.set(c.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        HEAP_OPS.with(|c| c.set(c.get() + 1));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_run_does_not_allocate() {
+    let registry = PreallocatedRegistry::with_capacity(8);
+    let counter = Arc::new(AtomicUsize::new(0));
+    for name in ["a", "b", "c"] {
+        let counter = counter.clone();
+        registry
+            .register(name, move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+    }
+    // the hooks still own their clones of the Arc; no deallocation when they run
+
+    let before = HEAP_OPS.with(Cell::get);
+    registry.run();
+    let after = HEAP_OPS.with(Cell::get);
+
+    assert_eq!(after - before, 0);
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+    registry.with_results(|results| assert_eq!(results.len(), 3));
+}
+
+#[test]
+fn test_run_of_full_registry_does_not_allocate() {
+    let registry = PreallocatedRegistry::with_capacity(5);
+    let mut registered = 0;
+    while registry.register("hook", || {}).is_ok() {
+        registered += 1;
+    }
+    assert_eq!(registered, 5);
+
+    let before = HEAP_OPS.with(Cell::get);
+    registry.run();
+    let after = HEAP_OPS.with(Cell::get);
+
+    assert_eq!(after - before, 0);
+    registry.with_results(|results| assert_eq!(results.len(), 5));
+}
@@ -0,0 +1,57 @@
+/*
+MIT License
+
+Copyright (c) 2021 Philipp Schuster
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+//! Fallible hooks can return `anyhow::Error` and `eyre::Report` directly. Their
+//! context chains end up in the report.
+
+use simple_on_shutdown::{HookConfig, Registry, ShutdownReason};
+
+#[test]
+fn test_anyhow_context_chain() {
+    use anyhow::Context;
+
+    let registry = Registry::new();
+    registry.register_fallible(HookConfig::new("flush"), |_ctx| {
+        Err(anyhow::anyhow!("disk full")).context("flushing cache")
+    });
+    let report = registry.run(ShutdownReason::Exit, None);
+    assert_eq!(
+        report.results[0].outcome.to_string(),
+        "failed: flushing cache: disk full"
+    );
+}
+
+#[test]
+fn test_eyre_context_chain() {
+    use eyre::WrapErr;
+
+    let registry = Registry::new();
+    registry.register_fallible(HookConfig::new("flush"), |_ctx| {
+        Err(eyre::eyre!("disk full")).wrap_err("flushing cache")
+    });
+    let report = registry.run(ShutdownReason::Exit, None);
+    assert_eq!(
+        report.results[0].outcome.to_string(),
+        "failed: flushing cache: disk full"
+    );
+}
@@ -0,0 +1,30 @@
+//! The macros must not depend on the prelude or the imports of the caller, e.g.
+//! in `no_std` crates or crates that shadow `Box`.
+
+#![no_implicit_prelude]
+
+use ::std::cell::Cell;
+use ::std::clone::Clone;
+use ::std::rc::Rc;
+
+/// Shadows the `Box` of the prelude.
+#[allow(dead_code)]
+struct Box;
+
+#[test]
+fn test_macros_without_prelude() {
+    let called = Rc::new(Cell::new(0));
+    {
+        let c = called.clone();
+        ::simple_on_shutdown::on_shutdown!(move || c.set(c.get() + 1));
+        ::simple_on_shutdown::on_shutdown!({});
+        let c = called.clone();
+        let _guard = ::simple_on_shutdown::on_shutdown_expr!(c.set(c.get() + 1));
+        let c = called.clone();
+        ::simple_on_shutdown::on_shutdown_sized!(<= 8 bytes, move || c.set(c.get() + 1));
+    }
+    ::std::assert_eq!(called.get(), 3);
+
+    let config = ::simple_on_shutdown::hook_config!("flush");
+    ::std::assert_eq!(config.name(), "flush");
+}
@@ -0,0 +1,43 @@
+//! The `minimal` feature must reject every feature that adds dependencies or
+//! `std`. New features are checked here, so the guard in `lib.rs` can't be
+//! forgotten. That the guard actually fails the build is checked by the
+//! `minimal` CI job.
+
+const MANIFEST: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"));
+const LIB: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.rs"));
+
+/// Names of the features in the `[features]` table.
+fn features() -> Vec<&'static str> {
+    MANIFEST
+        .lines()
+        .skip_while(|l| *l != "[features]")
+        .skip(1)
+        .take_while(|l| !l.starts_with('['))
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split_once(" = "))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+#[test]
+fn test_minimal_rejects_features_with_dependencies() {
+    let guard = LIB
+        .split("compile_error!(\n    \"feature `minimal`")
+        .next()
+        .unwrap()
+        .rsplit("#[cfg(all(")
+        .next()
+        .unwrap();
+    let features = features();
+    assert!(features.contains(&"minimal"));
+    for name in features {
+        if matches!(name, "default" | "minimal") {
+            continue;
+        }
+        assert!(
+            guard.contains(&format!("feature = \"{}\"", name)),
+            "feature `{}` must be rejected by `minimal` in lib.rs",
+            name
+        );
+    }
+}
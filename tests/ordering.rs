@@ -0,0 +1,116 @@
+//! The execution order is part of the API contract, see the docs of `on_shutdown!`.
+
+use simple_on_shutdown::{
+    on_shutdown, ExecutionOrder, HookConfig, OnShutdownCallback, Registry, ShutdownAnchor,
+    ShutdownReason,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+type Order = Rc<RefCell<Vec<&'static str>>>;
+
+fn push(order: &Order, name: &'static str) -> impl FnOnce() + 'static {
+    let order = order.clone();
+    move || order.borrow_mut().push(name)
+}
+
+#[test]
+fn test_macros_in_one_scope_run_in_reverse_order() {
+    let order = Order::default();
+    {
+        let first = push(&order, "first");
+        let second = push(&order, "second");
+        let third = push(&order, "third");
+        on_shutdown!(first);
+        on_shutdown!(second);
+        on_shutdown!(third);
+    }
+    assert_eq!(*order.borrow(), ["third", "second", "first"]);
+}
+
+#[test]
+fn test_inner_scopes_run_first() {
+    let order = Order::default();
+    {
+        let outer = push(&order, "outer");
+        on_shutdown!(outer);
+        {
+            let inner = push(&order, "inner");
+            on_shutdown!(inner);
+        }
+        order.borrow_mut().push("between");
+    }
+    assert_eq!(*order.borrow(), ["inner", "between", "outer"]);
+}
+
+#[test]
+fn test_guards_in_struct_fields_run_in_declaration_order() {
+    struct Service {
+        _first: OnShutdownCallback,
+        _second: OnShutdownCallback,
+    }
+
+    let order = Order::default();
+    let service = Service {
+        _first: push(&order, "first").into(),
+        _second: push(&order, "second").into(),
+    };
+    drop(service);
+    assert_eq!(*order.borrow(), ["first", "second"]);
+}
+
+#[test]
+fn test_anchor_runs_in_reverse_order() {
+    let order = Order::default();
+    let anchor = ShutdownAnchor::new();
+    anchor.attach(push(&order, "first"));
+    anchor.adopt(push(&order, "second").into());
+    anchor.attach(push(&order, "third"));
+    drop(anchor);
+    assert_eq!(*order.borrow(), ["third", "second", "first"]);
+}
+
+/// Registers hooks `a`, `b`, `c` with the given priorities and checks that
+/// `execution_order()` predicts the actual order.
+fn check_registry(order: ExecutionOrder, priorities: [i32; 3], expected: [&str; 3]) {
+    let registry = Registry::new();
+    registry.set_execution_order(order);
+    let executed = Arc::new(Mutex::new(Vec::new()));
+    for (name, priority) in ["a", "b", "c"].iter().copied().zip(priorities) {
+        let executed = executed.clone();
+        registry.register(HookConfig::new(name).priority(priority), move || {
+            executed.lock().unwrap().push(name)
+        });
+    }
+    assert_eq!(registry.execution_order(), expected);
+    registry.run(ShutdownReason::Exit, None);
+    assert_eq!(*executed.lock().unwrap(), expected);
+}
+
+#[test]
+fn test_registry_order() {
+    check_registry(ExecutionOrder::Lifo, [0, 0, 0], ["c", "b", "a"]);
+    check_registry(ExecutionOrder::Fifo, [0, 0, 0], ["a", "b", "c"]);
+    check_registry(ExecutionOrder::Priority, [1, 5, 1], ["b", "c", "a"]);
+}
+
+#[test]
+fn test_children_run_before_parent() {
+    let registry = Registry::new();
+    registry.register(HookConfig::new("parent"), || {});
+    let older = registry.child();
+    older.register(HookConfig::new("older"), || {});
+    let younger = registry.child();
+    younger.register(HookConfig::new("younger"), || {});
+
+    let expected = ["younger", "older", "parent"];
+    assert_eq!(registry.execution_order(), expected);
+    let report = registry.run(ShutdownReason::Exit, None);
+    let executed = report
+        .results
+        .iter()
+        .map(|r| r.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(executed, expected);
+}
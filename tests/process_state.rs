@@ -0,0 +1,48 @@
+//! Hooks that depend on the working directory. The working directory belongs
+//! to the process, so these tests run in their own binary.
+
+use simple_on_shutdown::{HookConfig, ProcessState, Registry, ShutdownReason};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_working_dir_and_restore() {
+    let start = std::env::current_dir().unwrap();
+    let temp = std::env::temp_dir().canonicalize().unwrap();
+    let registry = Registry::new();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_c = seen.clone();
+    registry.register_process_state_restore(HookConfig::new("restore"));
+    registry.register(HookConfig::new("in start"), move || {
+        seen_c
+            .lock()
+            .unwrap()
+            .push(std::env::current_dir().unwrap())
+    });
+    let seen_c = seen.clone();
+    registry.register(HookConfig::new("in temp").working_dir(&temp), move || {
+        seen_c
+            .lock()
+            .unwrap()
+            .push(std::env::current_dir().unwrap())
+    });
+    registry.register(
+        HookConfig::new("missing").working_dir(temp.join("does-not-exist")),
+        || unreachable!(),
+    );
+
+    std::env::set_current_dir(&temp).unwrap();
+    std::env::set_current_dir(&start).unwrap();
+    let report = registry.run(ShutdownReason::Exit, None);
+    let failures = report.failures().map(|r| &r.name).collect::<Vec<_>>();
+    assert_eq!(failures, ["missing"]);
+    // the working directory is left after each hook
+    assert_eq!(*seen.lock().unwrap(), [temp.clone(), start.clone()]);
+    assert_eq!(std::env::current_dir().unwrap(), start);
+
+    // restored after the hooks changed it
+    let state = ProcessState::capture();
+    assert_eq!(state.cwd(), Some(start.as_path()));
+    std::env::set_current_dir(&temp).unwrap();
+    state.restore().unwrap();
+    assert_eq!(std::env::current_dir().unwrap(), start);
+}
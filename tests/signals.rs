@@ -0,0 +1,129 @@
+//! Sends signals to the `signal_hooks` example and verifies that the hooks ran.
+
+#![cfg(all(unix, feature = "signals", feature = "testing"))]
+
+use simple_on_shutdown::testing::ShutdownProbe;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// `cargo test` builds the examples next to the integration tests.
+fn example(name: &str) -> Command {
+    let mut path = PathBuf::from(std::env::current_exe().unwrap().parent().unwrap());
+    path.pop();
+    path.push("examples");
+    path.push(name);
+    Command::new(path)
+}
+
+fn assert_hooks_run_on(sig: i32) {
+    let mut probe = ShutdownProbe::spawn(example("signal_hooks")).unwrap();
+    assert!(probe.wait_for_line("ready", Duration::from_secs(10)));
+    probe.signal(sig).unwrap();
+    let output = probe.finish(Duration::from_secs(10)).unwrap();
+    assert!(
+        output.contains(&format!("flush hook ran: Signal({})", sig)),
+        "{:?}",
+        output.lines
+    );
+    assert_eq!(output.status.code(), Some(128 + sig));
+}
+
+#[test]
+fn test_reload_hooks_run_on_every_sighup() {
+    let mut probe = ShutdownProbe::spawn(example("signal_hooks")).unwrap();
+    assert!(probe.wait_for_line("ready", Duration::from_secs(10)));
+    for i in 1..=2 {
+        probe.signal(libc::SIGHUP).unwrap();
+        assert!(probe.wait_for_line(&format!("reload {}", i), Duration::from_secs(10)));
+    }
+    probe.signal(libc::SIGTERM).unwrap();
+    let output = probe.finish(Duration::from_secs(10)).unwrap();
+    assert_eq!(output.status.code(), Some(128 + libc::SIGTERM));
+}
+
+#[test]
+fn test_diagnostic_hooks_run_on_sigusr1() {
+    let mut probe = ShutdownProbe::spawn(example("signal_hooks")).unwrap();
+    assert!(probe.wait_for_line("ready", Duration::from_secs(10)));
+    probe.signal(libc::SIGUSR1).unwrap();
+    assert!(probe.wait_for_line("state: 1 hooks", Duration::from_secs(10)));
+    probe.signal(libc::SIGTERM).unwrap();
+    let output = probe.finish(Duration::from_secs(10)).unwrap();
+    assert!(output.contains("flush hook ran"), "{:?}", output.lines);
+}
+
+#[test]
+fn test_second_signal_forces_exit() {
+    let mut cmd = example("signal_hooks");
+    cmd.env("SLOW_HOOK", "1");
+    let mut probe = ShutdownProbe::spawn(cmd).unwrap();
+    assert!(probe.wait_for_line("ready", Duration::from_secs(10)));
+    probe.signal(libc::SIGTERM).unwrap();
+    assert!(probe.wait_for_line("slow hook started", Duration::from_secs(10)));
+    probe.signal(libc::SIGINT).unwrap();
+    let output = probe.finish(Duration::from_secs(10)).unwrap();
+    assert!(
+        output.contains("hooks that didn't finish: slow, flush"),
+        "{:?}",
+        output.lines
+    );
+    // the exit code of the first signal wins
+    assert_eq!(output.status.code(), Some(128 + libc::SIGTERM));
+}
+
+#[test]
+fn test_repeated_signals_are_logged() {
+    let mut cmd = example("signal_hooks");
+    cmd.env("SLOW_HOOK", "1").env("LOG_REPEATED_SIGNALS", "1");
+    let mut probe = ShutdownProbe::spawn(cmd).unwrap();
+    assert!(probe.wait_for_line("ready", Duration::from_secs(10)));
+    probe.signal(libc::SIGTERM).unwrap();
+    assert!(probe.wait_for_line("slow hook started", Duration::from_secs(10)));
+    for _ in 0..5 {
+        probe.signal(libc::SIGTERM).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    // the aggregated message comes after the rate limit
+    std::thread::sleep(Duration::from_millis(1500));
+    probe.signal(libc::SIGKILL).unwrap();
+    let output = probe.finish(Duration::from_secs(10)).unwrap();
+
+    let counts: Vec<usize> = output
+        .lines
+        .iter()
+        .filter_map(|l| l.strip_prefix("shutdown: received "))
+        .map(|l| l.trim_end_matches(" additional SIGTERM").parse().unwrap())
+        .collect();
+    assert!(counts.len() >= 2, "{:?}", output.lines);
+    assert_eq!(counts.iter().sum::<usize>(), 5, "{:?}", output.lines);
+    // not force-exited
+    assert_eq!(output.status.code(), None);
+}
+
+#[test]
+fn test_hooks_run_on_sigterm() {
+    assert_hooks_run_on(libc::SIGTERM);
+}
+
+#[test]
+fn test_hooks_run_on_sigint() {
+    assert_hooks_run_on(libc::SIGINT);
+}
+
+#[test]
+fn test_signal_during_exit_keeps_exit_code() {
+    let mut cmd = example("signal_hooks");
+    cmd.env("EXIT_CODE", "3");
+    let mut probe = ShutdownProbe::spawn(cmd).unwrap();
+    assert!(probe.wait_for_line("exit hooks started", Duration::from_secs(10)));
+    probe.signal(libc::SIGTERM).unwrap();
+    let output = probe.finish(Duration::from_secs(10)).unwrap();
+    let runs = output
+        .lines
+        .iter()
+        .filter(|l| l.contains("flush hook ran"))
+        .collect::<Vec<_>>();
+    assert_eq!(runs, ["flush hook ran: Exit"], "{:?}", output.lines);
+    assert_eq!(output.status.code(), Some(3));
+}